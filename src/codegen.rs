@@ -1,318 +1,3970 @@
 use std::fs::File;
-use std::io::Write;
+use std::io::{self, Write};
+use std::rc::Rc;
+use std::cell::RefCell;
+use crate::error::CompileError;
+use crate::lexer::{is_initial, is_subsequent, CHAR_NAMES};
 use crate::parser::*;
 
-pub fn gen_asm(parser: Parser, dest_path: String) {
-    CodeGen::new(dest_path).gen_asm(parser);
+// every value is a 64-bit word whose low 3 bits tell its type.
+//   xxx...x000  fixnum (the integer shifted left by 3)
+//   ptr....001  pair
+//   ptr....010  procedure
+//   ptr....011  other heap object
+//   xxx...x111  immediate: #f = 0x07, #t = 0x0f, '() = 0x17, unspecified = 0x1f, char = (code << 8) | 0x2f
+const TAG_MASK: i64 = 7;
+const FIXNUM_TAG: i64 = 0;
+const FIXNUM_SHIFT: i64 = 3;
+const PAIR_TAG: i64 = 1;
+const PROC_TAG: i64 = 2;
+const OBJ_TAG: i64 = 3;
+const FALSE: i64 = 0x07;
+const TRUE: i64 = 0x0f;
+const NIL: i64 = 0x17;
+const VOID: i64 = 0x1f;
+const CHAR_TAG: i64 = 0x2f;
+const CHAR_SHIFT: i64 = 8;
+
+// a pair is two words [car, cdr]. every other heap object begins with a header word
+// (length << 8) | (type << 3) | 6, which no value can look like. objects of a type below
+// RAW_TYPE hold `length` values after the header, the others hold `length` raw bytes.
+const HEADER_TAG: i64 = 6;
+const FORWARD: i64 = HEADER_TAG;
+const CLOSURE_TYPE: i64 = 1;
+const BOX_TYPE: i64 = 2;
+const SYMBOL_TYPE: i64 = 3;
+const VECTOR_TYPE: i64 = 4;
+const RAW_TYPE: i64 = 8;
+const STRING_TYPE: i64 = RAW_TYPE;
+const BYTEVECTOR_TYPE: i64 = RAW_TYPE + 1;
+const BIGNUM_TYPE: i64 = RAW_TYPE + 2;
+
+const HEAP_SIZE: i64 = 1 << 20;
+
+// the longest vector, bytevector or string, whose size in bytes cannot overflow
+const MAX_LENGTH: i64 = 1 << 48;
+
+// the exit status of a program stopped by an error
+const ERROR_STATUS: i64 = 70;
+
+fn header(type_: i64, len: usize) -> i64 {
+    ((len as i64) << 8) | (type_ << 3) | HEADER_TAG
+}
+
+// the bytes escaped in written strings and the characters after their backslash
+const STRING_ESCAPES: [(u8, u8); 5] = [(b'"', b'"'), (b'\\', b'\\'), (b'\n', b'n'), (b'\t', b't'), (b'\r', b'r')];
+
+// escapes a string for the .ascii directive, with a byte for each character
+fn escape(string: &str) -> String {
+    let mut escaped = String::new();
+    for byte in string.chars().map(|c| c as u8) {
+        match byte {
+            b'"' => escaped.push_str("\\\""),
+            b'\\' => escaped.push_str("\\\\"),
+            0x20..=0x7e => escaped.push(byte as char),
+            _ => escaped.push_str(&format!("\\{:03o}", byte)),
+        }
+    }
+    escaped
+}
+
+// the label of a global variable. letters and digits are kept and every other byte is written
+// as _ and two hex digits, so that each identifier has its own label. the labels of global
+// variables begin with g_ and those of the runtime with rt_, so they do not clash.
+fn mangle(name: &str) -> String {
+    let mut label = String::from("g_");
+    for byte in name.bytes() {
+        if byte.is_ascii_alphanumeric() {
+            label.push(byte as char);
+        } else {
+            label.push_str(&format!("_{:02x}", byte));
+        }
+    }
+    label
+}
+
+pub fn gen_asm(parser: Parser, dest_path: String) -> Result<(), CompileError> {
+    let dest = match File::create(&dest_path) {
+        Ok(dest) => dest,
+        Err(err) => return Err(CompileError::new(format!("could not create {}: {}", dest_path, err), None)),
+    };
+    match CodeGen::new(dest).gen_asm(parser) {
+        Ok(()) => Ok(()),
+        Err(err) => Err(CompileError::new(format!("could not write {}: {}", dest_path, err), None)),
+    }
 }
 
 struct CodeGen {
     dest: File,
     lambda_num: usize,
     if_num: usize,
-    call_num: usize,
+    args_num: usize,
+    strings: Vec<String>,
+    symbols: Vec<String>,
+    data: Vec<String>,
+    routine: String,
 }
 
 impl CodeGen {
-    fn new(dest_path: String) -> Self {
+    fn new(dest: File) -> Self {
         CodeGen {
-            dest: File::create(dest_path).unwrap(),
+            dest,
             lambda_num: 0,
             if_num: 0,
-            call_num: 0,
+            args_num: 0,
+            strings: Vec::new(),
+            symbols: Vec::new(),
+            data: Vec::new(),
+            routine: String::new(),
         }
     }
 
-    fn gen_asm(&mut self, parser: Parser) {
-        writeln!(self.dest, ".intel_syntax noprefix").unwrap();
-        writeln!(self.dest, ".global main").unwrap();
+    fn gen_asm(&mut self, parser: Parser) -> io::Result<()> {
+        writeln!(self.dest, ".intel_syntax noprefix")?;
+        writeln!(self.dest, ".global main")?;
 
-        writeln!(self.dest, ".data").unwrap();
+        writeln!(self.dest, ".data")?;
 
+        // the globals are roots of the garbage collector
+        writeln!(self.dest, "rt_globals_begin:")?;
         for global in parser.env.globals() {
-            writeln!(self.dest, "{}:", global.0).unwrap();
-            writeln!(self.dest, "    .zero 8").unwrap();
-        }
-
-        writeln!(self.dest, ".text").unwrap();
-
-        writeln!(self.dest, "cons:").unwrap();
-        writeln!(self.dest, "    mov rdi, 2").unwrap();
-        writeln!(self.dest, "    mov rsi, 8").unwrap();
-        writeln!(self.dest, "    call calloc").unwrap();
-        writeln!(self.dest, "    mov rdi, QWORD PTR [rsp+8]").unwrap();
-        writeln!(self.dest, "    mov rsi, QWORD PTR [rsp+16]").unwrap();
-        writeln!(self.dest, "    mov QWORD PTR [rax], rdi").unwrap();
-        writeln!(self.dest, "    mov QWORD PTR [rax+8], rsi").unwrap();
-        writeln!(self.dest, "    ret").unwrap();
-        
-        writeln!(self.dest, "car:").unwrap();
-        writeln!(self.dest, "    mov rax, QWORD PTR [rsp+8]").unwrap();
-        writeln!(self.dest, "    mov rax, QWORD PTR [rax]").unwrap();
-        writeln!(self.dest, "    ret").unwrap();
-        
-        writeln!(self.dest, "cdr:").unwrap();
-        writeln!(self.dest, "    mov rax, QWORD PTR [rsp+8]").unwrap();
-        writeln!(self.dest, "    mov rax, QWORD PTR [rax+8]").unwrap();
-        writeln!(self.dest, "    ret").unwrap();
-
-        writeln!(self.dest, "equal:").unwrap();
-        writeln!(self.dest, "    mov rax, QWORD PTR [rsp+8]").unwrap();
-        writeln!(self.dest, "    mov rdi, QWORD PTR [rsp+16]").unwrap();
-        writeln!(self.dest, "    cmp rax, rdi").unwrap();
-        writeln!(self.dest, "    sete al").unwrap();
-        writeln!(self.dest, "    movzb rax, al").unwrap();
-        writeln!(self.dest, "    ret").unwrap();
-
-        writeln!(self.dest, "neq:").unwrap();
-        writeln!(self.dest, "    mov rax, QWORD PTR [rsp+8]").unwrap();
-        writeln!(self.dest, "    mov rdi, QWORD PTR [rsp+16]").unwrap();
-        writeln!(self.dest, "    cmp rax, rdi").unwrap();
-        writeln!(self.dest, "    setne al").unwrap();
-        writeln!(self.dest, "    movzb rax, al").unwrap();
-        writeln!(self.dest, "    ret").unwrap();
-
-        writeln!(self.dest, "lth:").unwrap();
-        writeln!(self.dest, "    mov rax, QWORD PTR [rsp+8]").unwrap();
-        writeln!(self.dest, "    mov rdi, QWORD PTR [rsp+16]").unwrap();
-        writeln!(self.dest, "    cmp rax, rdi").unwrap();
-        writeln!(self.dest, "    setl al").unwrap();
-        writeln!(self.dest, "    movzb rax, al").unwrap();
-        writeln!(self.dest, "    ret").unwrap();
-
-        writeln!(self.dest, "leq:").unwrap();
-        writeln!(self.dest, "    mov rax, QWORD PTR [rsp+8]").unwrap();
-        writeln!(self.dest, "    mov rdi, QWORD PTR [rsp+16]").unwrap();
-        writeln!(self.dest, "    cmp rax, rdi").unwrap();
-        writeln!(self.dest, "    setle al").unwrap();
-        writeln!(self.dest, "    movzb rax, al").unwrap();
-        writeln!(self.dest, "    ret").unwrap();
-
-        writeln!(self.dest, "gth:").unwrap();
-        writeln!(self.dest, "    mov rax, QWORD PTR [rsp+8]").unwrap();
-        writeln!(self.dest, "    mov rdi, QWORD PTR [rsp+16]").unwrap();
-        writeln!(self.dest, "    cmp rax, rdi").unwrap();
-        writeln!(self.dest, "    setg al").unwrap();
-        writeln!(self.dest, "    movzb rax, al").unwrap();
-        writeln!(self.dest, "    ret").unwrap();
-
-        writeln!(self.dest, "geq:").unwrap();
-        writeln!(self.dest, "    mov rax, QWORD PTR [rsp+8]").unwrap();
-        writeln!(self.dest, "    mov rdi, QWORD PTR [rsp+16]").unwrap();
-        writeln!(self.dest, "    cmp rax, rdi").unwrap();
-        writeln!(self.dest, "    setge al").unwrap();
-        writeln!(self.dest, "    movzb rax, al").unwrap();
-        writeln!(self.dest, "    ret").unwrap();
-
-        writeln!(self.dest, "add:").unwrap();
-        writeln!(self.dest, "    mov rax, 0").unwrap();
-        writeln!(self.dest, "    add rax, QWORD PTR [rsp+8]").unwrap();
-        writeln!(self.dest, "    add rax, QWORD PTR [rsp+16]").unwrap();
-        writeln!(self.dest, "    ret").unwrap();
-
-        writeln!(self.dest, "sub:").unwrap();
-        writeln!(self.dest, "    mov rax, QWORD PTR [rsp+8]").unwrap();
-        writeln!(self.dest, "    sub rax, QWORD PTR [rsp+16]").unwrap();
-        writeln!(self.dest, "    ret").unwrap();
-
-        writeln!(self.dest, "div:").unwrap();
-        writeln!(self.dest, "    mov rax, QWORD PTR [rsp+8]").unwrap();
-        writeln!(self.dest, "    cqo").unwrap();
-        writeln!(self.dest, "    idiv QWORD PTR [rsp+16]").unwrap();
-        writeln!(self.dest, "    ret").unwrap();
-
-        writeln!(self.dest, "rem:").unwrap();
-        writeln!(self.dest, "    mov rax, QWORD PTR [rsp+8]").unwrap();
-        writeln!(self.dest, "    cqo").unwrap();
-        writeln!(self.dest, "    idiv QWORD PTR [rsp+16]").unwrap();
-        writeln!(self.dest, "    mov rax, rdi").unwrap();
-        writeln!(self.dest, "    ret").unwrap();
+            writeln!(self.dest, "{}:", mangle(&global.0))?;
+            writeln!(self.dest, "    .zero 8")?;
+        }
+        // the interned symbols, which begin with the symbols in the source
+        writeln!(self.dest, "rt_symbol_table:")?;
+        writeln!(self.dest, "    .quad symbol_list")?;
+        writeln!(self.dest, "rt_globals_end:")?;
+
+        for name in ["rt_heap_start", "rt_heap_ptr", "rt_heap_end", "rt_heap_size", "rt_stack_base", "rt_gc_stack_top", "rt_print_stream",
+                     "rt_bignum_x", "rt_bignum_y"] {
+            writeln!(self.dest, "{}:", name)?;
+            writeln!(self.dest, "    .zero 8")?;
+        }
+
+        // the messages of the errors are formats of printf
+        for (label, text) in [("type", "wrong type argument"), ("range", "index out of range"),
+                              ("byte", "value out of range"), ("constant", "attempt to modify a constant"),
+                              ("call", "attempt to call a non-procedure"), ("zero", "division by zero"),
+                              ("circular", "wrong type argument, circular list"),
+                              ("inexact", "quotient is not an integer"),
+                              ("memory", "out of memory"),
+                              ("arity", "wrong number of arguments, expected %ld, given %ld"),
+                              ("arity_rest", "wrong number of arguments, expected at least %ld, given %ld"),
+                              ("prefix", "error: "), ("name", ": ")] {
+            writeln!(self.dest, "rt_{}_error_msg:", label)?;
+            writeln!(self.dest, "    .asciz \"{}\"", text)?;
+        }
+
+        for (label, text) in [("long", "%ld"), ("true", "#t"), ("false", "#f"), ("nil", "()"), ("void", "#<void>"),
+                              ("proc", "#<procedure>"), ("unknown", "#<unknown>"), ("dot", " . "), ("bytevector", "#u8("),
+                              ("char", "#\\\\"), ("char_hex", "#\\\\x%lx")] {
+            writeln!(self.dest, "rt_print_{}_str:", label)?;
+            writeln!(self.dest, "    .asciz \"{}\"", text)?;
+        }
+        for (name, _) in CHAR_NAMES {
+            writeln!(self.dest, "rt_print_char_{}_str:", name)?;
+            writeln!(self.dest, "    .asciz \"#\\\\{}\"", name)?;
+        }
+
+        // whether a symbol can begin with each byte, in bit 1, and have it after, in bit 0.
+        // the other symbols are written between bars.
+        writeln!(self.dest, "rt_symbol_chars:")?;
+        let flags: Vec<String> = (0..=255u8).map(|byte| {
+            let c = byte as char;
+            let initial = is_initial(c) && !c.is_ascii_digit();
+            ((initial as u8) << 1 | is_subsequent(c) as u8).to_string()
+        }).collect();
+        writeln!(self.dest, "    .byte {}", flags.join(", "))?;
+
+        writeln!(self.dest, ".text")?;
+
+        // every runtime error ends up here. rdi is the information of the failed procedure or 0,
+        // rsi is the message formatted with rdx and rcx, or 0 when the message is the rcx
+        // characters at rdx, and r8 points to r9 irritants.
+        // the output written so far is flushed before the error is written to stderr.
+        writeln!(self.dest, "rt_scheme_error:")?;
+        writeln!(self.dest, "    mov rbx, rdi")?;
+        writeln!(self.dest, "    mov r12, rsi")?;
+        writeln!(self.dest, "    mov r13, rdx")?;
+        writeln!(self.dest, "    mov r14, rcx")?;
+        writeln!(self.dest, "    mov r15, r8")?;
+        writeln!(self.dest, "    mov rbp, r9")?;
+        writeln!(self.dest, "    and rsp, -16")?;
+        writeln!(self.dest, "    xor edi, edi")?;
+        writeln!(self.dest, "    call fflush")?;
+        writeln!(self.dest, "    mov rax, QWORD PTR [rip+stderr@GOTPCREL]")?;
+        writeln!(self.dest, "    mov rax, QWORD PTR [rax]")?;
+        writeln!(self.dest, "    mov [rip+rt_print_stream], rax")?;
+        writeln!(self.dest, "    lea rdi, [rip+rt_prefix_error_msg]")?;
+        writeln!(self.dest, "    call rt_print_format")?;
+        writeln!(self.dest, "    test rbx, rbx")?;
+        writeln!(self.dest, "    jz rt_scheme_error_message")?;
+        writeln!(self.dest, "    mov rdi, QWORD PTR [rbx+8]")?;
+        writeln!(self.dest, "    mov rsi, QWORD PTR [rdi-{}]", OBJ_TAG)?;
+        writeln!(self.dest, "    shr rsi, 8")?;
+        writeln!(self.dest, "    add rdi, {}", 8 - OBJ_TAG)?;
+        writeln!(self.dest, "    call rt_print_chars")?;
+        writeln!(self.dest, "    lea rdi, [rip+rt_name_error_msg]")?;
+        writeln!(self.dest, "    call rt_print_format")?;
+        writeln!(self.dest, "rt_scheme_error_message:")?;
+        writeln!(self.dest, "    mov rdi, r12")?;
+        writeln!(self.dest, "    mov rsi, r13")?;
+        writeln!(self.dest, "    mov rdx, r14")?;
+        writeln!(self.dest, "    test rdi, rdi")?;
+        writeln!(self.dest, "    jz rt_scheme_error_string")?;
+        writeln!(self.dest, "    call rt_print_format")?;
+        writeln!(self.dest, "    jmp rt_scheme_error_irritants")?;
+        writeln!(self.dest, "rt_scheme_error_string:")?;
+        writeln!(self.dest, "    mov rdi, r13")?;
+        writeln!(self.dest, "    mov rsi, r14")?;
+        writeln!(self.dest, "    call rt_print_chars")?;
+        writeln!(self.dest, "rt_scheme_error_irritants:")?;
+        writeln!(self.dest, "    test rbp, rbp")?;
+        writeln!(self.dest, "    jz rt_scheme_error_done")?;
+        writeln!(self.dest, "    mov edi, {}", b' ')?;
+        writeln!(self.dest, "    call rt_print_byte")?;
+        writeln!(self.dest, "    mov rdi, QWORD PTR [r15]")?;
+        writeln!(self.dest, "    mov esi, 1")?;
+        writeln!(self.dest, "    call rt_print")?;
+        writeln!(self.dest, "    add r15, 8")?;
+        writeln!(self.dest, "    dec rbp")?;
+        writeln!(self.dest, "    jmp rt_scheme_error_irritants")?;
+        writeln!(self.dest, "rt_scheme_error_done:")?;
+        writeln!(self.dest, "    mov edi, {}", b'\n')?;
+        writeln!(self.dest, "    call rt_print_byte")?;
+        writeln!(self.dest, "    mov edi, {}", ERROR_STATUS)?;
+        writeln!(self.dest, "    call exit")?;
+
+        // the errors of the checks in the routines take the information of the procedure in rdi
+        // and the offending value in rsi, which is the irritant
+        for label in ["type", "range", "byte", "constant"] {
+            writeln!(self.dest, "rt_{}_error:", label)?;
+            writeln!(self.dest, "    lea r10, [rip+rt_{}_error_msg]", label)?;
+            writeln!(self.dest, "    jmp rt_value_error")?;
+        }
+        writeln!(self.dest, "rt_call_error:")?;
+        writeln!(self.dest, "    xor edi, edi")?;
+        writeln!(self.dest, "    lea r10, [rip+rt_call_error_msg]")?;
+        writeln!(self.dest, "rt_value_error:")?;
+        writeln!(self.dest, "    push rsi")?;
+        writeln!(self.dest, "    mov rsi, r10")?;
+        writeln!(self.dest, "    mov r8, rsp")?;
+        writeln!(self.dest, "    mov r9d, 1")?;
+        writeln!(self.dest, "    jmp rt_scheme_error")?;
+
+        // a circular list is not printed as the irritant
+        for label in ["zero", "circular", "inexact"] {
+            writeln!(self.dest, "rt_{}_error:", label)?;
+            writeln!(self.dest, "    lea rsi, [rip+rt_{}_error_msg]", label)?;
+            writeln!(self.dest, "    xor r9d, r9d")?;
+            writeln!(self.dest, "    jmp rt_scheme_error")?;
+        }
+
+        // the heap cannot grow, in no procedure in particular
+        writeln!(self.dest, "rt_memory_error:")?;
+        writeln!(self.dest, "    xor edi, edi")?;
+        writeln!(self.dest, "    lea rsi, [rip+rt_memory_error_msg]")?;
+        writeln!(self.dest, "    xor r9d, r9d")?;
+        writeln!(self.dest, "    jmp rt_scheme_error")?;
+
+        // (error message irritant ...) takes the number of its arguments in rcx
+        self.gen_routine("rt_user_error")?;
+        writeln!(self.dest, "    mov rax, QWORD PTR [rsp+8]")?;
+        self.check_type("rax", STRING_TYPE)?;
+        writeln!(self.dest, "    lea r9, [rcx-1]")?;
+        writeln!(self.dest, "    lea r8, [rsp+16]")?;
+        writeln!(self.dest, "    mov rcx, QWORD PTR [rax-{}]", OBJ_TAG)?;
+        writeln!(self.dest, "    shr rcx, 8")?;
+        writeln!(self.dest, "    lea rdx, [rax+{}]", 8 - OBJ_TAG)?;
+        writeln!(self.dest, "    xor esi, esi")?;
+        writeln!(self.dest, "    xor edi, edi")?;
+        writeln!(self.dest, "    jmp rt_scheme_error")?;
+
+        // called when the number of the arguments in rcx differs from the arity in the
+        // information of the procedure in rdx. returns if the procedure takes the rest.
+        writeln!(self.dest, "rt_check_arity:")?;
+        writeln!(self.dest, "    mov r11, QWORD PTR [rdx]")?;
+        writeln!(self.dest, "    test r11, r11")?;
+        writeln!(self.dest, "    jns rt_arity_error")?;
+        writeln!(self.dest, "    sar r11, {}", FIXNUM_SHIFT)?;
+        writeln!(self.dest, "    not r11")?;
+        writeln!(self.dest, "    cmp rcx, r11")?;
+        writeln!(self.dest, "    jb rt_arity_error")?;
+        writeln!(self.dest, "    ret")?;
+
+        writeln!(self.dest, "rt_arity_error:")?;
+        writeln!(self.dest, "    mov rdi, rdx")?;
+        writeln!(self.dest, "    mov rdx, QWORD PTR [rdi]")?;
+        writeln!(self.dest, "    sar rdx, {}", FIXNUM_SHIFT)?;
+        writeln!(self.dest, "    lea rsi, [rip+rt_arity_error_msg]")?;
+        writeln!(self.dest, "    test rdx, rdx")?;
+        writeln!(self.dest, "    mov r8, rdx")?;
+        writeln!(self.dest, "    not r8")?;
+        writeln!(self.dest, "    cmovs rdx, r8")?;
+        writeln!(self.dest, "    lea r8, [rip+rt_arity_rest_error_msg]")?;
+        writeln!(self.dest, "    cmovs rsi, r8")?;
+        writeln!(self.dest, "    xor r9d, r9d")?;
+        writeln!(self.dest, "    jmp rt_scheme_error")?;
+
+        self.gen_gc()?;
+
+        for (name, label, args_num, rest) in PRIMITIVES {
+            self.gen_proc_info(label, name, args_num, rest)?;
+        }
+        // the routines taking an optional argument report too many arguments with these
+        for (label, name) in [("rt_make_vector", "make-vector"), ("rt_make_bytevector", "make-bytevector"),
+                              ("rt_make_string", "make-string")] {
+            self.gen_proc_info(&format!("{}_max", label), name, 2, false)?;
+        }
+
+        self.gen_routine("rt_cons")?;
+        writeln!(self.dest, "    mov rdi, 16")?;
+        writeln!(self.dest, "    call rt_alloc")?;
+        writeln!(self.dest, "    mov rdi, QWORD PTR [rsp+8]")?;
+        writeln!(self.dest, "    mov rsi, QWORD PTR [rsp+16]")?;
+        writeln!(self.dest, "    mov QWORD PTR [rax], rdi")?;
+        writeln!(self.dest, "    mov QWORD PTR [rax+8], rsi")?;
+        writeln!(self.dest, "    or rax, {}", PAIR_TAG)?;
+        writeln!(self.dest, "    ret 16")?;
+
+        self.gen_lists()?;
+        self.gen_predicates()?;
+
+        for (label, set) in [("equal", "sete"), ("neq", "setne"), ("lth", "setl"), ("leq", "setle"), ("gth", "setg"), ("geq", "setge")] {
+            self.gen_compare(&format!("rt_{}", label), set, "call rt_num_cmp", |gen, reg| gen.check_number(reg))?;
+        }
+
+        self.gen_arith()?;
+        self.gen_bignums()?;
+        self.gen_rest_args()?;
+        self.gen_strings()?;
+        self.gen_vectors()?;
+        self.gen_chars()?;
+        self.gen_symbols()?;
+        self.gen_output()?;
 
         for proc in parser.proc_list {
-            self.gen_proc(proc);
+            self.gen_proc(proc)?;
         }
 
-        writeln!(self.dest, "main:").unwrap();
-        writeln!(self.dest, "    push rbp").unwrap();
-        writeln!(self.dest, "    mov rbp, rsp").unwrap();
-        writeln!(self.dest, "    sub rsp, 200").unwrap();
+        writeln!(self.dest, "main:")?;
+        writeln!(self.dest, "    push rbp")?;
+        writeln!(self.dest, "    mov rbp, rsp")?;
+        writeln!(self.dest, "    sub rsp, 200")?;
+        writeln!(self.dest, "    and rsp, -16")?;
+
+        writeln!(self.dest, "    mov [rip+rt_stack_base], rsp")?;
+        writeln!(self.dest, "    mov rdi, {}", HEAP_SIZE)?;
+        writeln!(self.dest, "    mov [rip+rt_heap_size], rdi")?;
+        writeln!(self.dest, "    call malloc")?;
+        writeln!(self.dest, "    test rax, rax")?;
+        writeln!(self.dest, "    jz rt_memory_error")?;
+        writeln!(self.dest, "    mov [rip+rt_heap_start], rax")?;
+        writeln!(self.dest, "    mov [rip+rt_heap_ptr], rax")?;
+        writeln!(self.dest, "    add rax, {}", HEAP_SIZE)?;
+        writeln!(self.dest, "    mov [rip+rt_heap_end], rax")?;
+        writeln!(self.dest, "    mov rax, QWORD PTR [rip+stdout@GOTPCREL]")?;
+        writeln!(self.dest, "    mov rax, QWORD PTR [rax]")?;
+        writeln!(self.dest, "    mov [rip+rt_print_stream], rax")?;
 
         for node in parser.node_list {
-            self.gen_node(node);
+            self.gen_node(node)?;
+        }
+
+        // the exit status is the value of the last expression if it is an integer
+        writeln!(self.dest, "    pop rax")?;
+        writeln!(self.dest, "    xor ecx, ecx")?;
+        writeln!(self.dest, "    mov rdi, rax")?;
+        writeln!(self.dest, "    sar rax, {}", FIXNUM_SHIFT)?;
+        writeln!(self.dest, "    and rdi, {}", TAG_MASK)?;
+        writeln!(self.dest, "    cmovnz rax, rcx")?;
+        writeln!(self.dest, "    mov rsp, rbp")?;
+        writeln!(self.dest, "    pop rbp")?;
+        writeln!(self.dest, "    ret")?;
+
+        // constants are objects outside the heap, so the collector leaves them in place
+        writeln!(self.dest, ".data")?;
+        for line in std::mem::take(&mut self.data) {
+            writeln!(self.dest, "{}", line)?;
+        }
+        let symbols = std::mem::take(&mut self.symbols);
+        for (i, name) in symbols.iter().enumerate() {
+            writeln!(self.dest, "    .p2align 3")?;
+            writeln!(self.dest, "symbol_{}:", i)?;
+            writeln!(self.dest, "    .quad {}", header(SYMBOL_TYPE, 1))?;
+            writeln!(self.dest, "    .quad string_{}+{}", self.strings.len(), OBJ_TAG)?;
+            self.strings.push(name.clone());
+            writeln!(self.dest, "symbol_list_{}:", i)?;
+            writeln!(self.dest, "    .quad symbol_{}+{}", i, OBJ_TAG)?;
+            if i + 1 < symbols.len() {
+                writeln!(self.dest, "    .quad symbol_list_{}+{}", i + 1, PAIR_TAG)?;
+            } else {
+                writeln!(self.dest, "    .quad {}", NIL)?;
+            }
+        }
+        if symbols.is_empty() {
+            writeln!(self.dest, ".set symbol_list, {}", NIL)?;
+        } else {
+            writeln!(self.dest, ".set symbol_list, symbol_list_0+{}", PAIR_TAG)?;
+        }
+
+        writeln!(self.dest, ".section .rodata")?;
+        for (i, string) in std::mem::take(&mut self.strings).iter().enumerate() {
+            writeln!(self.dest, "    .p2align 3")?;
+            writeln!(self.dest, "string_{}:", i)?;
+            let len = string.chars().count();
+            writeln!(self.dest, "    .quad {}", header(STRING_TYPE, len))?;
+            writeln!(self.dest, "    .ascii \"{}\"", escape(string))?;
+            writeln!(self.dest, "    .zero {}", 8 - len % 8)?;
+        }
+        Ok(())
+    }
+
+    // a copying collector. `rt_alloc` takes the size in bytes in rdi and returns an untagged pointer.
+    // when the heap is full, every value on the stack and in the globals is forwarded to a new
+    // space and the objects reachable from them are copied in breadth-first order.
+    // routines that allocate must keep their values on the stack, not in registers.
+    fn gen_gc(&mut self) -> io::Result<()> {
+        writeln!(self.dest, "rt_alloc:")?;
+        writeln!(self.dest, "    mov rax, [rip+rt_heap_ptr]")?;
+        writeln!(self.dest, "    lea rdx, [rax+rdi]")?;
+        writeln!(self.dest, "    cmp rdx, [rip+rt_heap_end]")?;
+        writeln!(self.dest, "    ja rt_alloc_full")?;
+        writeln!(self.dest, "    mov [rip+rt_heap_ptr], rdx")?;
+        writeln!(self.dest, "    ret")?;
+        writeln!(self.dest, "rt_alloc_full:")?;
+        writeln!(self.dest, "    push rdi")?;
+        writeln!(self.dest, "    call rt_gc")?;
+        writeln!(self.dest, "    pop rdi")?;
+        writeln!(self.dest, "    jmp rt_alloc")?;
+
+        // collects once, and once more into a larger space if less than half of it is free
+        writeln!(self.dest, "rt_gc:")?;
+        writeln!(self.dest, "    push rbp")?;
+        writeln!(self.dest, "    mov rbp, rsp")?;
+        writeln!(self.dest, "    push rbx")?;
+        writeln!(self.dest, "    push r12")?;
+        writeln!(self.dest, "    push r13")?;
+        writeln!(self.dest, "    push r14")?;
+        writeln!(self.dest, "    push r15")?;
+        writeln!(self.dest, "    push rdi")?;
+        writeln!(self.dest, "    lea rax, [rbp+16]")?;
+        writeln!(self.dest, "    mov [rip+rt_gc_stack_top], rax")?;
+        writeln!(self.dest, "    and rsp, -16")?;
+        writeln!(self.dest, "    mov rdi, [rip+rt_heap_size]")?;
+        writeln!(self.dest, "    call rt_gc_collect")?;
+        writeln!(self.dest, "    mov rax, [rip+rt_heap_ptr]")?;
+        writeln!(self.dest, "    sub rax, [rip+rt_heap_start]")?;
+        writeln!(self.dest, "    add rax, [rbp-48]")?;
+        writeln!(self.dest, "    shl rax, 1")?;
+        writeln!(self.dest, "    cmp rax, [rip+rt_heap_size]")?;
+        writeln!(self.dest, "    jbe rt_gc_done")?;
+        writeln!(self.dest, "    mov rdi, rax")?;
+        writeln!(self.dest, "    call rt_gc_collect")?;
+        writeln!(self.dest, "rt_gc_done:")?;
+        writeln!(self.dest, "    lea rsp, [rbp-40]")?;
+        writeln!(self.dest, "    pop r15")?;
+        writeln!(self.dest, "    pop r14")?;
+        writeln!(self.dest, "    pop r13")?;
+        writeln!(self.dest, "    pop r12")?;
+        writeln!(self.dest, "    pop rbx")?;
+        writeln!(self.dest, "    pop rbp")?;
+        writeln!(self.dest, "    ret")?;
+
+        // rdi is the size of the new space. r13 is the free pointer of the new space,
+        // r14 and r15 are the bounds of the old space.
+        writeln!(self.dest, "rt_gc_collect:")?;
+        writeln!(self.dest, "    mov [rip+rt_heap_size], rdi")?;
+        writeln!(self.dest, "    push rdi")?;
+        writeln!(self.dest, "    call malloc")?;
+        writeln!(self.dest, "    test rax, rax")?;
+        writeln!(self.dest, "    jz rt_memory_error")?;
+        writeln!(self.dest, "    push rax")?;
+        writeln!(self.dest, "    sub rsp, 8")?;
+        writeln!(self.dest, "    mov r13, rax")?;
+        writeln!(self.dest, "    mov r14, [rip+rt_heap_start]")?;
+        writeln!(self.dest, "    mov r15, [rip+rt_heap_ptr]")?;
+
+        writeln!(self.dest, "    mov rbx, [rip+rt_gc_stack_top]")?;
+        writeln!(self.dest, "    mov r12, [rip+rt_stack_base]")?;
+        writeln!(self.dest, "rt_gc_stack:")?;
+        writeln!(self.dest, "    cmp rbx, r12")?;
+        writeln!(self.dest, "    jae rt_gc_stack_done")?;
+        writeln!(self.dest, "    mov rax, [rbx]")?;
+        writeln!(self.dest, "    call rt_gc_forward")?;
+        writeln!(self.dest, "    mov [rbx], rax")?;
+        writeln!(self.dest, "    add rbx, 8")?;
+        writeln!(self.dest, "    jmp rt_gc_stack")?;
+        writeln!(self.dest, "rt_gc_stack_done:")?;
+
+        writeln!(self.dest, "    lea rbx, [rip+rt_globals_begin]")?;
+        writeln!(self.dest, "    lea r12, [rip+rt_globals_end]")?;
+        writeln!(self.dest, "rt_gc_globals:")?;
+        writeln!(self.dest, "    cmp rbx, r12")?;
+        writeln!(self.dest, "    jae rt_gc_globals_done")?;
+        writeln!(self.dest, "    mov rax, [rbx]")?;
+        writeln!(self.dest, "    call rt_gc_forward")?;
+        writeln!(self.dest, "    mov [rbx], rax")?;
+        writeln!(self.dest, "    add rbx, 8")?;
+        writeln!(self.dest, "    jmp rt_gc_globals")?;
+        writeln!(self.dest, "rt_gc_globals_done:")?;
+
+        // scan the copied objects until the scan pointer reaches the free pointer
+        writeln!(self.dest, "    mov rbx, [rsp+8]")?;
+        writeln!(self.dest, "rt_gc_scan:")?;
+        writeln!(self.dest, "    cmp rbx, r13")?;
+        writeln!(self.dest, "    jae rt_gc_scan_done")?;
+        writeln!(self.dest, "    mov rax, [rbx]")?;
+        writeln!(self.dest, "    mov rcx, rax")?;
+        writeln!(self.dest, "    and rcx, {}", TAG_MASK)?;
+        writeln!(self.dest, "    cmp rcx, {}", HEADER_TAG)?;
+        writeln!(self.dest, "    je rt_gc_scan_obj")?;
+        writeln!(self.dest, "    lea r12, [rbx+16]")?;
+        writeln!(self.dest, "    jmp rt_gc_scan_values")?;
+        writeln!(self.dest, "rt_gc_scan_obj:")?;
+        writeln!(self.dest, "    mov rdx, rax")?;
+        writeln!(self.dest, "    shr rdx, 8")?;
+        writeln!(self.dest, "    shr rax, 3")?;
+        writeln!(self.dest, "    and rax, 31")?;
+        writeln!(self.dest, "    cmp rax, {}", RAW_TYPE)?;
+        writeln!(self.dest, "    jb rt_gc_scan_header")?;
+        writeln!(self.dest, "    shr rdx, 3")?;
+        writeln!(self.dest, "    lea rbx, [rbx+8*rdx+16]")?;
+        writeln!(self.dest, "    jmp rt_gc_scan")?;
+        writeln!(self.dest, "rt_gc_scan_header:")?;
+        writeln!(self.dest, "    lea r12, [rbx+8*rdx+8]")?;
+        writeln!(self.dest, "    add rbx, 8")?;
+        writeln!(self.dest, "rt_gc_scan_values:")?;
+        writeln!(self.dest, "    cmp rbx, r12")?;
+        writeln!(self.dest, "    jae rt_gc_scan")?;
+        writeln!(self.dest, "    mov rax, [rbx]")?;
+        writeln!(self.dest, "    call rt_gc_forward")?;
+        writeln!(self.dest, "    mov [rbx], rax")?;
+        writeln!(self.dest, "    add rbx, 8")?;
+        writeln!(self.dest, "    jmp rt_gc_scan_values")?;
+        writeln!(self.dest, "rt_gc_scan_done:")?;
+
+        writeln!(self.dest, "    mov rdi, r14")?;
+        writeln!(self.dest, "    call free")?;
+        writeln!(self.dest, "    mov rax, [rsp+8]")?;
+        writeln!(self.dest, "    mov [rip+rt_heap_start], rax")?;
+        writeln!(self.dest, "    mov [rip+rt_heap_ptr], r13")?;
+        writeln!(self.dest, "    add rax, [rsp+16]")?;
+        writeln!(self.dest, "    mov [rip+rt_heap_end], rax")?;
+        writeln!(self.dest, "    add rsp, 24")?;
+        writeln!(self.dest, "    ret")?;
+
+        // returns the new location of the value in rax, copying the object if not yet moved.
+        // anything that does not point into the old space (code addresses, static data) is kept.
+        writeln!(self.dest, "rt_gc_forward:")?;
+        writeln!(self.dest, "    mov rcx, rax")?;
+        writeln!(self.dest, "    and rcx, {}", TAG_MASK)?;
+        writeln!(self.dest, "    cmp rcx, {}", PAIR_TAG)?;
+        writeln!(self.dest, "    jb rt_gc_forward_done")?;
+        writeln!(self.dest, "    cmp rcx, {}", OBJ_TAG)?;
+        writeln!(self.dest, "    ja rt_gc_forward_done")?;
+        writeln!(self.dest, "    mov rdx, rax")?;
+        writeln!(self.dest, "    sub rdx, rcx")?;
+        writeln!(self.dest, "    cmp rdx, r14")?;
+        writeln!(self.dest, "    jb rt_gc_forward_done")?;
+        writeln!(self.dest, "    cmp rdx, r15")?;
+        writeln!(self.dest, "    jae rt_gc_forward_done")?;
+        writeln!(self.dest, "    mov rsi, [rdx]")?;
+        writeln!(self.dest, "    cmp rsi, {}", FORWARD)?;
+        writeln!(self.dest, "    jne rt_gc_forward_copy")?;
+        writeln!(self.dest, "    mov rax, [rdx+8]")?;
+        writeln!(self.dest, "    or rax, rcx")?;
+        writeln!(self.dest, "    ret")?;
+        writeln!(self.dest, "rt_gc_forward_copy:")?;
+        writeln!(self.dest, "    mov r8, 2")?;
+        writeln!(self.dest, "    cmp rcx, {}", PAIR_TAG)?;
+        writeln!(self.dest, "    je rt_gc_forward_move")?;
+        writeln!(self.dest, "    mov r8, rsi")?;
+        writeln!(self.dest, "    shr r8, 8")?;
+        writeln!(self.dest, "    shr rsi, 3")?;
+        writeln!(self.dest, "    and rsi, 31")?;
+        writeln!(self.dest, "    cmp rsi, {}", RAW_TYPE)?;
+        writeln!(self.dest, "    jb rt_gc_forward_values")?;
+        writeln!(self.dest, "    shr r8, 3")?;
+        writeln!(self.dest, "    inc r8")?;
+        writeln!(self.dest, "rt_gc_forward_values:")?;
+        writeln!(self.dest, "    inc r8")?;
+        writeln!(self.dest, "rt_gc_forward_move:")?;
+        writeln!(self.dest, "    mov rax, r13")?;
+        writeln!(self.dest, "    xor rsi, rsi")?;
+        writeln!(self.dest, "rt_gc_forward_loop:")?;
+        writeln!(self.dest, "    mov rdi, [rdx+8*rsi]")?;
+        writeln!(self.dest, "    mov [r13+8*rsi], rdi")?;
+        writeln!(self.dest, "    inc rsi")?;
+        writeln!(self.dest, "    cmp rsi, r8")?;
+        writeln!(self.dest, "    jb rt_gc_forward_loop")?;
+        writeln!(self.dest, "    lea r13, [r13+8*r8]")?;
+        writeln!(self.dest, "    mov QWORD PTR [rdx], {}", FORWARD)?;
+        writeln!(self.dest, "    mov [rdx+8], rax")?;
+        writeln!(self.dest, "    or rax, rcx")?;
+        writeln!(self.dest, "rt_gc_forward_done:")?;
+        writeln!(self.dest, "    ret")?;
+        Ok(())
+    }
+
+    // a string holds a byte for each character after the header, followed by at least one zero
+    // byte, so its characters are at most \xff.
+    // routines that make a string allocate it first and then read their arguments again.
+    fn gen_strings(&mut self) -> io::Result<()> {
+        self.gen_type_predicate("rt_is_string", STRING_TYPE)?;
+
+        // the fill is optional, so the number of the arguments is in rcx
+        self.gen_routine("rt_make_string")?;
+        self.check_max_args(2)?;
+        writeln!(self.dest, "    mov rdi, QWORD PTR [rsp+8]")?;
+        self.check_length("rdi")?;
+        writeln!(self.dest, "    mov eax, {}", ((b' ' as i64) << CHAR_SHIFT) | CHAR_TAG)?;
+        writeln!(self.dest, "    cmp rcx, 2")?;
+        writeln!(self.dest, "    cmove rax, QWORD PTR [rsp+16]")?;
+        self.check_string_char("rax")?;
+        writeln!(self.dest, "    push rax")?;
+        writeln!(self.dest, "    shl rcx, {}", FIXNUM_SHIFT)?;
+        writeln!(self.dest, "    push rcx")?;
+        writeln!(self.dest, "    mov rdi, QWORD PTR [rsp+24]")?;
+        self.gen_alloc_size(true)?;
+        writeln!(self.dest, "    call rt_alloc")?;
+        writeln!(self.dest, "    mov rcx, QWORD PTR [rsp+24]")?;
+        writeln!(self.dest, "    sar rcx, {}", FIXNUM_SHIFT)?;
+        self.gen_string_header("rcx")?;
+        writeln!(self.dest, "    mov rdx, rax")?;
+        writeln!(self.dest, "    mov rax, QWORD PTR [rsp+8]")?;
+        writeln!(self.dest, "    shr rax, {}", CHAR_SHIFT)?;
+        writeln!(self.dest, "    lea rdi, [rdx+8]")?;
+        writeln!(self.dest, "    rep stosb")?;
+        writeln!(self.dest, "    lea rax, [rdx+{}]", OBJ_TAG)?;
+        writeln!(self.dest, "    pop rcx")?;
+        writeln!(self.dest, "    shr rcx, {}", FIXNUM_SHIFT)?;
+        writeln!(self.dest, "    pop rdx")?;
+        writeln!(self.dest, "    pop rdx")?;
+        writeln!(self.dest, "    lea rsp, [rsp+8*rcx]")?;
+        writeln!(self.dest, "    jmp rdx")?;
+
+        // (string char ...) checks its arguments before it allocates
+        self.gen_routine("rt_string")?;
+        writeln!(self.dest, "    mov rdx, rcx")?;
+        writeln!(self.dest, "rt_string_check:")?;
+        writeln!(self.dest, "    test rdx, rdx")?;
+        writeln!(self.dest, "    jz rt_string_alloc")?;
+        writeln!(self.dest, "    mov rdi, QWORD PTR [rsp+8*rdx]")?;
+        self.check_string_char("rdi")?;
+        writeln!(self.dest, "    dec rdx")?;
+        writeln!(self.dest, "    jmp rt_string_check")?;
+        writeln!(self.dest, "rt_string_alloc:")?;
+        writeln!(self.dest, "    shl rcx, {}", FIXNUM_SHIFT)?;
+        writeln!(self.dest, "    push rcx")?;
+        writeln!(self.dest, "    mov rdi, rcx")?;
+        self.gen_alloc_size(true)?;
+        writeln!(self.dest, "    call rt_alloc")?;
+        writeln!(self.dest, "    mov rcx, QWORD PTR [rsp]")?;
+        writeln!(self.dest, "    shr rcx, {}", FIXNUM_SHIFT)?;
+        self.gen_string_header("rcx")?;
+        writeln!(self.dest, "    xor edx, edx")?;
+        writeln!(self.dest, "rt_string_loop:")?;
+        writeln!(self.dest, "    cmp rdx, rcx")?;
+        writeln!(self.dest, "    jae rt_string_done")?;
+        writeln!(self.dest, "    mov rdi, QWORD PTR [rsp+8*rdx+16]")?;
+        writeln!(self.dest, "    shr rdi, {}", CHAR_SHIFT)?;
+        writeln!(self.dest, "    mov BYTE PTR [rax+rdx+8], dil")?;
+        writeln!(self.dest, "    inc rdx")?;
+        writeln!(self.dest, "    jmp rt_string_loop")?;
+        writeln!(self.dest, "rt_string_done:")?;
+        writeln!(self.dest, "    or rax, {}", OBJ_TAG)?;
+        writeln!(self.dest, "    pop rcx")?;
+        writeln!(self.dest, "    shr rcx, {}", FIXNUM_SHIFT)?;
+        writeln!(self.dest, "    pop rdx")?;
+        writeln!(self.dest, "    lea rsp, [rsp+8*rcx]")?;
+        writeln!(self.dest, "    jmp rdx")?;
+
+        self.gen_routine("rt_string_length")?;
+        writeln!(self.dest, "    mov rax, QWORD PTR [rsp+8]")?;
+        self.check_type("rax", STRING_TYPE)?;
+        writeln!(self.dest, "    mov rax, QWORD PTR [rax-{}]", OBJ_TAG)?;
+        writeln!(self.dest, "    shr rax, 8")?;
+        writeln!(self.dest, "    shl rax, {}", FIXNUM_SHIFT)?;
+        writeln!(self.dest, "    ret 8")?;
+
+        self.gen_routine("rt_string_ref")?;
+        writeln!(self.dest, "    mov rax, QWORD PTR [rsp+8]")?;
+        writeln!(self.dest, "    mov rdi, QWORD PTR [rsp+16]")?;
+        self.check_type("rax", STRING_TYPE)?;
+        self.check_tag("rdi", FIXNUM_TAG)?;
+        writeln!(self.dest, "    sar rdi, {}", FIXNUM_SHIFT)?;
+        writeln!(self.dest, "    mov rcx, QWORD PTR [rax-{}]", OBJ_TAG)?;
+        writeln!(self.dest, "    shr rcx, 8")?;
+        writeln!(self.dest, "    cmp rdi, rcx")?;
+        self.gen_error("jb", "rt_range_error", Some("QWORD PTR [rsp+16]"))?;
+        writeln!(self.dest, "    movzx eax, BYTE PTR [rax+rdi+{}]", 8 - OBJ_TAG)?;
+        writeln!(self.dest, "    shl rax, {}", CHAR_SHIFT)?;
+        writeln!(self.dest, "    or rax, {}", CHAR_TAG)?;
+        writeln!(self.dest, "    ret 16")?;
+
+        self.gen_routine("rt_string_set")?;
+        writeln!(self.dest, "    mov rax, QWORD PTR [rsp+8]")?;
+        writeln!(self.dest, "    mov rdi, QWORD PTR [rsp+16]")?;
+        writeln!(self.dest, "    mov rsi, QWORD PTR [rsp+24]")?;
+        self.check_type("rax", STRING_TYPE)?;
+        self.check_mutable("rax")?;
+        self.check_index("rax", "rdi")?;
+        self.check_string_char("rsi")?;
+        writeln!(self.dest, "    shr rsi, {}", CHAR_SHIFT)?;
+        writeln!(self.dest, "    mov BYTE PTR [rax+rdi+{}], sil", 8 - OBJ_TAG)?;
+        writeln!(self.dest, "    mov eax, {}", VOID)?;
+        writeln!(self.dest, "    ret 24")?;
+
+        self.gen_routine("rt_substring")?;
+        writeln!(self.dest, "    mov rax, QWORD PTR [rsp+8]")?;
+        writeln!(self.dest, "    mov rsi, QWORD PTR [rsp+16]")?;
+        writeln!(self.dest, "    mov rdx, QWORD PTR [rsp+24]")?;
+        self.check_type("rax", STRING_TYPE)?;
+        self.check_tag("rsi", FIXNUM_TAG)?;
+        self.check_tag("rdx", FIXNUM_TAG)?;
+        writeln!(self.dest, "    sar rsi, {}", FIXNUM_SHIFT)?;
+        writeln!(self.dest, "    sar rdx, {}", FIXNUM_SHIFT)?;
+        writeln!(self.dest, "    mov rcx, QWORD PTR [rax-{}]", OBJ_TAG)?;
+        writeln!(self.dest, "    shr rcx, 8")?;
+        writeln!(self.dest, "    cmp rdx, rcx")?;
+        self.gen_error("jbe", "rt_range_error", Some("QWORD PTR [rsp+24]"))?;
+        writeln!(self.dest, "    cmp rsi, rdx")?;
+        self.gen_error("jbe", "rt_range_error", Some("QWORD PTR [rsp+16]"))?;
+        writeln!(self.dest, "    mov rdi, rdx")?;
+        writeln!(self.dest, "    sub rdi, rsi")?;
+        writeln!(self.dest, "    and rdi, -8")?;
+        writeln!(self.dest, "    add rdi, 16")?;
+        writeln!(self.dest, "    call rt_alloc")?;
+        writeln!(self.dest, "    mov rsi, QWORD PTR [rsp+16]")?;
+        writeln!(self.dest, "    sar rsi, {}", FIXNUM_SHIFT)?;
+        writeln!(self.dest, "    mov rcx, QWORD PTR [rsp+24]")?;
+        writeln!(self.dest, "    sar rcx, {}", FIXNUM_SHIFT)?;
+        writeln!(self.dest, "    sub rcx, rsi")?;
+        self.gen_string_header("rcx")?;
+        writeln!(self.dest, "    add rsi, QWORD PTR [rsp+8]")?;
+        writeln!(self.dest, "    add rsi, {}", 8 - OBJ_TAG)?;
+        writeln!(self.dest, "    lea rdi, [rax+8]")?;
+        writeln!(self.dest, "    rep movsb")?;
+        writeln!(self.dest, "    or rax, {}", OBJ_TAG)?;
+        writeln!(self.dest, "    ret 24")?;
+
+        self.gen_routine("rt_string_copy")?;
+        writeln!(self.dest, "    mov rdi, QWORD PTR [rsp+8]")?;
+        self.check_type("rdi", STRING_TYPE)?;
+        writeln!(self.dest, "    mov rdi, QWORD PTR [rdi-{}]", OBJ_TAG)?;
+        writeln!(self.dest, "    shr rdi, 8")?;
+        writeln!(self.dest, "    and rdi, -8")?;
+        writeln!(self.dest, "    add rdi, 16")?;
+        writeln!(self.dest, "    call rt_alloc")?;
+        writeln!(self.dest, "    mov rsi, QWORD PTR [rsp+8]")?;
+        writeln!(self.dest, "    mov rcx, QWORD PTR [rsi-{}]", OBJ_TAG)?;
+        writeln!(self.dest, "    shr rcx, 8")?;
+        self.gen_string_header("rcx")?;
+        writeln!(self.dest, "    add rsi, {}", 8 - OBJ_TAG)?;
+        writeln!(self.dest, "    lea rdi, [rax+8]")?;
+        writeln!(self.dest, "    rep movsb")?;
+        writeln!(self.dest, "    or rax, {}", OBJ_TAG)?;
+        writeln!(self.dest, "    ret 8")?;
+
+        // the total length and the number of the strings are kept on the stack as fixnums
+        self.gen_routine("rt_string_append")?;
+        writeln!(self.dest, "    xor edi, edi")?;
+        writeln!(self.dest, "    mov rdx, rcx")?;
+        writeln!(self.dest, "rt_string_append_check:")?;
+        writeln!(self.dest, "    test rdx, rdx")?;
+        writeln!(self.dest, "    jz rt_string_append_alloc")?;
+        writeln!(self.dest, "    mov rsi, QWORD PTR [rsp+8*rdx]")?;
+        self.check_type("rsi", STRING_TYPE)?;
+        writeln!(self.dest, "    mov rsi, QWORD PTR [rsi-{}]", OBJ_TAG)?;
+        writeln!(self.dest, "    shr rsi, 8")?;
+        writeln!(self.dest, "    add rdi, rsi")?;
+        writeln!(self.dest, "    dec rdx")?;
+        writeln!(self.dest, "    jmp rt_string_append_check")?;
+        writeln!(self.dest, "rt_string_append_alloc:")?;
+        writeln!(self.dest, "    shl rcx, {}", FIXNUM_SHIFT)?;
+        writeln!(self.dest, "    push rcx")?;
+        writeln!(self.dest, "    shl rdi, {}", FIXNUM_SHIFT)?;
+        writeln!(self.dest, "    push rdi")?;
+        self.gen_alloc_size(true)?;
+        writeln!(self.dest, "    call rt_alloc")?;
+        writeln!(self.dest, "    pop rcx")?;
+        writeln!(self.dest, "    sar rcx, {}", FIXNUM_SHIFT)?;
+        self.gen_string_header("rcx")?;
+        writeln!(self.dest, "    lea rdi, [rax+8]")?;
+        writeln!(self.dest, "    mov r8, QWORD PTR [rsp]")?;
+        writeln!(self.dest, "    shr r8, {}", FIXNUM_SHIFT)?;
+        writeln!(self.dest, "    xor edx, edx")?;
+        writeln!(self.dest, "rt_string_append_loop:")?;
+        writeln!(self.dest, "    cmp rdx, r8")?;
+        writeln!(self.dest, "    jae rt_string_append_done")?;
+        writeln!(self.dest, "    mov rsi, QWORD PTR [rsp+8*rdx+16]")?;
+        writeln!(self.dest, "    mov rcx, QWORD PTR [rsi-{}]", OBJ_TAG)?;
+        writeln!(self.dest, "    shr rcx, 8")?;
+        writeln!(self.dest, "    add rsi, {}", 8 - OBJ_TAG)?;
+        writeln!(self.dest, "    rep movsb")?;
+        writeln!(self.dest, "    inc rdx")?;
+        writeln!(self.dest, "    jmp rt_string_append_loop")?;
+        writeln!(self.dest, "rt_string_append_done:")?;
+        writeln!(self.dest, "    or rax, {}", OBJ_TAG)?;
+        writeln!(self.dest, "    pop rcx")?;
+        writeln!(self.dest, "    shr rcx, {}", FIXNUM_SHIFT)?;
+        writeln!(self.dest, "    pop rdx")?;
+        writeln!(self.dest, "    lea rsp, [rsp+8*rcx]")?;
+        writeln!(self.dest, "    jmp rdx")?;
+
+        // equal headers mean equal lengths, then the bytes are compared
+        self.gen_routine("rt_string_eq")?;
+        writeln!(self.dest, "    mov rsi, QWORD PTR [rsp+8]")?;
+        writeln!(self.dest, "    mov rdi, QWORD PTR [rsp+16]")?;
+        self.check_type("rsi", STRING_TYPE)?;
+        self.check_type("rdi", STRING_TYPE)?;
+        writeln!(self.dest, "    mov rcx, QWORD PTR [rsi-{}]", OBJ_TAG)?;
+        writeln!(self.dest, "    cmp rcx, QWORD PTR [rdi-{}]", OBJ_TAG)?;
+        writeln!(self.dest, "    jne rt_string_eq_done")?;
+        writeln!(self.dest, "    shr rcx, 8")?;
+        writeln!(self.dest, "    add rsi, {}", 8 - OBJ_TAG)?;
+        writeln!(self.dest, "    add rdi, {}", 8 - OBJ_TAG)?;
+        writeln!(self.dest, "    repe cmpsb")?;
+        writeln!(self.dest, "rt_string_eq_done:")?;
+        writeln!(self.dest, "    sete al")?;
+        writeln!(self.dest, "    movzb rax, al")?;
+        writeln!(self.dest, "    shl rax, 3")?;
+        writeln!(self.dest, "    or rax, {}", FALSE)?;
+        writeln!(self.dest, "    ret 16")?;
+
+        // compares the common prefix, and the lengths when it is the same
+        self.gen_routine("rt_string_lt")?;
+        writeln!(self.dest, "    mov rsi, QWORD PTR [rsp+8]")?;
+        writeln!(self.dest, "    mov rdi, QWORD PTR [rsp+16]")?;
+        self.check_type("rsi", STRING_TYPE)?;
+        self.check_type("rdi", STRING_TYPE)?;
+        writeln!(self.dest, "    mov r8, QWORD PTR [rsi-{}]", OBJ_TAG)?;
+        writeln!(self.dest, "    shr r8, 8")?;
+        writeln!(self.dest, "    mov r9, QWORD PTR [rdi-{}]", OBJ_TAG)?;
+        writeln!(self.dest, "    shr r9, 8")?;
+        writeln!(self.dest, "    mov rcx, r8")?;
+        writeln!(self.dest, "    cmp rcx, r9")?;
+        writeln!(self.dest, "    cmova rcx, r9")?;
+        writeln!(self.dest, "    add rsi, {}", 8 - OBJ_TAG)?;
+        writeln!(self.dest, "    add rdi, {}", 8 - OBJ_TAG)?;
+        writeln!(self.dest, "    test rcx, rcx")?;
+        writeln!(self.dest, "    jz rt_string_lt_length")?;
+        writeln!(self.dest, "    repe cmpsb")?;
+        writeln!(self.dest, "    jne rt_string_lt_done")?;
+        writeln!(self.dest, "rt_string_lt_length:")?;
+        writeln!(self.dest, "    cmp r8, r9")?;
+        writeln!(self.dest, "rt_string_lt_done:")?;
+        writeln!(self.dest, "    setb al")?;
+        writeln!(self.dest, "    movzb rax, al")?;
+        writeln!(self.dest, "    shl rax, 3")?;
+        writeln!(self.dest, "    or rax, {}", FALSE)?;
+        writeln!(self.dest, "    ret 16")?;
+
+        // reads an optional sign and decimal digits, and returns #f if the string is not a number.
+        // the digits are added to the magnitude with the generic arithmetic, which may allocate,
+        // so the string stays on the stack and is read at an index.
+        self.gen_routine("rt_string_to_number")?;
+        writeln!(self.dest, "    mov rsi, QWORD PTR [rsp+8]")?;
+        self.check_type("rsi", STRING_TYPE)?;
+        writeln!(self.dest, "    push rbp")?;
+        writeln!(self.dest, "    mov rbp, rsp")?;
+        writeln!(self.dest, "    mov rcx, QWORD PTR [rsi-{}]", OBJ_TAG)?;
+        writeln!(self.dest, "    shr rcx, 8")?;
+        writeln!(self.dest, "    xor eax, eax")?;
+        writeln!(self.dest, "    xor r8d, r8d")?;
+        writeln!(self.dest, "    test rcx, rcx")?;
+        writeln!(self.dest, "    jz rt_string_to_number_fail")?;
+        writeln!(self.dest, "    movzx edi, BYTE PTR [rsi+{}]", 8 - OBJ_TAG)?;
+        writeln!(self.dest, "    cmp edi, {}", b'+')?;
+        writeln!(self.dest, "    je rt_string_to_number_sign")?;
+        writeln!(self.dest, "    cmp edi, {}", b'-')?;
+        writeln!(self.dest, "    jne rt_string_to_number_start")?;
+        writeln!(self.dest, "    mov r8d, 1")?;
+        writeln!(self.dest, "rt_string_to_number_sign:")?;
+        writeln!(self.dest, "    inc rax")?;
+        writeln!(self.dest, "    cmp rax, rcx")?;
+        writeln!(self.dest, "    je rt_string_to_number_fail")?;
+        // the sign, the magnitude and the index are at [rbp-8], [rbp-16] and [rbp-24]
+        writeln!(self.dest, "rt_string_to_number_start:")?;
+        writeln!(self.dest, "    push r8")?;
+        writeln!(self.dest, "    push 0")?;
+        writeln!(self.dest, "    shl rax, {}", FIXNUM_SHIFT)?;
+        writeln!(self.dest, "    push rax")?;
+        writeln!(self.dest, "rt_string_to_number_digits:")?;
+        writeln!(self.dest, "    mov rsi, QWORD PTR [rbp+16]")?;
+        writeln!(self.dest, "    mov rax, QWORD PTR [rbp-24]")?;
+        writeln!(self.dest, "    sar rax, {}", FIXNUM_SHIFT)?;
+        writeln!(self.dest, "    movzx edi, BYTE PTR [rsi+rax+{}]", 8 - OBJ_TAG)?;
+        writeln!(self.dest, "    sub edi, {}", b'0')?;
+        writeln!(self.dest, "    cmp edi, 9")?;
+        writeln!(self.dest, "    ja rt_string_to_number_fail")?;
+        writeln!(self.dest, "    shl rdi, {}", FIXNUM_SHIFT)?;
+        writeln!(self.dest, "    push rdi")?;
+        writeln!(self.dest, "    mov rdi, QWORD PTR [rbp-16]")?;
+        writeln!(self.dest, "    mov esi, {}", 10 << FIXNUM_SHIFT)?;
+        writeln!(self.dest, "    call rt_num_mul")?;
+        writeln!(self.dest, "    mov rdi, rax")?;
+        writeln!(self.dest, "    pop rsi")?;
+        writeln!(self.dest, "    call rt_num_add")?;
+        writeln!(self.dest, "    mov QWORD PTR [rbp-16], rax")?;
+        writeln!(self.dest, "    add QWORD PTR [rbp-24], {}", 1 << FIXNUM_SHIFT)?;
+        writeln!(self.dest, "    mov rax, QWORD PTR [rbp-24]")?;
+        writeln!(self.dest, "    sar rax, {}", FIXNUM_SHIFT)?;
+        writeln!(self.dest, "    mov rsi, QWORD PTR [rbp+16]")?;
+        writeln!(self.dest, "    mov rcx, QWORD PTR [rsi-{}]", OBJ_TAG)?;
+        writeln!(self.dest, "    shr rcx, 8")?;
+        writeln!(self.dest, "    cmp rax, rcx")?;
+        writeln!(self.dest, "    jb rt_string_to_number_digits")?;
+        writeln!(self.dest, "    mov rax, QWORD PTR [rbp-16]")?;
+        writeln!(self.dest, "    cmp QWORD PTR [rbp-8], 0")?;
+        writeln!(self.dest, "    je rt_string_to_number_done")?;
+        writeln!(self.dest, "    xor edi, edi")?;
+        writeln!(self.dest, "    mov rsi, rax")?;
+        writeln!(self.dest, "    call rt_num_sub")?;
+        writeln!(self.dest, "rt_string_to_number_done:")?;
+        writeln!(self.dest, "    leave")?;
+        writeln!(self.dest, "    ret 8")?;
+        writeln!(self.dest, "rt_string_to_number_fail:")?;
+        writeln!(self.dest, "    mov rax, {}", FALSE)?;
+        writeln!(self.dest, "    leave")?;
+        writeln!(self.dest, "    ret 8")?;
+
+        // counts the digits, allocates the string and writes the digits from the end
+        self.gen_routine("rt_number_to_string")?;
+        writeln!(self.dest, "    mov rax, QWORD PTR [rsp+8]")?;
+        self.check_number("rax")?;
+        writeln!(self.dest, "    test rax, {}", TAG_MASK)?;
+        writeln!(self.dest, "    jnz rt_number_to_string_big")?;
+        writeln!(self.dest, "    sar rax, {}", FIXNUM_SHIFT)?;
+        writeln!(self.dest, "    mov r8, 10")?;
+        writeln!(self.dest, "    xor ecx, ecx")?;
+        writeln!(self.dest, "    test rax, rax")?;
+        writeln!(self.dest, "    jns rt_number_to_string_count")?;
+        writeln!(self.dest, "    neg rax")?;
+        writeln!(self.dest, "    inc rcx")?;
+        writeln!(self.dest, "rt_number_to_string_count:")?;
+        writeln!(self.dest, "    xor edx, edx")?;
+        writeln!(self.dest, "    div r8")?;
+        writeln!(self.dest, "    inc rcx")?;
+        writeln!(self.dest, "    test rax, rax")?;
+        writeln!(self.dest, "    jnz rt_number_to_string_count")?;
+        writeln!(self.dest, "    lea rdi, [rcx*8]")?;
+        writeln!(self.dest, "    push rdi")?;
+        writeln!(self.dest, "    and rcx, -8")?;
+        writeln!(self.dest, "    lea rdi, [rcx+16]")?;
+        writeln!(self.dest, "    call rt_alloc")?;
+        writeln!(self.dest, "    pop rcx")?;
+        writeln!(self.dest, "    shr rcx, {}", FIXNUM_SHIFT)?;
+        self.gen_string_header("rcx")?;
+        writeln!(self.dest, "    mov rsi, rax")?;
+        writeln!(self.dest, "    mov rax, QWORD PTR [rsp+8]")?;
+        writeln!(self.dest, "    sar rax, {}", FIXNUM_SHIFT)?;
+        writeln!(self.dest, "    mov r8, 10")?;
+        writeln!(self.dest, "    mov r9, rax")?;
+        writeln!(self.dest, "    test rax, rax")?;
+        writeln!(self.dest, "    jns rt_number_to_string_digits")?;
+        writeln!(self.dest, "    neg rax")?;
+        writeln!(self.dest, "rt_number_to_string_digits:")?;
+        writeln!(self.dest, "    xor edx, edx")?;
+        writeln!(self.dest, "    div r8")?;
+        writeln!(self.dest, "    add edx, {}", b'0')?;
+        writeln!(self.dest, "    mov BYTE PTR [rsi+rcx+7], dl")?;
+        writeln!(self.dest, "    dec rcx")?;
+        writeln!(self.dest, "    test rax, rax")?;
+        writeln!(self.dest, "    jnz rt_number_to_string_digits")?;
+        writeln!(self.dest, "    test r9, r9")?;
+        writeln!(self.dest, "    jns rt_number_to_string_done")?;
+        writeln!(self.dest, "    mov BYTE PTR [rsi+8], {}", b'-')?;
+        writeln!(self.dest, "rt_number_to_string_done:")?;
+        writeln!(self.dest, "    lea rax, [rsi+{}]", OBJ_TAG)?;
+        writeln!(self.dest, "    ret 8")?;
+
+        // a bignum has fewer than 24 digits for each limb. they are written at the end of a
+        // string of that length and moved to its beginning.
+        writeln!(self.dest, "rt_number_to_string_big:")?;
+        writeln!(self.dest, "    push rbp")?;
+        writeln!(self.dest, "    mov rbp, rsp")?;
+        writeln!(self.dest, "    call rt_bignum_size")?;
+        writeln!(self.dest, "    imul rax, rax, 24")?;
+        writeln!(self.dest, "    push rax")?;
+        writeln!(self.dest, "    lea rdi, [rax+16]")?;
+        writeln!(self.dest, "    call rt_alloc")?;
+        writeln!(self.dest, "    push rax")?;
+        writeln!(self.dest, "    mov rdi, QWORD PTR [rbp+16]")?;
+        writeln!(self.dest, "    lea rsi, [rax+8]")?;
+        writeln!(self.dest, "    add rsi, QWORD PTR [rbp-8]")?;
+        writeln!(self.dest, "    push rsi")?;
+        writeln!(self.dest, "    call rt_bignum_digits")?;
+        writeln!(self.dest, "    mov rsi, rax")?;
+        writeln!(self.dest, "    pop rcx")?;
+        writeln!(self.dest, "    sub rcx, rax")?;
+        writeln!(self.dest, "    mov r8, rcx")?;
+        writeln!(self.dest, "    mov rax, QWORD PTR [rbp-16]")?;
+        writeln!(self.dest, "    lea rdi, [rax+8]")?;
+        writeln!(self.dest, "    rep movsb")?;
+        self.gen_string_header("r8")?;
+        writeln!(self.dest, "    or rax, {}", OBJ_TAG)?;
+        writeln!(self.dest, "    leave")?;
+        writeln!(self.dest, "    ret 8")?;
+        Ok(())
+    }
+
+    // a character is its code shifted left by 8 with the tag below. the case conversions and
+    // the classes of characters cover ASCII only.
+    fn gen_chars(&mut self) -> io::Result<()> {
+        self.gen_routine("rt_char_to_integer")?;
+        writeln!(self.dest, "    mov rax, QWORD PTR [rsp+8]")?;
+        self.check_char("rax")?;
+        writeln!(self.dest, "    shr rax, {}", CHAR_SHIFT)?;
+        writeln!(self.dest, "    shl rax, {}", FIXNUM_SHIFT)?;
+        writeln!(self.dest, "    ret 8")?;
+
+        // the code must be a unicode scalar value, below 0x110000 and not a surrogate
+        self.gen_routine("rt_integer_to_char")?;
+        writeln!(self.dest, "    mov rax, QWORD PTR [rsp+8]")?;
+        self.check_tag("rax", FIXNUM_TAG)?;
+        writeln!(self.dest, "    cmp rax, {}", 0x110000 << FIXNUM_SHIFT)?;
+        self.gen_error("jb", "rt_range_error", Some("rax"))?;
+        writeln!(self.dest, "    lea rdx, [rax-{}]", 0xd800 << FIXNUM_SHIFT)?;
+        writeln!(self.dest, "    cmp rdx, {}", 0x800 << FIXNUM_SHIFT)?;
+        self.gen_error("jae", "rt_range_error", Some("rax"))?;
+        writeln!(self.dest, "    shr rax, {}", FIXNUM_SHIFT)?;
+        writeln!(self.dest, "    shl rax, {}", CHAR_SHIFT)?;
+        writeln!(self.dest, "    or rax, {}", CHAR_TAG)?;
+        writeln!(self.dest, "    ret 8")?;
+
+        for (label, set) in [("equal", "sete"), ("lth", "setb"), ("leq", "setbe"), ("gth", "seta"), ("geq", "setae")] {
+            self.gen_compare(&format!("rt_char_{}", label), set, "cmp rdi, rsi", |gen, reg| gen.check_char(reg))?;
+        }
+
+        // the letters of the other case are 32 apart
+        for (label, from, diff) in [("rt_char_upcase", b'a', -32), ("rt_char_downcase", b'A', 32)] {
+            self.gen_routine(label)?;
+            writeln!(self.dest, "    mov rax, QWORD PTR [rsp+8]")?;
+            self.check_char("rax")?;
+            writeln!(self.dest, "    shr rax, {}", CHAR_SHIFT)?;
+            writeln!(self.dest, "    lea rdx, [rax{:+}]", diff)?;
+            writeln!(self.dest, "    lea rcx, [rax-{}]", from)?;
+            writeln!(self.dest, "    cmp rcx, 26")?;
+            writeln!(self.dest, "    cmovb rax, rdx")?;
+            writeln!(self.dest, "    shl rax, {}", CHAR_SHIFT)?;
+            writeln!(self.dest, "    or rax, {}", CHAR_TAG)?;
+            writeln!(self.dest, "    ret 8")?;
+        }
+
+        // a letter of either case becomes lowercase when the bit 5 is set
+        self.gen_routine("rt_is_char_alphabetic")?;
+        writeln!(self.dest, "    mov rax, QWORD PTR [rsp+8]")?;
+        self.check_char("rax")?;
+        writeln!(self.dest, "    shr rax, {}", CHAR_SHIFT)?;
+        writeln!(self.dest, "    or rax, 32")?;
+        writeln!(self.dest, "    sub rax, {}", b'a')?;
+        writeln!(self.dest, "    cmp rax, 26")?;
+        self.gen_bool("setb")?;
+        writeln!(self.dest, "    ret 8")?;
+
+        self.gen_routine("rt_is_char_numeric")?;
+        writeln!(self.dest, "    mov rax, QWORD PTR [rsp+8]")?;
+        self.check_char("rax")?;
+        writeln!(self.dest, "    shr rax, {}", CHAR_SHIFT)?;
+        writeln!(self.dest, "    sub rax, {}", b'0')?;
+        writeln!(self.dest, "    cmp rax, 10")?;
+        self.gen_bool("setb")?;
+        writeln!(self.dest, "    ret 8")?;
+
+        // the space, and tab, newline, vertical tab, form feed and return, which are 9 to 13
+        self.gen_routine("rt_is_char_whitespace")?;
+        writeln!(self.dest, "    mov rax, QWORD PTR [rsp+8]")?;
+        self.check_char("rax")?;
+        writeln!(self.dest, "    shr rax, {}", CHAR_SHIFT)?;
+        writeln!(self.dest, "    cmp rax, {}", b' ')?;
+        writeln!(self.dest, "    sete dl")?;
+        writeln!(self.dest, "    sub rax, {}", b'\t')?;
+        writeln!(self.dest, "    cmp rax, 5")?;
+        writeln!(self.dest, "    setb al")?;
+        writeln!(self.dest, "    or al, dl")?;
+        self.gen_bool("setnz")?;
+        writeln!(self.dest, "    ret 8")?;
+        Ok(())
+    }
+
+    // a vector holds its values after the header and a bytevector its bytes, like a string.
+    // the routines taking an optional fill check the number of their arguments themselves.
+    fn gen_vectors(&mut self) -> io::Result<()> {
+        for (label, type_) in [("rt_make_vector", VECTOR_TYPE), ("rt_make_bytevector", BYTEVECTOR_TYPE)] {
+            let bytes = type_ == BYTEVECTOR_TYPE;
+            self.gen_routine(label)?;
+            self.check_max_args(2)?;
+            writeln!(self.dest, "    mov rdi, QWORD PTR [rsp+8]")?;
+            self.check_length("rdi")?;
+            writeln!(self.dest, "    mov eax, {}", if bytes { 0 } else { VOID })?;
+            writeln!(self.dest, "    cmp rcx, 2")?;
+            writeln!(self.dest, "    cmove rax, QWORD PTR [rsp+16]")?;
+            if bytes {
+                self.check_byte("rax")?;
+            }
+            writeln!(self.dest, "    push rax")?;
+            writeln!(self.dest, "    shl rcx, {}", FIXNUM_SHIFT)?;
+            writeln!(self.dest, "    push rcx")?;
+            writeln!(self.dest, "    mov rdi, QWORD PTR [rsp+24]")?;
+            self.gen_alloc_size(bytes)?;
+            writeln!(self.dest, "    call rt_alloc")?;
+            writeln!(self.dest, "    mov rcx, QWORD PTR [rsp+24]")?;
+            writeln!(self.dest, "    sar rcx, {}", FIXNUM_SHIFT)?;
+            self.gen_header(type_, "rcx")?;
+            writeln!(self.dest, "    mov rdx, rax")?;
+            writeln!(self.dest, "    mov rax, QWORD PTR [rsp+8]")?;
+            writeln!(self.dest, "    lea rdi, [rdx+8]")?;
+            if bytes {
+                writeln!(self.dest, "    shr rax, {}", FIXNUM_SHIFT)?;
+                writeln!(self.dest, "    rep stosb")?;
+            } else {
+                writeln!(self.dest, "    rep stosq")?;
+            }
+            writeln!(self.dest, "    lea rax, [rdx+{}]", OBJ_TAG)?;
+            writeln!(self.dest, "    pop rcx")?;
+            writeln!(self.dest, "    shr rcx, {}", FIXNUM_SHIFT)?;
+            writeln!(self.dest, "    pop rdx")?;
+            writeln!(self.dest, "    pop rdx")?;
+            writeln!(self.dest, "    lea rsp, [rsp+8*rcx]")?;
+            writeln!(self.dest, "    jmp rdx")?;
+        }
+
+        // (vector obj ...) and (bytevector byte ...) hold their arguments
+        for (label, type_) in [("rt_vector", VECTOR_TYPE), ("rt_bytevector", BYTEVECTOR_TYPE)] {
+            let bytes = type_ == BYTEVECTOR_TYPE;
+            self.gen_routine(label)?;
+            if bytes {
+                writeln!(self.dest, "    mov rdx, rcx")?;
+                writeln!(self.dest, "{}_check:", label)?;
+                writeln!(self.dest, "    test rdx, rdx")?;
+                writeln!(self.dest, "    jz {}_alloc", label)?;
+                writeln!(self.dest, "    mov rdi, QWORD PTR [rsp+8*rdx]")?;
+                self.check_byte("rdi")?;
+                writeln!(self.dest, "    dec rdx")?;
+                writeln!(self.dest, "    jmp {}_check", label)?;
+                writeln!(self.dest, "{}_alloc:", label)?;
+            }
+            writeln!(self.dest, "    shl rcx, {}", FIXNUM_SHIFT)?;
+            writeln!(self.dest, "    push rcx")?;
+            writeln!(self.dest, "    mov rdi, rcx")?;
+            self.gen_alloc_size(bytes)?;
+            writeln!(self.dest, "    call rt_alloc")?;
+            writeln!(self.dest, "    mov rcx, QWORD PTR [rsp]")?;
+            writeln!(self.dest, "    shr rcx, {}", FIXNUM_SHIFT)?;
+            self.gen_header(type_, "rcx")?;
+            writeln!(self.dest, "    xor edx, edx")?;
+            writeln!(self.dest, "{}_loop:", label)?;
+            writeln!(self.dest, "    cmp rdx, rcx")?;
+            writeln!(self.dest, "    jae {}_done", label)?;
+            writeln!(self.dest, "    mov rdi, QWORD PTR [rsp+8*rdx+16]")?;
+            if bytes {
+                writeln!(self.dest, "    shr rdi, {}", FIXNUM_SHIFT)?;
+                writeln!(self.dest, "    mov BYTE PTR [rax+rdx+8], dil")?;
+            } else {
+                writeln!(self.dest, "    mov QWORD PTR [rax+8*rdx+8], rdi")?;
+            }
+            writeln!(self.dest, "    inc rdx")?;
+            writeln!(self.dest, "    jmp {}_loop", label)?;
+            writeln!(self.dest, "{}_done:", label)?;
+            writeln!(self.dest, "    or rax, {}", OBJ_TAG)?;
+            writeln!(self.dest, "    pop rcx")?;
+            writeln!(self.dest, "    shr rcx, {}", FIXNUM_SHIFT)?;
+            writeln!(self.dest, "    pop rdx")?;
+            writeln!(self.dest, "    lea rsp, [rsp+8*rcx]")?;
+            writeln!(self.dest, "    jmp rdx")?;
+        }
+
+        self.gen_type_predicate("rt_is_bytevector", BYTEVECTOR_TYPE)?;
+
+        for (label, type_) in [("rt_vector_length", VECTOR_TYPE), ("rt_bytevector_length", BYTEVECTOR_TYPE)] {
+            self.gen_routine(label)?;
+            writeln!(self.dest, "    mov rax, QWORD PTR [rsp+8]")?;
+            self.check_type("rax", type_)?;
+            writeln!(self.dest, "    mov rax, QWORD PTR [rax-{}]", OBJ_TAG)?;
+            writeln!(self.dest, "    shr rax, 8")?;
+            writeln!(self.dest, "    shl rax, {}", FIXNUM_SHIFT)?;
+            writeln!(self.dest, "    ret 8")?;
+        }
+
+        self.gen_routine("rt_vector_ref")?;
+        writeln!(self.dest, "    mov rax, QWORD PTR [rsp+8]")?;
+        writeln!(self.dest, "    mov rdi, QWORD PTR [rsp+16]")?;
+        self.check_type("rax", VECTOR_TYPE)?;
+        self.check_index("rax", "rdi")?;
+        writeln!(self.dest, "    mov rax, QWORD PTR [rax+8*rdi+{}]", 8 - OBJ_TAG)?;
+        writeln!(self.dest, "    ret 16")?;
+
+        self.gen_routine("rt_vector_set")?;
+        writeln!(self.dest, "    mov rax, QWORD PTR [rsp+8]")?;
+        writeln!(self.dest, "    mov rdi, QWORD PTR [rsp+16]")?;
+        self.check_type("rax", VECTOR_TYPE)?;
+        self.check_mutable("rax")?;
+        self.check_index("rax", "rdi")?;
+        writeln!(self.dest, "    mov rsi, QWORD PTR [rsp+24]")?;
+        writeln!(self.dest, "    mov QWORD PTR [rax+8*rdi+{}], rsi", 8 - OBJ_TAG)?;
+        writeln!(self.dest, "    mov eax, {}", VOID)?;
+        writeln!(self.dest, "    ret 24")?;
+
+        self.gen_routine("rt_bytevector_u8_ref")?;
+        writeln!(self.dest, "    mov rax, QWORD PTR [rsp+8]")?;
+        writeln!(self.dest, "    mov rdi, QWORD PTR [rsp+16]")?;
+        self.check_type("rax", BYTEVECTOR_TYPE)?;
+        self.check_index("rax", "rdi")?;
+        writeln!(self.dest, "    movzx eax, BYTE PTR [rax+rdi+{}]", 8 - OBJ_TAG)?;
+        writeln!(self.dest, "    shl rax, {}", FIXNUM_SHIFT)?;
+        writeln!(self.dest, "    ret 16")?;
+
+        self.gen_routine("rt_bytevector_u8_set")?;
+        writeln!(self.dest, "    mov rax, QWORD PTR [rsp+8]")?;
+        writeln!(self.dest, "    mov rdi, QWORD PTR [rsp+16]")?;
+        writeln!(self.dest, "    mov rsi, QWORD PTR [rsp+24]")?;
+        self.check_type("rax", BYTEVECTOR_TYPE)?;
+        self.check_mutable("rax")?;
+        self.check_index("rax", "rdi")?;
+        self.check_byte("rsi")?;
+        writeln!(self.dest, "    shr rsi, {}", FIXNUM_SHIFT)?;
+        writeln!(self.dest, "    mov BYTE PTR [rax+rdi+{}], sil", 8 - OBJ_TAG)?;
+        writeln!(self.dest, "    mov eax, {}", VOID)?;
+        writeln!(self.dest, "    ret 24")?;
+
+        self.gen_routine("rt_bytevector_copy")?;
+        writeln!(self.dest, "    mov rdi, QWORD PTR [rsp+8]")?;
+        self.check_type("rdi", BYTEVECTOR_TYPE)?;
+        writeln!(self.dest, "    mov rdi, QWORD PTR [rdi-{}]", OBJ_TAG)?;
+        writeln!(self.dest, "    shr rdi, 8")?;
+        writeln!(self.dest, "    shl rdi, {}", FIXNUM_SHIFT)?;
+        self.gen_alloc_size(true)?;
+        writeln!(self.dest, "    call rt_alloc")?;
+        writeln!(self.dest, "    mov rsi, QWORD PTR [rsp+8]")?;
+        writeln!(self.dest, "    mov rcx, QWORD PTR [rsi-{}]", OBJ_TAG)?;
+        writeln!(self.dest, "    mov QWORD PTR [rax], rcx")?;
+        writeln!(self.dest, "    shr rcx, 8")?;
+        writeln!(self.dest, "    add rsi, {}", 8 - OBJ_TAG)?;
+        writeln!(self.dest, "    lea rdi, [rax+8]")?;
+        writeln!(self.dest, "    rep movsb")?;
+        writeln!(self.dest, "    or rax, {}", OBJ_TAG)?;
+        writeln!(self.dest, "    ret 8")?;
+
+        self.gen_routine("rt_vector_fill")?;
+        writeln!(self.dest, "    mov rdx, QWORD PTR [rsp+8]")?;
+        self.check_type("rdx", VECTOR_TYPE)?;
+        self.check_mutable("rdx")?;
+        writeln!(self.dest, "    mov rcx, QWORD PTR [rdx-{}]", OBJ_TAG)?;
+        writeln!(self.dest, "    shr rcx, 8")?;
+        writeln!(self.dest, "    lea rdi, [rdx+{}]", 8 - OBJ_TAG)?;
+        writeln!(self.dest, "    mov rax, QWORD PTR [rsp+16]")?;
+        writeln!(self.dest, "    rep stosq")?;
+        writeln!(self.dest, "    mov eax, {}", VOID)?;
+        writeln!(self.dest, "    ret 16")?;
+
+        // conses the elements from the last one. the stack holds the list and the index.
+        self.gen_routine("rt_vector_to_list")?;
+        writeln!(self.dest, "    mov rax, QWORD PTR [rsp+8]")?;
+        self.check_type("rax", VECTOR_TYPE)?;
+        writeln!(self.dest, "    mov rcx, QWORD PTR [rax-{}]", OBJ_TAG)?;
+        writeln!(self.dest, "    shr rcx, 8")?;
+        writeln!(self.dest, "    shl rcx, {}", FIXNUM_SHIFT)?;
+        writeln!(self.dest, "    push {}", NIL)?;
+        writeln!(self.dest, "    push rcx")?;
+        writeln!(self.dest, "rt_vector_to_list_loop:")?;
+        writeln!(self.dest, "    mov rcx, QWORD PTR [rsp]")?;
+        writeln!(self.dest, "    test rcx, rcx")?;
+        writeln!(self.dest, "    jz rt_vector_to_list_done")?;
+        writeln!(self.dest, "    sub rcx, 8")?;
+        writeln!(self.dest, "    mov QWORD PTR [rsp], rcx")?;
+        writeln!(self.dest, "    mov rdi, 16")?;
+        writeln!(self.dest, "    call rt_alloc")?;
+        writeln!(self.dest, "    mov rcx, QWORD PTR [rsp]")?;
+        writeln!(self.dest, "    mov rdx, QWORD PTR [rsp+24]")?;
+        writeln!(self.dest, "    mov rdi, QWORD PTR [rdx+rcx+{}]", 8 - OBJ_TAG)?;
+        writeln!(self.dest, "    mov QWORD PTR [rax], rdi")?;
+        writeln!(self.dest, "    mov rdi, QWORD PTR [rsp+8]")?;
+        writeln!(self.dest, "    mov QWORD PTR [rax+8], rdi")?;
+        writeln!(self.dest, "    or rax, {}", PAIR_TAG)?;
+        writeln!(self.dest, "    mov QWORD PTR [rsp+8], rax")?;
+        writeln!(self.dest, "    jmp rt_vector_to_list_loop")?;
+        writeln!(self.dest, "rt_vector_to_list_done:")?;
+        writeln!(self.dest, "    pop rcx")?;
+        writeln!(self.dest, "    pop rax")?;
+        writeln!(self.dest, "    ret 8")?;
+
+        // the list is measured first, then copied into the new vector
+        self.gen_routine("rt_list_to_vector")?;
+        writeln!(self.dest, "    mov rdx, QWORD PTR [rsp+8]")?;
+        writeln!(self.dest, "    mov edi, 8")?;
+        writeln!(self.dest, "rt_list_to_vector_length:")?;
+        writeln!(self.dest, "    cmp rdx, {}", NIL)?;
+        writeln!(self.dest, "    je rt_list_to_vector_alloc")?;
+        writeln!(self.dest, "    mov r11, rdx")?;
+        writeln!(self.dest, "    and r11, {}", TAG_MASK)?;
+        writeln!(self.dest, "    cmp r11, {}", PAIR_TAG)?;
+        self.gen_error("je", "rt_type_error", Some("QWORD PTR [rsp+8]"))?;
+        writeln!(self.dest, "    add rdi, 8")?;
+        writeln!(self.dest, "    mov rdx, QWORD PTR [rdx+{}]", 8 - PAIR_TAG)?;
+        writeln!(self.dest, "    jmp rt_list_to_vector_length")?;
+        writeln!(self.dest, "rt_list_to_vector_alloc:")?;
+        writeln!(self.dest, "    push rdi")?;
+        writeln!(self.dest, "    call rt_alloc")?;
+        writeln!(self.dest, "    pop rcx")?;
+        writeln!(self.dest, "    shr rcx, {}", FIXNUM_SHIFT)?;
+        writeln!(self.dest, "    dec rcx")?;
+        self.gen_header(VECTOR_TYPE, "rcx")?;
+        writeln!(self.dest, "    mov rdx, QWORD PTR [rsp+8]")?;
+        writeln!(self.dest, "    lea rdi, [rax+8]")?;
+        writeln!(self.dest, "rt_list_to_vector_loop:")?;
+        writeln!(self.dest, "    cmp rdx, {}", NIL)?;
+        writeln!(self.dest, "    je rt_list_to_vector_done")?;
+        writeln!(self.dest, "    mov rcx, QWORD PTR [rdx-{}]", PAIR_TAG)?;
+        writeln!(self.dest, "    mov QWORD PTR [rdi], rcx")?;
+        writeln!(self.dest, "    add rdi, 8")?;
+        writeln!(self.dest, "    mov rdx, QWORD PTR [rdx+{}]", 8 - PAIR_TAG)?;
+        writeln!(self.dest, "    jmp rt_list_to_vector_loop")?;
+        writeln!(self.dest, "rt_list_to_vector_done:")?;
+        writeln!(self.dest, "    or rax, {}", OBJ_TAG)?;
+        writeln!(self.dest, "    ret 8")?;
+
+        // (vector-map proc vector ...) and (vector-for-each proc vector ...) go up to the length
+        // of the shortest vector. the stack holds the number of the arguments, the index, the
+        // length and the result, which is filled with unspecified values before any call.
+        for (label, collect) in [("rt_vector_map", true), ("rt_vector_for_each", false)] {
+            self.gen_routine(label)?;
+            writeln!(self.dest, "    lea r10, [rcx-1]")?;
+            writeln!(self.dest, "    mov rsi, -1")?;
+            writeln!(self.dest, "{}_check:", label)?;
+            writeln!(self.dest, "    mov rdx, QWORD PTR [rsp+8*r10+8]")?;
+            self.check_type("rdx", VECTOR_TYPE)?;
+            writeln!(self.dest, "    mov rdx, QWORD PTR [rdx-{}]", OBJ_TAG)?;
+            writeln!(self.dest, "    shr rdx, 8")?;
+            writeln!(self.dest, "    cmp rdx, rsi")?;
+            writeln!(self.dest, "    cmovb rsi, rdx")?;
+            writeln!(self.dest, "    dec r10")?;
+            writeln!(self.dest, "    jnz {}_check", label)?;
+            writeln!(self.dest, "    push {}", VOID)?;
+            writeln!(self.dest, "    shl rsi, {}", FIXNUM_SHIFT)?;
+            writeln!(self.dest, "    push rsi")?;
+            writeln!(self.dest, "    push 0")?;
+            writeln!(self.dest, "    shl rcx, {}", FIXNUM_SHIFT)?;
+            writeln!(self.dest, "    push rcx")?;
+            if collect {
+                writeln!(self.dest, "    mov rdi, rsi")?;
+                self.gen_alloc_size(false)?;
+                writeln!(self.dest, "    call rt_alloc")?;
+                writeln!(self.dest, "    mov rcx, QWORD PTR [rsp+16]")?;
+                writeln!(self.dest, "    shr rcx, {}", FIXNUM_SHIFT)?;
+                self.gen_header(VECTOR_TYPE, "rcx")?;
+                writeln!(self.dest, "    mov rdx, rax")?;
+                writeln!(self.dest, "    mov eax, {}", VOID)?;
+                writeln!(self.dest, "    lea rdi, [rdx+8]")?;
+                writeln!(self.dest, "    rep stosq")?;
+                writeln!(self.dest, "    lea rax, [rdx+{}]", OBJ_TAG)?;
+                writeln!(self.dest, "    mov QWORD PTR [rsp+24], rax")?;
+            }
+            writeln!(self.dest, "{}_loop:", label)?;
+            writeln!(self.dest, "    mov rax, QWORD PTR [rsp+8]")?;
+            writeln!(self.dest, "    cmp rax, QWORD PTR [rsp+16]")?;
+            writeln!(self.dest, "    je {}_done", label)?;
+            writeln!(self.dest, "    mov r8, QWORD PTR [rsp]")?;
+            writeln!(self.dest, "    shr r8, {}", FIXNUM_SHIFT)?;
+            writeln!(self.dest, "    dec r8")?;
+            writeln!(self.dest, "    lea r9, [rsp+48]")?;
+            writeln!(self.dest, "    mov r10, r8")?;
+            writeln!(self.dest, "{}_next:", label)?;
+            writeln!(self.dest, "    mov rdx, QWORD PTR [r9+8*r10-8]")?;
+            writeln!(self.dest, "    push QWORD PTR [rdx+rax+{}]", 8 - OBJ_TAG)?;
+            writeln!(self.dest, "    dec r10")?;
+            writeln!(self.dest, "    jnz {}_next", label)?;
+            writeln!(self.dest, "    mov rax, QWORD PTR [r9-8]")?;
+            writeln!(self.dest, "    mov rcx, r8")?;
+            self.gen_check_call(None)?;
+            writeln!(self.dest, "    call QWORD PTR [rax+{}]", 8 - PROC_TAG)?;
+            if collect {
+                writeln!(self.dest, "    mov rdx, QWORD PTR [rsp+24]")?;
+                writeln!(self.dest, "    mov rcx, QWORD PTR [rsp+8]")?;
+                writeln!(self.dest, "    mov QWORD PTR [rdx+rcx+{}], rax", 8 - OBJ_TAG)?;
+            }
+            writeln!(self.dest, "    add QWORD PTR [rsp+8], 8")?;
+            writeln!(self.dest, "    jmp {}_loop", label)?;
+            writeln!(self.dest, "{}_done:", label)?;
+            writeln!(self.dest, "    pop rcx")?;
+            writeln!(self.dest, "    shr rcx, {}", FIXNUM_SHIFT)?;
+            writeln!(self.dest, "    add rsp, 16")?;
+            writeln!(self.dest, "    pop rax")?;
+            writeln!(self.dest, "    pop rdx")?;
+            writeln!(self.dest, "    lea rsp, [rsp+8*rcx]")?;
+            writeln!(self.dest, "    jmp rdx")?;
+        }
+        Ok(())
+    }
+
+    // turns the length in rdi, 8 times the number of the values or of the bytes, into the
+    // size of the object. raw objects keep at least one byte after their data.
+    fn gen_alloc_size(&mut self, bytes: bool) -> io::Result<()> {
+        if bytes {
+            writeln!(self.dest, "    sar rdi, {}", FIXNUM_SHIFT)?;
+            writeln!(self.dest, "    and rdi, -8")?;
+            writeln!(self.dest, "    add rdi, 16")?;
+        } else {
+            writeln!(self.dest, "    add rdi, 8")?;
+        }
+        Ok(())
+    }
+
+    // writes the header of an object of the length in `len` to the object at rax
+    fn gen_header(&mut self, type_: i64, len: &str) -> io::Result<()> {
+        writeln!(self.dest, "    mov r10, {}", len)?;
+        writeln!(self.dest, "    shl r10, 8")?;
+        writeln!(self.dest, "    or r10, {}", header(type_, 0))?;
+        writeln!(self.dest, "    mov QWORD PTR [rax], r10")?;
+        Ok(())
+    }
+
+    // the list library. routines that allocate keep the lists they build on the stack,
+    // as the head and the last pair, and advance their list arguments in place.
+    fn gen_lists(&mut self) -> io::Result<()> {
+        // car, cdr and their compositions up to four levels, where cadr is (car (cdr x))
+        for len in 1..5 {
+            for bits in 0..1 << len {
+                let path: String = (0..len).map(|i| if bits >> (len - 1 - i) & 1 == 0 { 'a' } else { 'd' }).collect();
+                self.gen_routine(&format!("rt_c{}r", path))?;
+                writeln!(self.dest, "    mov rax, QWORD PTR [rsp+8]")?;
+                for step in path.chars().rev() {
+                    self.check_tag("rax", PAIR_TAG)?;
+                    let offset = if step == 'a' { -PAIR_TAG } else { 8 - PAIR_TAG };
+                    writeln!(self.dest, "    mov rax, QWORD PTR [rax{:+}]", offset)?;
+                }
+                writeln!(self.dest, "    ret 8")?;
+            }
+        }
+
+        for (label, offset) in [("rt_set_car", -PAIR_TAG), ("rt_set_cdr", 8 - PAIR_TAG)] {
+            self.gen_routine(label)?;
+            writeln!(self.dest, "    mov rax, QWORD PTR [rsp+8]")?;
+            self.check_tag("rax", PAIR_TAG)?;
+            self.check_mutable("rax")?;
+            writeln!(self.dest, "    mov rdi, QWORD PTR [rsp+16]")?;
+            writeln!(self.dest, "    mov QWORD PTR [rax{:+}], rdi", offset)?;
+            writeln!(self.dest, "    mov eax, {}", VOID)?;
+            writeln!(self.dest, "    ret 16")?;
         }
 
-        writeln!(self.dest, "    pop rax").unwrap();
-        writeln!(self.dest, "    mov rsp, rbp").unwrap();
-        writeln!(self.dest, "    pop rbp").unwrap();
-        writeln!(self.dest, "    ret").unwrap();
+        // conses the arguments from the last one. the stack holds the list, the number of
+        // the arguments and the number of those left.
+        self.gen_routine("rt_list")?;
+        writeln!(self.dest, "    push {}", NIL)?;
+        writeln!(self.dest, "    shl rcx, {}", FIXNUM_SHIFT)?;
+        writeln!(self.dest, "    push rcx")?;
+        writeln!(self.dest, "    push rcx")?;
+        writeln!(self.dest, "rt_list_loop:")?;
+        writeln!(self.dest, "    mov rcx, QWORD PTR [rsp]")?;
+        writeln!(self.dest, "    test rcx, rcx")?;
+        writeln!(self.dest, "    jz rt_list_done")?;
+        writeln!(self.dest, "    sub rcx, 8")?;
+        writeln!(self.dest, "    mov QWORD PTR [rsp], rcx")?;
+        writeln!(self.dest, "    mov rdi, 16")?;
+        writeln!(self.dest, "    call rt_alloc")?;
+        writeln!(self.dest, "    mov rcx, QWORD PTR [rsp]")?;
+        writeln!(self.dest, "    mov rdi, QWORD PTR [rsp+rcx+32]")?;
+        writeln!(self.dest, "    mov QWORD PTR [rax], rdi")?;
+        writeln!(self.dest, "    mov rdi, QWORD PTR [rsp+16]")?;
+        writeln!(self.dest, "    mov QWORD PTR [rax+8], rdi")?;
+        writeln!(self.dest, "    or rax, {}", PAIR_TAG)?;
+        writeln!(self.dest, "    mov QWORD PTR [rsp+16], rax")?;
+        writeln!(self.dest, "    jmp rt_list_loop")?;
+        writeln!(self.dest, "rt_list_done:")?;
+        writeln!(self.dest, "    pop rcx")?;
+        writeln!(self.dest, "    pop rcx")?;
+        writeln!(self.dest, "    shr rcx, {}", FIXNUM_SHIFT)?;
+        writeln!(self.dest, "    pop rax")?;
+        writeln!(self.dest, "    pop rdx")?;
+        writeln!(self.dest, "    lea rsp, [rsp+8*rcx]")?;
+        writeln!(self.dest, "    jmp rdx")?;
+
+        // rdi follows rdx at half its pace, as in list?, and meets it if the list is circular
+        self.gen_routine("rt_length")?;
+        writeln!(self.dest, "    mov rdx, QWORD PTR [rsp+8]")?;
+        writeln!(self.dest, "    mov rdi, rdx")?;
+        writeln!(self.dest, "    xor eax, eax")?;
+        writeln!(self.dest, "rt_length_loop:")?;
+        writeln!(self.dest, "    cmp rdx, {}", NIL)?;
+        writeln!(self.dest, "    je rt_length_done")?;
+        writeln!(self.dest, "    mov r11, rdx")?;
+        writeln!(self.dest, "    and r11, {}", TAG_MASK)?;
+        writeln!(self.dest, "    cmp r11, {}", PAIR_TAG)?;
+        self.gen_error("je", "rt_type_error", Some("QWORD PTR [rsp+8]"))?;
+        writeln!(self.dest, "    add rax, {}", 1 << FIXNUM_SHIFT)?;
+        writeln!(self.dest, "    mov rdx, QWORD PTR [rdx+{}]", 8 - PAIR_TAG)?;
+        writeln!(self.dest, "    test rax, {}", 1 << FIXNUM_SHIFT)?;
+        writeln!(self.dest, "    jnz rt_length_loop")?;
+        writeln!(self.dest, "    mov rdi, QWORD PTR [rdi+{}]", 8 - PAIR_TAG)?;
+        writeln!(self.dest, "    cmp rdx, rdi")?;
+        self.gen_error("jne", "rt_circular_error", None)?;
+        writeln!(self.dest, "    jmp rt_length_loop")?;
+        writeln!(self.dest, "rt_length_done:")?;
+        writeln!(self.dest, "    ret 8")?;
+
+        // the lists are appended from the right, so every list but the last is copied once.
+        // the stack holds the number of the arguments and the number of those left.
+        self.gen_routine("rt_append")?;
+        writeln!(self.dest, "    shl rcx, {}", FIXNUM_SHIFT)?;
+        writeln!(self.dest, "    push rcx")?;
+        writeln!(self.dest, "    push rcx")?;
+        writeln!(self.dest, "    mov eax, {}", NIL)?;
+        writeln!(self.dest, "    test rcx, rcx")?;
+        writeln!(self.dest, "    jz rt_append_done")?;
+        writeln!(self.dest, "    sub QWORD PTR [rsp], 8")?;
+        writeln!(self.dest, "    mov rax, QWORD PTR [rsp+rcx+16]")?;
+        writeln!(self.dest, "rt_append_loop:")?;
+        writeln!(self.dest, "    mov rcx, QWORD PTR [rsp]")?;
+        writeln!(self.dest, "    test rcx, rcx")?;
+        writeln!(self.dest, "    jz rt_append_done")?;
+        writeln!(self.dest, "    sub rcx, 8")?;
+        writeln!(self.dest, "    mov QWORD PTR [rsp], rcx")?;
+        writeln!(self.dest, "    push rax")?;
+        writeln!(self.dest, "    push QWORD PTR [rsp+rcx+32]")?;
+        writeln!(self.dest, "    call rt_append2")?;
+        writeln!(self.dest, "    jmp rt_append_loop")?;
+        writeln!(self.dest, "rt_append_done:")?;
+        writeln!(self.dest, "    pop rcx")?;
+        writeln!(self.dest, "    pop rcx")?;
+        writeln!(self.dest, "    shr rcx, {}", FIXNUM_SHIFT)?;
+        writeln!(self.dest, "    pop rdx")?;
+        writeln!(self.dest, "    lea rsp, [rsp+8*rcx]")?;
+        writeln!(self.dest, "    jmp rdx")?;
+
+        // copies the first list and ends it with the second. the argument slot holds the rest of the list.
+        writeln!(self.dest, "rt_append2:")?;
+        writeln!(self.dest, "    mov rax, QWORD PTR [rsp+8]")?;
+        writeln!(self.dest, "    cmp rax, {}", NIL)?;
+        writeln!(self.dest, "    jne rt_append2_copy")?;
+        writeln!(self.dest, "    mov rax, QWORD PTR [rsp+16]")?;
+        writeln!(self.dest, "    ret 16")?;
+        writeln!(self.dest, "rt_append2_copy:")?;
+        writeln!(self.dest, "    push {}", NIL)?;
+        writeln!(self.dest, "    push {}", NIL)?;
+        writeln!(self.dest, "rt_append2_loop:")?;
+        writeln!(self.dest, "    mov rax, QWORD PTR [rsp+24]")?;
+        self.check_tag("rax", PAIR_TAG)?;
+        writeln!(self.dest, "    mov rdi, 16")?;
+        writeln!(self.dest, "    call rt_alloc")?;
+        writeln!(self.dest, "    mov rdx, QWORD PTR [rsp+24]")?;
+        writeln!(self.dest, "    mov rcx, QWORD PTR [rdx-{}]", PAIR_TAG)?;
+        writeln!(self.dest, "    mov QWORD PTR [rax], rcx")?;
+        writeln!(self.dest, "    mov QWORD PTR [rax+8], {}", NIL)?;
+        writeln!(self.dest, "    or rax, {}", PAIR_TAG)?;
+        self.gen_link("rt_append2", 0)?;
+        writeln!(self.dest, "    mov rdx, QWORD PTR [rdx+{}]", 8 - PAIR_TAG)?;
+        writeln!(self.dest, "    mov QWORD PTR [rsp+24], rdx")?;
+        writeln!(self.dest, "    cmp rdx, {}", NIL)?;
+        writeln!(self.dest, "    jne rt_append2_loop")?;
+        writeln!(self.dest, "    mov rcx, QWORD PTR [rsp+32]")?;
+        writeln!(self.dest, "    mov QWORD PTR [rax+{}], rcx", 8 - PAIR_TAG)?;
+        writeln!(self.dest, "    mov rax, QWORD PTR [rsp+8]")?;
+        writeln!(self.dest, "    add rsp, 16")?;
+        writeln!(self.dest, "    ret 16")?;
+
+        self.gen_routine("rt_reverse")?;
+        writeln!(self.dest, "    push {}", NIL)?;
+        writeln!(self.dest, "rt_reverse_loop:")?;
+        writeln!(self.dest, "    mov rax, QWORD PTR [rsp+16]")?;
+        writeln!(self.dest, "    cmp rax, {}", NIL)?;
+        writeln!(self.dest, "    je rt_reverse_done")?;
+        self.check_tag("rax", PAIR_TAG)?;
+        writeln!(self.dest, "    mov rdi, 16")?;
+        writeln!(self.dest, "    call rt_alloc")?;
+        writeln!(self.dest, "    mov rdx, QWORD PTR [rsp+16]")?;
+        writeln!(self.dest, "    mov rcx, QWORD PTR [rdx-{}]", PAIR_TAG)?;
+        writeln!(self.dest, "    mov QWORD PTR [rax], rcx")?;
+        writeln!(self.dest, "    mov rcx, QWORD PTR [rsp]")?;
+        writeln!(self.dest, "    mov QWORD PTR [rax+8], rcx")?;
+        writeln!(self.dest, "    or rax, {}", PAIR_TAG)?;
+        writeln!(self.dest, "    mov QWORD PTR [rsp], rax")?;
+        writeln!(self.dest, "    mov rdx, QWORD PTR [rdx+{}]", 8 - PAIR_TAG)?;
+        writeln!(self.dest, "    mov QWORD PTR [rsp+16], rdx")?;
+        writeln!(self.dest, "    jmp rt_reverse_loop")?;
+        writeln!(self.dest, "rt_reverse_done:")?;
+        writeln!(self.dest, "    pop rax")?;
+        writeln!(self.dest, "    ret 8")?;
+
+        // a list too short for the index is a range error
+        for (label, car) in [("rt_list_tail", false), ("rt_list_ref", true)] {
+            self.gen_routine(label)?;
+            writeln!(self.dest, "    mov rax, QWORD PTR [rsp+8]")?;
+            writeln!(self.dest, "    mov rcx, QWORD PTR [rsp+16]")?;
+            self.check_tag("rcx", FIXNUM_TAG)?;
+            writeln!(self.dest, "    test rcx, rcx")?;
+            self.gen_error("jns", "rt_range_error", Some("rcx"))?;
+            writeln!(self.dest, "    sar rcx, {}", FIXNUM_SHIFT)?;
+            writeln!(self.dest, "{}_loop:", label)?;
+            writeln!(self.dest, "    test rcx, rcx")?;
+            writeln!(self.dest, "    jz {}_done", label)?;
+            self.check_index_pair()?;
+            writeln!(self.dest, "    mov rax, QWORD PTR [rax+{}]", 8 - PAIR_TAG)?;
+            writeln!(self.dest, "    dec rcx")?;
+            writeln!(self.dest, "    jmp {}_loop", label)?;
+            writeln!(self.dest, "{}_done:", label)?;
+            if car {
+                self.check_index_pair()?;
+                writeln!(self.dest, "    mov rax, QWORD PTR [rax-{}]", PAIR_TAG)?;
+            }
+            writeln!(self.dest, "    ret 16")?;
+        }
+
+        // (memq obj list) returns the first pair of the list whose car is obj, and (assq obj alist)
+        // the first pair of the association list whose car is obj. member and assoc use equal?.
+        for (label, assoc, equal) in [("rt_memq", false, None), ("rt_memv", false, Some("rt_eqv_values")),
+                                      ("rt_member", false, Some("rt_equal_values")), ("rt_assq", true, None),
+                                      ("rt_assv", true, Some("rt_eqv_values")), ("rt_assoc", true, Some("rt_equal_values"))] {
+            self.gen_routine(label)?;
+            writeln!(self.dest, "    mov rdx, QWORD PTR [rsp+16]")?;
+            writeln!(self.dest, "{}_loop:", label)?;
+            writeln!(self.dest, "    mov eax, {}", FALSE)?;
+            writeln!(self.dest, "    cmp rdx, {}", NIL)?;
+            writeln!(self.dest, "    je {}_done", label)?;
+            self.check_tag("rdx", PAIR_TAG)?;
+            writeln!(self.dest, "    mov rax, rdx")?;
+            writeln!(self.dest, "    mov rsi, QWORD PTR [rdx-{}]", PAIR_TAG)?;
+            if assoc {
+                self.check_tag("rsi", PAIR_TAG)?;
+                writeln!(self.dest, "    mov rax, rsi")?;
+                writeln!(self.dest, "    mov rsi, QWORD PTR [rsi-{}]", PAIR_TAG)?;
+            }
+            if let Some(equal) = equal {
+                writeln!(self.dest, "    push rdx")?;
+                writeln!(self.dest, "    push rax")?;
+                writeln!(self.dest, "    mov rdi, QWORD PTR [rsp+24]")?;
+                writeln!(self.dest, "    call {}", equal)?;
+                writeln!(self.dest, "    mov ecx, eax")?;
+                writeln!(self.dest, "    pop rax")?;
+                writeln!(self.dest, "    pop rdx")?;
+                writeln!(self.dest, "    test ecx, ecx")?;
+                writeln!(self.dest, "    jnz {}_done", label)?;
+            } else {
+                writeln!(self.dest, "    cmp rsi, QWORD PTR [rsp+8]")?;
+                writeln!(self.dest, "    je {}_done", label)?;
+            }
+            writeln!(self.dest, "    mov rdx, QWORD PTR [rdx+{}]", 8 - PAIR_TAG)?;
+            writeln!(self.dest, "    jmp {}_loop", label)?;
+            writeln!(self.dest, "{}_done:", label)?;
+            writeln!(self.dest, "    ret 16")?;
+        }
+
+        // (map proc list ...) and (for-each proc list ...) stop at the end of the shortest list.
+        // the stack holds the head and the last pair of the result and the number of the arguments.
+        for (label, collect) in [("rt_map", true), ("rt_for_each", false)] {
+            self.gen_routine(label)?;
+            writeln!(self.dest, "    push {}", NIL)?;
+            writeln!(self.dest, "    push {}", NIL)?;
+            writeln!(self.dest, "    shl rcx, {}", FIXNUM_SHIFT)?;
+            writeln!(self.dest, "    push rcx")?;
+            writeln!(self.dest, "{}_loop:", label)?;
+            writeln!(self.dest, "    mov r8, QWORD PTR [rsp]")?;
+            writeln!(self.dest, "    shr r8, {}", FIXNUM_SHIFT)?;
+            writeln!(self.dest, "    dec r8")?;
+            writeln!(self.dest, "    lea r9, [rsp+40]")?;
+            self.gen_next_args(label)?;
+            writeln!(self.dest, "    mov rax, QWORD PTR [r9-8]")?;
+            writeln!(self.dest, "    mov rcx, r8")?;
+            self.gen_check_call(None)?;
+            writeln!(self.dest, "    call QWORD PTR [rax+{}]", 8 - PROC_TAG)?;
+            if collect {
+                writeln!(self.dest, "    push rax")?;
+                writeln!(self.dest, "    mov rdi, 16")?;
+                writeln!(self.dest, "    call rt_alloc")?;
+                writeln!(self.dest, "    pop rdi")?;
+                writeln!(self.dest, "    mov QWORD PTR [rax], rdi")?;
+                writeln!(self.dest, "    mov QWORD PTR [rax+8], {}", NIL)?;
+                writeln!(self.dest, "    or rax, {}", PAIR_TAG)?;
+                self.gen_link(label, 8)?;
+            }
+            writeln!(self.dest, "    jmp {}_loop", label)?;
+            writeln!(self.dest, "{}_done:", label)?;
+            writeln!(self.dest, "    pop rcx")?;
+            writeln!(self.dest, "    shr rcx, {}", FIXNUM_SHIFT)?;
+            writeln!(self.dest, "    pop rax")?;
+            writeln!(self.dest, "    pop rax")?;
+            if !collect {
+                writeln!(self.dest, "    mov eax, {}", VOID)?;
+            }
+            writeln!(self.dest, "    pop rdx")?;
+            writeln!(self.dest, "    lea rsp, [rsp+8*rcx]")?;
+            writeln!(self.dest, "    jmp rdx")?;
+        }
+
+        // (filter pred list) keeps the elements for which pred does not return #f
+        self.gen_routine("rt_filter")?;
+        writeln!(self.dest, "    push {}", NIL)?;
+        writeln!(self.dest, "    push {}", NIL)?;
+        writeln!(self.dest, "rt_filter_loop:")?;
+        writeln!(self.dest, "    mov rdx, QWORD PTR [rsp+32]")?;
+        writeln!(self.dest, "    cmp rdx, {}", NIL)?;
+        writeln!(self.dest, "    je rt_filter_done")?;
+        self.check_tag("rdx", PAIR_TAG)?;
+        writeln!(self.dest, "    push QWORD PTR [rdx-{}]", PAIR_TAG)?;
+        writeln!(self.dest, "    mov rax, QWORD PTR [rsp+32]")?;
+        self.gen_check_call(Some(1))?;
+        writeln!(self.dest, "    call QWORD PTR [rax+{}]", 8 - PROC_TAG)?;
+        writeln!(self.dest, "    cmp rax, {}", FALSE)?;
+        writeln!(self.dest, "    je rt_filter_next")?;
+        writeln!(self.dest, "    mov rdi, 16")?;
+        writeln!(self.dest, "    call rt_alloc")?;
+        writeln!(self.dest, "    mov rdx, QWORD PTR [rsp+32]")?;
+        writeln!(self.dest, "    mov rcx, QWORD PTR [rdx-{}]", PAIR_TAG)?;
+        writeln!(self.dest, "    mov QWORD PTR [rax], rcx")?;
+        writeln!(self.dest, "    mov QWORD PTR [rax+8], {}", NIL)?;
+        writeln!(self.dest, "    or rax, {}", PAIR_TAG)?;
+        self.gen_link("rt_filter", 0)?;
+        writeln!(self.dest, "rt_filter_next:")?;
+        writeln!(self.dest, "    mov rdx, QWORD PTR [rsp+32]")?;
+        writeln!(self.dest, "    mov rdx, QWORD PTR [rdx+{}]", 8 - PAIR_TAG)?;
+        writeln!(self.dest, "    mov QWORD PTR [rsp+32], rdx")?;
+        writeln!(self.dest, "    jmp rt_filter_loop")?;
+        writeln!(self.dest, "rt_filter_done:")?;
+        writeln!(self.dest, "    mov rax, QWORD PTR [rsp+8]")?;
+        writeln!(self.dest, "    add rsp, 16")?;
+        writeln!(self.dest, "    ret 16")?;
+
+        // (fold-left proc init list ...) calls (proc acc x ...) from the left.
+        // the accumulator lives in the slot of init.
+        self.gen_routine("rt_fold_left")?;
+        writeln!(self.dest, "    shl rcx, {}", FIXNUM_SHIFT)?;
+        writeln!(self.dest, "    push rcx")?;
+        writeln!(self.dest, "rt_fold_left_loop:")?;
+        writeln!(self.dest, "    mov r8, QWORD PTR [rsp]")?;
+        writeln!(self.dest, "    shr r8, {}", FIXNUM_SHIFT)?;
+        writeln!(self.dest, "    sub r8, 2")?;
+        writeln!(self.dest, "    lea r9, [rsp+32]")?;
+        self.gen_next_args("rt_fold_left")?;
+        writeln!(self.dest, "    push QWORD PTR [r9-8]")?;
+        writeln!(self.dest, "    mov rax, QWORD PTR [r9-16]")?;
+        writeln!(self.dest, "    lea rcx, [r8+1]")?;
+        self.gen_check_call(None)?;
+        writeln!(self.dest, "    call QWORD PTR [rax+{}]", 8 - PROC_TAG)?;
+        writeln!(self.dest, "    mov QWORD PTR [rsp+24], rax")?;
+        writeln!(self.dest, "    jmp rt_fold_left_loop")?;
+        writeln!(self.dest, "rt_fold_left_done:")?;
+        writeln!(self.dest, "    pop rcx")?;
+        writeln!(self.dest, "    shr rcx, {}", FIXNUM_SHIFT)?;
+        writeln!(self.dest, "    mov rax, QWORD PTR [rsp+16]")?;
+        writeln!(self.dest, "    pop rdx")?;
+        writeln!(self.dest, "    lea rsp, [rsp+8*rcx]")?;
+        writeln!(self.dest, "    jmp rdx")?;
+
+        // (fold-right proc init list ...) calls (proc x ... acc) from the right. the elements
+        // are pushed in groups, one element of each list, and the groups are then popped one by
+        // one. rbp points to the arguments while the depth of the stack changes.
+        self.gen_routine("rt_fold_right")?;
+        writeln!(self.dest, "    push rbp")?;
+        writeln!(self.dest, "    mov rbp, rsp")?;
+        writeln!(self.dest, "    shl rcx, {}", FIXNUM_SHIFT)?;
+        writeln!(self.dest, "    push rcx")?;
+        writeln!(self.dest, "rt_fold_right_loop:")?;
+        writeln!(self.dest, "    mov r8, QWORD PTR [rbp-8]")?;
+        writeln!(self.dest, "    shr r8, {}", FIXNUM_SHIFT)?;
+        writeln!(self.dest, "    sub r8, 2")?;
+        writeln!(self.dest, "    lea r9, [rbp+32]")?;
+        self.gen_next_args("rt_fold_right")?;
+        writeln!(self.dest, "    jmp rt_fold_right_loop")?;
+        writeln!(self.dest, "rt_fold_right_done:")?;
+        writeln!(self.dest, "    mov rax, QWORD PTR [rbp+24]")?;
+        writeln!(self.dest, "rt_fold_right_group:")?;
+        writeln!(self.dest, "    lea rdx, [rbp-8]")?;
+        writeln!(self.dest, "    cmp rsp, rdx")?;
+        writeln!(self.dest, "    je rt_fold_right_end")?;
+        writeln!(self.dest, "    mov r8, QWORD PTR [rbp-8]")?;
+        writeln!(self.dest, "    shr r8, {}", FIXNUM_SHIFT)?;
+        writeln!(self.dest, "    sub r8, 2")?;
+        writeln!(self.dest, "    push rax")?;
+        // each push copies the next element of the group, from the last one to the first
+        writeln!(self.dest, "    mov r10, r8")?;
+        writeln!(self.dest, "rt_fold_right_copy:")?;
+        writeln!(self.dest, "    push QWORD PTR [rsp+8*r8]")?;
+        writeln!(self.dest, "    dec r10")?;
+        writeln!(self.dest, "    jnz rt_fold_right_copy")?;
+        writeln!(self.dest, "    mov rax, QWORD PTR [rbp+16]")?;
+        writeln!(self.dest, "    lea rcx, [r8+1]")?;
+        self.gen_check_call(None)?;
+        writeln!(self.dest, "    call QWORD PTR [rax+{}]", 8 - PROC_TAG)?;
+        writeln!(self.dest, "    mov r8, QWORD PTR [rbp-8]")?;
+        writeln!(self.dest, "    shr r8, {}", FIXNUM_SHIFT)?;
+        writeln!(self.dest, "    lea rsp, [rsp+8*r8-16]")?;
+        writeln!(self.dest, "    jmp rt_fold_right_group")?;
+        writeln!(self.dest, "rt_fold_right_end:")?;
+        writeln!(self.dest, "    pop rcx")?;
+        writeln!(self.dest, "    shr rcx, {}", FIXNUM_SHIFT)?;
+        writeln!(self.dest, "    pop rbp")?;
+        writeln!(self.dest, "    pop rdx")?;
+        writeln!(self.dest, "    lea rsp, [rsp+8*rcx]")?;
+        writeln!(self.dest, "    jmp rdx")?;
+        Ok(())
+    }
+
+    // raises a range error with the index in the second argument unless rax is a pair
+    fn check_index_pair(&mut self) -> io::Result<()> {
+        writeln!(self.dest, "    mov r11, rax")?;
+        writeln!(self.dest, "    and r11, {}", TAG_MASK)?;
+        writeln!(self.dest, "    cmp r11, {}", PAIR_TAG)?;
+        self.gen_error("je", "rt_range_error", Some("QWORD PTR [rsp+16]"))?;
+        Ok(())
     }
 
-    fn gen_proc(&mut self, proc: Lambda) {
+    // pushes the next elements of r8 lists, whose slots begin at r9, so that the element of
+    // the first list is on top, and advances the lists. jumps to `{label}_done` without
+    // pushing anything if a list has ended.
+    fn gen_next_args(&mut self, label: &str) -> io::Result<()> {
+        writeln!(self.dest, "    mov r10, r8")?;
+        writeln!(self.dest, "{}_check:", label)?;
+        writeln!(self.dest, "    mov rdx, QWORD PTR [r9+8*r10-8]")?;
+        writeln!(self.dest, "    mov r11, rdx")?;
+        writeln!(self.dest, "    and r11, {}", TAG_MASK)?;
+        writeln!(self.dest, "    cmp r11, {}", PAIR_TAG)?;
+        writeln!(self.dest, "    jne {}_done", label)?;
+        writeln!(self.dest, "    dec r10")?;
+        writeln!(self.dest, "    jnz {}_check", label)?;
+        writeln!(self.dest, "    mov r10, r8")?;
+        writeln!(self.dest, "{}_next:", label)?;
+        writeln!(self.dest, "    mov rdx, QWORD PTR [r9+8*r10-8]")?;
+        writeln!(self.dest, "    push QWORD PTR [rdx-{}]", PAIR_TAG)?;
+        writeln!(self.dest, "    mov rdx, QWORD PTR [rdx+{}]", 8 - PAIR_TAG)?;
+        writeln!(self.dest, "    mov QWORD PTR [r9+8*r10-8], rdx")?;
+        writeln!(self.dest, "    dec r10")?;
+        writeln!(self.dest, "    jnz {}_next", label)?;
+        Ok(())
+    }
+
+    // adds the new pair in rax to the end of the list being built, whose last pair is at
+    // [rsp+offset] and head right above it. both are () while the list is empty.
+    fn gen_link(&mut self, label: &str, offset: usize) -> io::Result<()> {
+        writeln!(self.dest, "    mov rcx, QWORD PTR [rsp+{}]", offset)?;
+        writeln!(self.dest, "    cmp rcx, {}", NIL)?;
+        writeln!(self.dest, "    jne {}_link", label)?;
+        writeln!(self.dest, "    mov QWORD PTR [rsp+{}], rax", offset + 8)?;
+        writeln!(self.dest, "    jmp {}_linked", label)?;
+        writeln!(self.dest, "{}_link:", label)?;
+        writeln!(self.dest, "    mov QWORD PTR [rcx+{}], rax", 8 - PAIR_TAG)?;
+        writeln!(self.dest, "{}_linked:", label)?;
+        writeln!(self.dest, "    mov QWORD PTR [rsp+{}], rax", offset)?;
+        Ok(())
+    }
+
+    // the type predicates and the equivalences. eq? and eqv? differ only for bignums, which are
+    // eqv? when they have the same value.
+    fn gen_predicates(&mut self) -> io::Result<()> {
+        self.gen_routine("rt_eq")?;
+        writeln!(self.dest, "    mov rax, QWORD PTR [rsp+8]")?;
+        writeln!(self.dest, "    cmp rax, QWORD PTR [rsp+16]")?;
+        self.gen_bool("sete")?;
+        writeln!(self.dest, "    ret 16")?;
+
+        self.gen_routine("rt_eqv")?;
+        writeln!(self.dest, "    mov rdi, QWORD PTR [rsp+8]")?;
+        writeln!(self.dest, "    mov rsi, QWORD PTR [rsp+16]")?;
+        writeln!(self.dest, "    call rt_eqv_values")?;
+        writeln!(self.dest, "    test eax, eax")?;
+        self.gen_bool("setnz")?;
+        writeln!(self.dest, "    ret 16")?;
+
+        // returns 1 in eax if the values in rdi and rsi are eqv? and 0 otherwise
+        writeln!(self.dest, "rt_eqv_values:")?;
+        writeln!(self.dest, "    mov eax, 1")?;
+        writeln!(self.dest, "    cmp rdi, rsi")?;
+        writeln!(self.dest, "    je rt_eqv_values_done")?;
+        writeln!(self.dest, "    xor eax, eax")?;
+        writeln!(self.dest, "    mov ecx, edi")?;
+        writeln!(self.dest, "    and ecx, {}", TAG_MASK)?;
+        writeln!(self.dest, "    cmp ecx, {}", OBJ_TAG)?;
+        writeln!(self.dest, "    jne rt_eqv_values_done")?;
+        writeln!(self.dest, "    mov rcx, QWORD PTR [rdi-{}]", OBJ_TAG)?;
+        writeln!(self.dest, "    and ecx, 255")?;
+        writeln!(self.dest, "    cmp ecx, {}", header(BIGNUM_TYPE, 0))?;
+        writeln!(self.dest, "    je rt_equal_values")?;
+        writeln!(self.dest, "rt_eqv_values_done:")?;
+        writeln!(self.dest, "    ret")?;
+
+        // the booleans differ only in the bit 8
+        for (label, mask, value) in [("rt_is_null", -1, NIL), ("rt_not", -1, FALSE), ("rt_is_boolean", !8, FALSE),
+                                     ("rt_is_pair", TAG_MASK, PAIR_TAG), ("rt_is_procedure", TAG_MASK, PROC_TAG),
+                                     ("rt_is_char", 255, CHAR_TAG)] {
+            self.gen_routine(label)?;
+            writeln!(self.dest, "    mov rax, QWORD PTR [rsp+8]")?;
+            writeln!(self.dest, "    and rax, {}", mask)?;
+            writeln!(self.dest, "    cmp rax, {}", value)?;
+            self.gen_bool("sete")?;
+            writeln!(self.dest, "    ret 8")?;
+        }
+        self.gen_type_predicate("rt_is_vector", VECTOR_TYPE)?;
+
+        // every number is an integer, a fixnum or a bignum
+        for label in ["rt_is_number", "rt_is_integer"] {
+            self.gen_routine(label)?;
+            writeln!(self.dest, "    mov rdi, QWORD PTR [rsp+8]")?;
+            writeln!(self.dest, "    mov eax, {}", TRUE)?;
+            writeln!(self.dest, "    test rdi, {}", TAG_MASK)?;
+            writeln!(self.dest, "    jz {}_done", label)?;
+            writeln!(self.dest, "    mov eax, {}", FALSE)?;
+            writeln!(self.dest, "    mov r11, rdi")?;
+            writeln!(self.dest, "    and r11, {}", TAG_MASK)?;
+            writeln!(self.dest, "    cmp r11, {}", OBJ_TAG)?;
+            writeln!(self.dest, "    jne {}_done", label)?;
+            writeln!(self.dest, "    mov r11, QWORD PTR [rdi-{}]", OBJ_TAG)?;
+            writeln!(self.dest, "    and r11, 255")?;
+            writeln!(self.dest, "    cmp r11, {}", header(BIGNUM_TYPE, 0))?;
+            writeln!(self.dest, "    jne {}_done", label)?;
+            writeln!(self.dest, "    mov eax, {}", TRUE)?;
+            writeln!(self.dest, "{}_done:", label)?;
+            writeln!(self.dest, "    ret 8")?;
+        }
+
+        // a list is a chain of pairs ending with '(). rsi goes through the pairs twice as
+        // fast as rdi, so it meets rdi again if the chain is circular.
+        self.gen_routine("rt_is_list")?;
+        writeln!(self.dest, "    mov rsi, QWORD PTR [rsp+8]")?;
+        writeln!(self.dest, "    mov rdi, rsi")?;
+        writeln!(self.dest, "    mov eax, {}", TRUE)?;
+        writeln!(self.dest, "rt_is_list_loop:")?;
+        for _ in 0..2 {
+            writeln!(self.dest, "    cmp rsi, {}", NIL)?;
+            writeln!(self.dest, "    je rt_is_list_done")?;
+            writeln!(self.dest, "    mov r11, rsi")?;
+            writeln!(self.dest, "    and r11, {}", TAG_MASK)?;
+            writeln!(self.dest, "    cmp r11, {}", PAIR_TAG)?;
+            writeln!(self.dest, "    jne rt_is_list_false")?;
+            writeln!(self.dest, "    mov rsi, QWORD PTR [rsi+{}]", 8 - PAIR_TAG)?;
+        }
+        writeln!(self.dest, "    mov rdi, QWORD PTR [rdi+{}]", 8 - PAIR_TAG)?;
+        writeln!(self.dest, "    cmp rsi, rdi")?;
+        writeln!(self.dest, "    jne rt_is_list_loop")?;
+        writeln!(self.dest, "rt_is_list_false:")?;
+        writeln!(self.dest, "    mov eax, {}", FALSE)?;
+        writeln!(self.dest, "rt_is_list_done:")?;
+        writeln!(self.dest, "    ret 8")?;
+
+        self.gen_routine("rt_is_equal")?;
+        writeln!(self.dest, "    mov rdi, QWORD PTR [rsp+8]")?;
+        writeln!(self.dest, "    mov rsi, QWORD PTR [rsp+16]")?;
+        writeln!(self.dest, "    call rt_equal_values")?;
+        writeln!(self.dest, "    test eax, eax")?;
+        self.gen_bool("setnz")?;
+        writeln!(self.dest, "    ret 16")?;
+
+        // returns 1 in eax if the values in rdi and rsi have the same structure. pairs and vectors
+        // are compared element by element, strings and bytevectors byte by byte. nothing is
+        // allocated, so the pointers may stay in registers and on the stack.
+        writeln!(self.dest, "rt_equal_values:")?;
+        writeln!(self.dest, "    cmp rdi, rsi")?;
+        writeln!(self.dest, "    je rt_equal_values_true")?;
+        writeln!(self.dest, "    mov eax, edi")?;
+        writeln!(self.dest, "    and eax, {}", TAG_MASK)?;
+        writeln!(self.dest, "    mov edx, esi")?;
+        writeln!(self.dest, "    and edx, {}", TAG_MASK)?;
+        writeln!(self.dest, "    cmp eax, edx")?;
+        writeln!(self.dest, "    jne rt_equal_values_false")?;
+        writeln!(self.dest, "    cmp eax, {}", PAIR_TAG)?;
+        writeln!(self.dest, "    je rt_equal_values_pair")?;
+        writeln!(self.dest, "    cmp eax, {}", OBJ_TAG)?;
+        writeln!(self.dest, "    jne rt_equal_values_false")?;
+        // objects of different types or lengths have different headers
+        writeln!(self.dest, "    mov rcx, QWORD PTR [rdi-{}]", OBJ_TAG)?;
+        writeln!(self.dest, "    cmp rcx, QWORD PTR [rsi-{}]", OBJ_TAG)?;
+        writeln!(self.dest, "    jne rt_equal_values_false")?;
+        writeln!(self.dest, "    movzx eax, cl")?;
+        writeln!(self.dest, "    shr rcx, 8")?;
+        writeln!(self.dest, "    add rdi, {}", 8 - OBJ_TAG)?;
+        writeln!(self.dest, "    add rsi, {}", 8 - OBJ_TAG)?;
+        writeln!(self.dest, "    cmp eax, {}", header(VECTOR_TYPE, 0))?;
+        writeln!(self.dest, "    je rt_equal_values_vector")?;
+        writeln!(self.dest, "    cmp eax, {}", header(STRING_TYPE, 0))?;
+        writeln!(self.dest, "    je rt_equal_values_bytes")?;
+        writeln!(self.dest, "    cmp eax, {}", header(BYTEVECTOR_TYPE, 0))?;
+        writeln!(self.dest, "    je rt_equal_values_bytes")?;
+        writeln!(self.dest, "    cmp eax, {}", header(BIGNUM_TYPE, 0))?;
+        writeln!(self.dest, "    jne rt_equal_values_false")?;
+        writeln!(self.dest, "rt_equal_values_bytes:")?;
+        writeln!(self.dest, "    repe cmpsb")?;
+        writeln!(self.dest, "    jne rt_equal_values_false")?;
+        writeln!(self.dest, "rt_equal_values_true:")?;
+        writeln!(self.dest, "    mov eax, 1")?;
+        writeln!(self.dest, "    ret")?;
+        writeln!(self.dest, "rt_equal_values_false:")?;
+        writeln!(self.dest, "    xor eax, eax")?;
+        writeln!(self.dest, "    ret")?;
+
+        // the cars are compared recursively and the cdrs in a loop
+        writeln!(self.dest, "rt_equal_values_pair:")?;
+        writeln!(self.dest, "    push rdi")?;
+        writeln!(self.dest, "    push rsi")?;
+        writeln!(self.dest, "    mov rdi, QWORD PTR [rdi-{}]", PAIR_TAG)?;
+        writeln!(self.dest, "    mov rsi, QWORD PTR [rsi-{}]", PAIR_TAG)?;
+        writeln!(self.dest, "    call rt_equal_values")?;
+        writeln!(self.dest, "    pop rsi")?;
+        writeln!(self.dest, "    pop rdi")?;
+        writeln!(self.dest, "    test eax, eax")?;
+        writeln!(self.dest, "    jz rt_equal_values_false")?;
+        writeln!(self.dest, "    mov rdi, QWORD PTR [rdi+{}]", 8 - PAIR_TAG)?;
+        writeln!(self.dest, "    mov rsi, QWORD PTR [rsi+{}]", 8 - PAIR_TAG)?;
+        writeln!(self.dest, "    jmp rt_equal_values")?;
+
+        // rdi and rsi point to the elements and rcx counts those left
+        writeln!(self.dest, "rt_equal_values_vector:")?;
+        writeln!(self.dest, "    test rcx, rcx")?;
+        writeln!(self.dest, "    jz rt_equal_values_true")?;
+        writeln!(self.dest, "    push rdi")?;
+        writeln!(self.dest, "    push rsi")?;
+        writeln!(self.dest, "    push rcx")?;
+        writeln!(self.dest, "    mov rdi, QWORD PTR [rdi]")?;
+        writeln!(self.dest, "    mov rsi, QWORD PTR [rsi]")?;
+        writeln!(self.dest, "    call rt_equal_values")?;
+        writeln!(self.dest, "    pop rcx")?;
+        writeln!(self.dest, "    pop rsi")?;
+        writeln!(self.dest, "    pop rdi")?;
+        writeln!(self.dest, "    test eax, eax")?;
+        writeln!(self.dest, "    jz rt_equal_values_false")?;
+        writeln!(self.dest, "    add rdi, 8")?;
+        writeln!(self.dest, "    add rsi, 8")?;
+        writeln!(self.dest, "    dec rcx")?;
+        writeln!(self.dest, "    jmp rt_equal_values_vector")?;
+        Ok(())
+    }
+
+    // the integers are fixnums, and bignums when they do not fit. / and quotient both truncate
+    // the quotient. the variadic routines fold their arguments into rax and pop them according
+    // to rcx. the operations go through the routines of gen_bignums, which take fixnums fast.
+    fn gen_arith(&mut self) -> io::Result<()> {
+        self.gen_fold("rt_add", Some(0), false, |gen| {
+            gen.gen_fold_call("rt_num_add")?;
+            Ok(())
+        })?;
+        self.gen_fold("rt_sub", Some(0), true, |gen| {
+            gen.gen_fold_call("rt_num_sub")?;
+            Ok(())
+        })?;
+        self.gen_fold("rt_mul", Some(1 << FIXNUM_SHIFT), false, |gen| {
+            gen.gen_fold_call("rt_num_mul")?;
+            Ok(())
+        })?;
+        self.gen_fold("rt_div", Some(1 << FIXNUM_SHIFT), true, |gen| {
+            writeln!(gen.dest, "    test rdi, rdi")?;
+            gen.gen_error("jnz", "rt_zero_error", None)?;
+            gen.gen_fold_call("rt_num_divmod")?;
+            writeln!(gen.dest, "    test rdx, rdx")?;
+            gen.gen_error("jz", "rt_inexact_error", None)?;
+            Ok(())
+        })?;
+        self.gen_fold("rt_min", None, false, |gen| {
+            writeln!(gen.dest, "    mov rsi, rax")?;
+            writeln!(gen.dest, "    call rt_num_cmp")?;
+            writeln!(gen.dest, "    cmovl rax, rdi")?;
+            Ok(())
+        })?;
+        self.gen_fold("rt_max", None, false, |gen| {
+            writeln!(gen.dest, "    mov rsi, rax")?;
+            writeln!(gen.dest, "    call rt_num_cmp")?;
+            writeln!(gen.dest, "    cmovg rax, rdi")?;
+            Ok(())
+        })?;
+        self.gen_fold("rt_gcd", Some(0), false, |gen| {
+            gen.gen_fold_call("rt_num_gcd")?;
+            Ok(())
+        })?;
+        self.gen_fold("rt_lcm", Some(1 << FIXNUM_SHIFT), false, |gen| {
+            gen.gen_fold_call("rt_num_lcm")?;
+            Ok(())
+        })?;
+
+        // the remainder has the sign of the dividend. the old name rem is kept.
+        for label in ["rt_remainder", "rt_rem"] {
+            self.gen_arith2(label, |gen| {
+                writeln!(gen.dest, "    test rdi, rdi")?;
+                gen.gen_error("jnz", "rt_zero_error", None)?;
+                writeln!(gen.dest, "    mov rsi, rdi")?;
+                writeln!(gen.dest, "    mov rdi, rax")?;
+                writeln!(gen.dest, "    call rt_num_divmod")?;
+                writeln!(gen.dest, "    mov rax, rdx")?;
+                Ok(())
+            })?;
+        }
+        self.gen_arith2("rt_quotient", |gen| {
+            writeln!(gen.dest, "    test rdi, rdi")?;
+            gen.gen_error("jnz", "rt_zero_error", None)?;
+            writeln!(gen.dest, "    mov rsi, rdi")?;
+            writeln!(gen.dest, "    mov rdi, rax")?;
+            writeln!(gen.dest, "    call rt_num_divmod")?;
+            Ok(())
+        })?;
+        // the modulo has the sign of the divisor, so the divisor is added to a nonzero
+        // remainder whose sign differs from it
+        self.gen_arith2("rt_modulo", |gen| {
+            writeln!(gen.dest, "    test rdi, rdi")?;
+            gen.gen_error("jnz", "rt_zero_error", None)?;
+            writeln!(gen.dest, "    mov rsi, rdi")?;
+            writeln!(gen.dest, "    mov rdi, rax")?;
+            writeln!(gen.dest, "    call rt_num_divmod")?;
+            writeln!(gen.dest, "    mov rax, rdx")?;
+            writeln!(gen.dest, "    test rax, rax")?;
+            writeln!(gen.dest, "    jz rt_modulo_done")?;
+            writeln!(gen.dest, "    mov rdi, rax")?;
+            writeln!(gen.dest, "    xor esi, esi")?;
+            writeln!(gen.dest, "    call rt_num_cmp")?;
+            writeln!(gen.dest, "    setl r10b")?;
+            writeln!(gen.dest, "    mov rdi, QWORD PTR [rsp+16]")?;
+            writeln!(gen.dest, "    call rt_num_cmp")?;
+            writeln!(gen.dest, "    setl r11b")?;
+            writeln!(gen.dest, "    cmp r10b, r11b")?;
+            writeln!(gen.dest, "    je rt_modulo_done")?;
+            writeln!(gen.dest, "    mov rdi, rax")?;
+            writeln!(gen.dest, "    mov rsi, QWORD PTR [rsp+16]")?;
+            writeln!(gen.dest, "    call rt_num_add")?;
+            writeln!(gen.dest, "rt_modulo_done:")?;
+            Ok(())
+        })?;
+        // exponentiation by squaring. the result is on the stack above the base and the
+        // exponent, and the base is not squared after the last bit of the exponent.
+        self.gen_arith2("rt_expt", |gen| {
+            gen.check_tag("rdi", FIXNUM_TAG)?;
+            writeln!(gen.dest, "    test rdi, rdi")?;
+            gen.gen_error("jns", "rt_range_error", Some("rdi"))?;
+            writeln!(gen.dest, "    push {}", 1 << FIXNUM_SHIFT)?;
+            writeln!(gen.dest, "rt_expt_loop:")?;
+            writeln!(gen.dest, "    test QWORD PTR [rsp+24], {}", 1 << FIXNUM_SHIFT)?;
+            writeln!(gen.dest, "    jz rt_expt_square")?;
+            writeln!(gen.dest, "    mov rdi, QWORD PTR [rsp]")?;
+            writeln!(gen.dest, "    mov rsi, QWORD PTR [rsp+16]")?;
+            writeln!(gen.dest, "    call rt_num_mul")?;
+            writeln!(gen.dest, "    mov QWORD PTR [rsp], rax")?;
+            writeln!(gen.dest, "rt_expt_square:")?;
+            writeln!(gen.dest, "    mov rax, QWORD PTR [rsp+24]")?;
+            writeln!(gen.dest, "    shr rax, {}", FIXNUM_SHIFT + 1)?;
+            writeln!(gen.dest, "    shl rax, {}", FIXNUM_SHIFT)?;
+            writeln!(gen.dest, "    mov QWORD PTR [rsp+24], rax")?;
+            writeln!(gen.dest, "    jz rt_expt_done")?;
+            writeln!(gen.dest, "    mov rdi, QWORD PTR [rsp+16]")?;
+            writeln!(gen.dest, "    mov rsi, rdi")?;
+            writeln!(gen.dest, "    call rt_num_mul")?;
+            writeln!(gen.dest, "    mov QWORD PTR [rsp+16], rax")?;
+            writeln!(gen.dest, "    jmp rt_expt_loop")?;
+            writeln!(gen.dest, "rt_expt_done:")?;
+            writeln!(gen.dest, "    pop rax")?;
+            Ok(())
+        })?;
+
+        self.gen_routine("rt_abs")?;
+        writeln!(self.dest, "    mov rdi, QWORD PTR [rsp+8]")?;
+        self.check_number("rdi")?;
+        writeln!(self.dest, "    call rt_num_abs")?;
+        writeln!(self.dest, "    ret 8")?;
+
+        // the signs are found by comparing with 0
+        for (label, set) in [("rt_is_zero", "sete"), ("rt_is_positive", "setg"), ("rt_is_negative", "setl")] {
+            self.gen_routine(label)?;
+            writeln!(self.dest, "    mov rdi, QWORD PTR [rsp+8]")?;
+            self.check_number("rdi")?;
+            writeln!(self.dest, "    xor esi, esi")?;
+            writeln!(self.dest, "    call rt_num_cmp")?;
+            self.gen_bool(set)?;
+            writeln!(self.dest, "    ret 8")?;
+        }
+        // the lowest limb of a bignum is shifted like a fixnum
+        for (label, set) in [("rt_is_even", "sete"), ("rt_is_odd", "setne")] {
+            self.gen_routine(label)?;
+            writeln!(self.dest, "    mov rax, QWORD PTR [rsp+8]")?;
+            self.check_number("rax")?;
+            writeln!(self.dest, "    test rax, {}", TAG_MASK)?;
+            writeln!(self.dest, "    jz {}_fixnum", label)?;
+            writeln!(self.dest, "    mov rax, QWORD PTR [rax+{}]", 16 - OBJ_TAG)?;
+            writeln!(self.dest, "    shl rax, {}", FIXNUM_SHIFT)?;
+            writeln!(self.dest, "{}_fixnum:", label)?;
+            writeln!(self.dest, "    test rax, {}", 1 << FIXNUM_SHIFT)?;
+            self.gen_bool(set)?;
+            writeln!(self.dest, "    ret 8")?;
+        }
+        Ok(())
+    }
+
+    // a routine folding its numeric arguments with `op`, which combines rdi into rax. the fold
+    // starts from the identity, or from the first argument if there is none or if `inverse` is
+    // true and there are several arguments, so that (- x) is (- 0 x) and (- x y) is x - y.
+    // r8 counts the arguments left and r9 points to the next one.
+    fn gen_fold(&mut self, label: &str, identity: Option<i64>, inverse: bool, op: fn(&mut CodeGen) -> io::Result<()>) -> io::Result<()> {
+        self.gen_routine(label)?;
+        writeln!(self.dest, "    mov r8, rcx")?;
+        writeln!(self.dest, "    lea r9, [rsp+8]")?;
+        match identity {
+            Some(identity) => {
+                writeln!(self.dest, "    mov rax, {}", identity)?;
+                if inverse {
+                    writeln!(self.dest, "    cmp r8, 1")?;
+                    writeln!(self.dest, "    je {}_loop", label)?;
+                    self.gen_fold_first()?;
+                }
+            },
+            None => self.gen_fold_first()?,
+        }
+        writeln!(self.dest, "{}_loop:", label)?;
+        writeln!(self.dest, "    test r8, r8")?;
+        writeln!(self.dest, "    jz {}_done", label)?;
+        writeln!(self.dest, "    mov rdi, QWORD PTR [r9]")?;
+        self.check_number("rdi")?;
+        op(self)?;
+        writeln!(self.dest, "    add r9, 8")?;
+        writeln!(self.dest, "    dec r8")?;
+        writeln!(self.dest, "    jmp {}_loop", label)?;
+        writeln!(self.dest, "{}_done:", label)?;
+        writeln!(self.dest, "    pop rdx")?;
+        writeln!(self.dest, "    lea rsp, [rsp+8*rcx]")?;
+        writeln!(self.dest, "    jmp rdx")?;
+        Ok(())
+    }
+
+    // calls the routine with rax and rdi from a fold. the counts are kept on the stack as
+    // fixnums and the pointer to the arguments, which is aligned, as it is.
+    fn gen_fold_call(&mut self, routine: &str) -> io::Result<()> {
+        writeln!(self.dest, "    shl rcx, {}", FIXNUM_SHIFT)?;
+        writeln!(self.dest, "    push rcx")?;
+        writeln!(self.dest, "    shl r8, {}", FIXNUM_SHIFT)?;
+        writeln!(self.dest, "    push r8")?;
+        writeln!(self.dest, "    push r9")?;
+        writeln!(self.dest, "    mov rsi, rdi")?;
+        writeln!(self.dest, "    mov rdi, rax")?;
+        writeln!(self.dest, "    call {}", routine)?;
+        writeln!(self.dest, "    pop r9")?;
+        writeln!(self.dest, "    pop r8")?;
+        writeln!(self.dest, "    shr r8, {}", FIXNUM_SHIFT)?;
+        writeln!(self.dest, "    pop rcx")?;
+        writeln!(self.dest, "    shr rcx, {}", FIXNUM_SHIFT)?;
+        Ok(())
+    }
+
+    // takes the first argument of a fold as its initial value
+    fn gen_fold_first(&mut self) -> io::Result<()> {
+        writeln!(self.dest, "    mov rax, QWORD PTR [r9]")?;
+        self.check_number("rax")?;
+        writeln!(self.dest, "    add r9, 8")?;
+        writeln!(self.dest, "    dec r8")?;
+        Ok(())
+    }
+
+    // a routine applying `op` to its two numeric arguments in rax and rdi
+    fn gen_arith2(&mut self, label: &str, op: fn(&mut CodeGen) -> io::Result<()>) -> io::Result<()> {
+        self.gen_routine(label)?;
+        writeln!(self.dest, "    mov rax, QWORD PTR [rsp+8]")?;
+        writeln!(self.dest, "    mov rdi, QWORD PTR [rsp+16]")?;
+        self.check_number("rax")?;
+        self.check_number("rdi")?;
+        op(self)?;
+        writeln!(self.dest, "    ret 16")?;
+        Ok(())
+    }
+
+    // a bignum is a raw object holding a sign word, 1 if negative, and the limbs of its
+    // magnitude from the lowest, with an unused word after them. it always has a nonzero
+    // highest limb and never fits in a fixnum, so every integer has a single representation.
+    //
+    // the rt_num routines take two numbers in rdi and rsi and return a number in rax. they try
+    // fixnums first, and otherwise allocate the result before reading the limbs, keeping the
+    // numbers on the stack. the rt_mag routines work on the magnitudes, A in rsi with rcx limbs
+    // and B in r8 with r9 limbs, writing to rdi. they do not allocate and keep these registers.
+    fn gen_bignums(&mut self) -> io::Result<()> {
+        writeln!(self.dest, "rt_num_add:")?;
+        self.gen_both_fixnums("rt_num_add_big")?;
+        writeln!(self.dest, "    mov rax, rdi")?;
+        writeln!(self.dest, "    add rax, rsi")?;
+        writeln!(self.dest, "    jo rt_num_add_big")?;
+        writeln!(self.dest, "    ret")?;
+        writeln!(self.dest, "rt_num_add_big:")?;
+        writeln!(self.dest, "    xor edx, edx")?;
+        writeln!(self.dest, "    jmp rt_bignum_add")?;
+
+        writeln!(self.dest, "rt_num_sub:")?;
+        self.gen_both_fixnums("rt_num_sub_big")?;
+        writeln!(self.dest, "    mov rax, rdi")?;
+        writeln!(self.dest, "    sub rax, rsi")?;
+        writeln!(self.dest, "    jo rt_num_sub_big")?;
+        writeln!(self.dest, "    ret")?;
+        writeln!(self.dest, "rt_num_sub_big:")?;
+        writeln!(self.dest, "    mov edx, 1")?;
+
+        // adds the numbers, or subtracts them if rdx is 1 by flipping the sign of the second.
+        // the magnitudes are added if the signs are the same, and the smaller is subtracted
+        // from the larger otherwise.
+        writeln!(self.dest, "rt_bignum_add:")?;
+        writeln!(self.dest, "    push rbp")?;
+        writeln!(self.dest, "    mov rbp, rsp")?;
+        writeln!(self.dest, "    push rdi")?;
+        writeln!(self.dest, "    push rsi")?;
+        writeln!(self.dest, "    shl rdx, {}", FIXNUM_SHIFT)?;
+        writeln!(self.dest, "    push rdx")?;
+        writeln!(self.dest, "    mov rax, rdi")?;
+        writeln!(self.dest, "    call rt_bignum_size")?;
+        writeln!(self.dest, "    mov rcx, rax")?;
+        writeln!(self.dest, "    mov rax, rsi")?;
+        writeln!(self.dest, "    call rt_bignum_size")?;
+        writeln!(self.dest, "    cmp rax, rcx")?;
+        writeln!(self.dest, "    cmovb rax, rcx")?;
+        writeln!(self.dest, "    lea rdi, [8*rax+32]")?;
+        writeln!(self.dest, "    call rt_alloc")?;
+        writeln!(self.dest, "    push rax")?;
+        self.gen_bignum_views()?;
+        writeln!(self.dest, "    mov r10, QWORD PTR [rbp-24]")?;
+        writeln!(self.dest, "    shr r10, {}", FIXNUM_SHIFT)?;
+        writeln!(self.dest, "    xor r11, r10")?;
+        writeln!(self.dest, "    mov rdi, QWORD PTR [rbp-32]")?;
+        writeln!(self.dest, "    add rdi, 16")?;
+        writeln!(self.dest, "    cmp rdx, r11")?;
+        writeln!(self.dest, "    jne rt_bignum_add_diff")?;
+        writeln!(self.dest, "    push rdx")?;
+        writeln!(self.dest, "    cmp rcx, r9")?;
+        writeln!(self.dest, "    jae rt_bignum_add_same")?;
+        writeln!(self.dest, "    xchg rsi, r8")?;
+        writeln!(self.dest, "    xchg rcx, r9")?;
+        writeln!(self.dest, "rt_bignum_add_same:")?;
+        writeln!(self.dest, "    call rt_mag_add")?;
+        writeln!(self.dest, "    inc rcx")?;
+        writeln!(self.dest, "    jmp rt_bignum_add_done")?;
+        writeln!(self.dest, "rt_bignum_add_diff:")?;
+        writeln!(self.dest, "    call rt_mag_cmp")?;
+        writeln!(self.dest, "    test eax, eax")?;
+        writeln!(self.dest, "    jns rt_bignum_add_sub")?;
+        writeln!(self.dest, "    xchg rsi, r8")?;
+        writeln!(self.dest, "    xchg rcx, r9")?;
+        writeln!(self.dest, "    mov rdx, r11")?;
+        writeln!(self.dest, "rt_bignum_add_sub:")?;
+        writeln!(self.dest, "    push rdx")?;
+        writeln!(self.dest, "    call rt_mag_sub")?;
+        writeln!(self.dest, "rt_bignum_add_done:")?;
+        writeln!(self.dest, "    pop rdx")?;
+        writeln!(self.dest, "    mov rax, QWORD PTR [rbp-32]")?;
+        writeln!(self.dest, "    call rt_bignum_finish")?;
+        writeln!(self.dest, "    leave")?;
+        writeln!(self.dest, "    ret")?;
+
+        writeln!(self.dest, "rt_num_mul:")?;
+        self.gen_both_fixnums("rt_num_mul_big")?;
+        writeln!(self.dest, "    mov rax, rdi")?;
+        writeln!(self.dest, "    sar rax, {}", FIXNUM_SHIFT)?;
+        writeln!(self.dest, "    imul rax, rsi")?;
+        writeln!(self.dest, "    jo rt_num_mul_big")?;
+        writeln!(self.dest, "    ret")?;
+        writeln!(self.dest, "rt_num_mul_big:")?;
+        writeln!(self.dest, "    push rbp")?;
+        writeln!(self.dest, "    mov rbp, rsp")?;
+        writeln!(self.dest, "    push rdi")?;
+        writeln!(self.dest, "    push rsi")?;
+        writeln!(self.dest, "    mov rax, rdi")?;
+        writeln!(self.dest, "    call rt_bignum_size")?;
+        writeln!(self.dest, "    mov rcx, rax")?;
+        writeln!(self.dest, "    mov rax, rsi")?;
+        writeln!(self.dest, "    call rt_bignum_size")?;
+        writeln!(self.dest, "    add rax, rcx")?;
+        writeln!(self.dest, "    lea rdi, [8*rax+24]")?;
+        writeln!(self.dest, "    call rt_alloc")?;
+        writeln!(self.dest, "    push rax")?;
+        writeln!(self.dest, "    push rax")?;
+        self.gen_bignum_views()?;
+        writeln!(self.dest, "    xor rdx, r11")?;
+        writeln!(self.dest, "    mov QWORD PTR [rbp-32], rdx")?;
+        writeln!(self.dest, "    mov rdi, QWORD PTR [rbp-24]")?;
+        writeln!(self.dest, "    add rdi, 16")?;
+        writeln!(self.dest, "    call rt_mag_mul")?;
+        writeln!(self.dest, "    add rcx, r9")?;
+        writeln!(self.dest, "    mov rdx, QWORD PTR [rbp-32]")?;
+        writeln!(self.dest, "    mov rax, QWORD PTR [rbp-24]")?;
+        writeln!(self.dest, "    call rt_bignum_finish")?;
+        writeln!(self.dest, "    leave")?;
+        writeln!(self.dest, "    ret")?;
+
+        // the truncated quotient in rax and the remainder, which has the sign of the dividend,
+        // in rdx. the divisor is not 0. only the smallest fixnum divided by -1 leaves the fixnums.
+        // the quotient and the remainder are allocated together, and the remainder has a limb
+        // more than the divisor, for the long division.
+        writeln!(self.dest, "rt_num_divmod:")?;
+        self.gen_both_fixnums("rt_num_divmod_big")?;
+        writeln!(self.dest, "    mov rax, rdi")?;
+        writeln!(self.dest, "    cqo")?;
+        writeln!(self.dest, "    idiv rsi")?;
+        writeln!(self.dest, "    imul rax, rax, {}", 1 << FIXNUM_SHIFT)?;
+        writeln!(self.dest, "    jo rt_num_divmod_big")?;
+        writeln!(self.dest, "    ret")?;
+        writeln!(self.dest, "rt_num_divmod_big:")?;
+        writeln!(self.dest, "    push rbp")?;
+        writeln!(self.dest, "    mov rbp, rsp")?;
+        writeln!(self.dest, "    push rdi")?;
+        writeln!(self.dest, "    push rsi")?;
+        writeln!(self.dest, "    mov rax, rdi")?;
+        writeln!(self.dest, "    call rt_bignum_size")?;
+        writeln!(self.dest, "    lea rcx, [8*rax+24]")?;
+        writeln!(self.dest, "    mov rax, rsi")?;
+        writeln!(self.dest, "    call rt_bignum_size")?;
+        writeln!(self.dest, "    push rcx")?;
+        writeln!(self.dest, "    lea rdi, [rcx+8*rax+32]")?;
+        writeln!(self.dest, "    call rt_alloc")?;
+        writeln!(self.dest, "    push rax")?;
+        self.gen_bignum_views()?;
+        writeln!(self.dest, "    push rdx")?;
+        writeln!(self.dest, "    xor rdx, r11")?;
+        writeln!(self.dest, "    push rdx")?;
+        writeln!(self.dest, "    mov rdi, QWORD PTR [rbp-32]")?;
+        writeln!(self.dest, "    mov r10, rdi")?;
+        writeln!(self.dest, "    add r10, QWORD PTR [rbp-24]")?;
+        writeln!(self.dest, "    add rdi, 16")?;
+        writeln!(self.dest, "    add r10, 16")?;
+        writeln!(self.dest, "    cmp r9, 1")?;
+        writeln!(self.dest, "    jne rt_num_divmod_long")?;
+        writeln!(self.dest, "    mov r8, QWORD PTR [r8]")?;
+        writeln!(self.dest, "    push r10")?;
+        writeln!(self.dest, "    call rt_mag_div_word")?;
+        writeln!(self.dest, "    pop r10")?;
+        writeln!(self.dest, "    mov QWORD PTR [r10], rdx")?;
+        writeln!(self.dest, "    xor r9d, r9d")?;
+        writeln!(self.dest, "    jmp rt_num_divmod_done")?;
+        writeln!(self.dest, "rt_num_divmod_long:")?;
+        writeln!(self.dest, "    call rt_mag_divmod")?;
+        writeln!(self.dest, "rt_num_divmod_done:")?;
+        writeln!(self.dest, "    mov rax, QWORD PTR [rbp-32]")?;
+        writeln!(self.dest, "    mov rdx, QWORD PTR [rbp-48]")?;
+        writeln!(self.dest, "    call rt_bignum_finish")?;
+        writeln!(self.dest, "    mov QWORD PTR [rbp-48], rax")?;
+        writeln!(self.dest, "    mov rax, QWORD PTR [rbp-32]")?;
+        writeln!(self.dest, "    add rax, QWORD PTR [rbp-24]")?;
+        writeln!(self.dest, "    lea rcx, [r9+1]")?;
+        writeln!(self.dest, "    mov rdx, QWORD PTR [rbp-40]")?;
+        writeln!(self.dest, "    call rt_bignum_finish")?;
+        writeln!(self.dest, "    mov rdx, rax")?;
+        writeln!(self.dest, "    mov rax, QWORD PTR [rbp-48]")?;
+        writeln!(self.dest, "    leave")?;
+        writeln!(self.dest, "    ret")?;
+
+        // sets the flags as `cmp rdi, rsi` would for the numbers, keeping every register.
+        // a negative number is below the others, and the larger magnitude of two is further from 0.
+        writeln!(self.dest, "rt_num_cmp:")?;
+        writeln!(self.dest, "    push rax")?;
+        writeln!(self.dest, "    mov rax, rdi")?;
+        writeln!(self.dest, "    or rax, rsi")?;
+        writeln!(self.dest, "    test rax, {}", TAG_MASK)?;
+        writeln!(self.dest, "    jnz rt_num_cmp_big")?;
+        writeln!(self.dest, "    pop rax")?;
+        writeln!(self.dest, "    cmp rdi, rsi")?;
+        writeln!(self.dest, "    ret")?;
+        writeln!(self.dest, "rt_num_cmp_big:")?;
+        for reg in ["rcx", "rdx", "rsi", "rdi", "r8", "r9", "r10", "r11"] {
+            writeln!(self.dest, "    push {}", reg)?;
+        }
+        writeln!(self.dest, "    mov rax, rsi")?;
+        writeln!(self.dest, "    lea rdi, [rip+rt_bignum_y]")?;
+        writeln!(self.dest, "    call rt_bignum_view")?;
+        writeln!(self.dest, "    mov r8, rsi")?;
+        writeln!(self.dest, "    mov r9, rcx")?;
+        writeln!(self.dest, "    mov r11, rdx")?;
+        writeln!(self.dest, "    mov rax, QWORD PTR [rsp+32]")?;
+        writeln!(self.dest, "    lea rdi, [rip+rt_bignum_x]")?;
+        writeln!(self.dest, "    call rt_bignum_view")?;
+        writeln!(self.dest, "    mov eax, 1")?;
+        writeln!(self.dest, "    cmp rdx, r11")?;
+        writeln!(self.dest, "    jne rt_num_cmp_sign")?;
+        writeln!(self.dest, "    call rt_mag_cmp")?;
+        writeln!(self.dest, "rt_num_cmp_sign:")?;
+        writeln!(self.dest, "    test rdx, rdx")?;
+        writeln!(self.dest, "    jz rt_num_cmp_done")?;
+        writeln!(self.dest, "    neg eax")?;
+        writeln!(self.dest, "rt_num_cmp_done:")?;
+        writeln!(self.dest, "    cmp eax, 0")?;
+        for reg in ["r11", "r10", "r9", "r8", "rdi", "rsi", "rdx", "rcx", "rax"] {
+            writeln!(self.dest, "    pop {}", reg)?;
+        }
+        writeln!(self.dest, "    ret")?;
+
+        // the absolute value of the number in rdi
+        writeln!(self.dest, "rt_num_abs:")?;
+        writeln!(self.dest, "    push rsi")?;
+        writeln!(self.dest, "    xor esi, esi")?;
+        writeln!(self.dest, "    call rt_num_cmp")?;
+        writeln!(self.dest, "    pop rsi")?;
+        writeln!(self.dest, "    mov rax, rdi")?;
+        writeln!(self.dest, "    jge rt_num_abs_done")?;
+        writeln!(self.dest, "    mov rsi, rdi")?;
+        writeln!(self.dest, "    xor edi, edi")?;
+        writeln!(self.dest, "    jmp rt_num_sub")?;
+        writeln!(self.dest, "rt_num_abs_done:")?;
+        writeln!(self.dest, "    ret")?;
+
+        // the greatest common divisor by the euclidean algorithm, with the pair on the stack
+        writeln!(self.dest, "rt_num_gcd:")?;
+        writeln!(self.dest, "    push rdi")?;
+        writeln!(self.dest, "    push rsi")?;
+        writeln!(self.dest, "rt_num_gcd_loop:")?;
+        writeln!(self.dest, "    mov rsi, QWORD PTR [rsp]")?;
+        writeln!(self.dest, "    test rsi, rsi")?;
+        writeln!(self.dest, "    jz rt_num_gcd_done")?;
+        writeln!(self.dest, "    mov rdi, QWORD PTR [rsp+8]")?;
+        writeln!(self.dest, "    call rt_num_divmod")?;
+        writeln!(self.dest, "    mov rax, QWORD PTR [rsp]")?;
+        writeln!(self.dest, "    mov QWORD PTR [rsp+8], rax")?;
+        writeln!(self.dest, "    mov QWORD PTR [rsp], rdx")?;
+        writeln!(self.dest, "    jmp rt_num_gcd_loop")?;
+        writeln!(self.dest, "rt_num_gcd_done:")?;
+        writeln!(self.dest, "    mov rdi, QWORD PTR [rsp+8]")?;
+        writeln!(self.dest, "    add rsp, 16")?;
+        writeln!(self.dest, "    jmp rt_num_abs")?;
+
+        // the least common multiple |a / gcd(a, b) * b|, which is 0 if either is 0
+        writeln!(self.dest, "rt_num_lcm:")?;
+        writeln!(self.dest, "    xor eax, eax")?;
+        writeln!(self.dest, "    test rdi, rdi")?;
+        writeln!(self.dest, "    jz rt_num_lcm_done")?;
+        writeln!(self.dest, "    test rsi, rsi")?;
+        writeln!(self.dest, "    jz rt_num_lcm_done")?;
+        writeln!(self.dest, "    push rdi")?;
+        writeln!(self.dest, "    push rsi")?;
+        writeln!(self.dest, "    call rt_num_gcd")?;
+        writeln!(self.dest, "    mov rsi, rax")?;
+        writeln!(self.dest, "    mov rdi, QWORD PTR [rsp+8]")?;
+        writeln!(self.dest, "    call rt_num_divmod")?;
+        writeln!(self.dest, "    mov rdi, rax")?;
+        writeln!(self.dest, "    mov rsi, QWORD PTR [rsp]")?;
+        writeln!(self.dest, "    call rt_num_mul")?;
+        writeln!(self.dest, "    add rsp, 16")?;
+        writeln!(self.dest, "    mov rdi, rax")?;
+        writeln!(self.dest, "    jmp rt_num_abs")?;
+        writeln!(self.dest, "rt_num_lcm_done:")?;
+        writeln!(self.dest, "    ret")?;
+
+        // the number of limbs of the number in rax, 1 for a fixnum
+        writeln!(self.dest, "rt_bignum_size:")?;
+        writeln!(self.dest, "    test rax, {}", TAG_MASK)?;
+        writeln!(self.dest, "    jnz rt_bignum_size_big")?;
+        writeln!(self.dest, "    mov eax, 1")?;
+        writeln!(self.dest, "    ret")?;
+        writeln!(self.dest, "rt_bignum_size_big:")?;
+        writeln!(self.dest, "    mov rax, QWORD PTR [rax-{}]", OBJ_TAG)?;
+        writeln!(self.dest, "    shr rax, 11")?;
+        writeln!(self.dest, "    dec rax")?;
+        writeln!(self.dest, "    ret")?;
+
+        // the limbs in rsi, their number in rcx and the sign in rdx of the number in rax.
+        // the magnitude of a fixnum is written to the word in rdi, and 0 has no limbs.
+        writeln!(self.dest, "rt_bignum_view:")?;
+        writeln!(self.dest, "    test rax, {}", TAG_MASK)?;
+        writeln!(self.dest, "    jnz rt_bignum_view_big")?;
+        writeln!(self.dest, "    mov rdx, rax")?;
+        writeln!(self.dest, "    sar rdx, 63")?;
+        writeln!(self.dest, "    sar rax, {}", FIXNUM_SHIFT)?;
+        writeln!(self.dest, "    xor rax, rdx")?;
+        writeln!(self.dest, "    sub rax, rdx")?;
+        writeln!(self.dest, "    neg rdx")?;
+        writeln!(self.dest, "    mov QWORD PTR [rdi], rax")?;
+        writeln!(self.dest, "    mov rsi, rdi")?;
+        writeln!(self.dest, "    xor ecx, ecx")?;
+        writeln!(self.dest, "    test rax, rax")?;
+        writeln!(self.dest, "    setnz cl")?;
+        writeln!(self.dest, "    ret")?;
+        writeln!(self.dest, "rt_bignum_view_big:")?;
+        writeln!(self.dest, "    mov rcx, QWORD PTR [rax-{}]", OBJ_TAG)?;
+        writeln!(self.dest, "    shr rcx, 11")?;
+        writeln!(self.dest, "    dec rcx")?;
+        writeln!(self.dest, "    mov rdx, QWORD PTR [rax+{}]", 8 - OBJ_TAG)?;
+        writeln!(self.dest, "    lea rsi, [rax+{}]", 16 - OBJ_TAG)?;
+        writeln!(self.dest, "    ret")?;
+
+        // makes a number of the object in rax whose rcx limbs are written, with the sign in rdx.
+        // the highest zero limbs are dropped, and a magnitude that fits is returned as a fixnum.
+        writeln!(self.dest, "rt_bignum_finish:")?;
+        writeln!(self.dest, "    test rcx, rcx")?;
+        writeln!(self.dest, "    jz rt_bignum_finish_zero")?;
+        writeln!(self.dest, "    cmp QWORD PTR [rax+8*rcx+8], 0")?;
+        writeln!(self.dest, "    jne rt_bignum_finish_top")?;
+        writeln!(self.dest, "    dec rcx")?;
+        writeln!(self.dest, "    jmp rt_bignum_finish")?;
+        writeln!(self.dest, "rt_bignum_finish_zero:")?;
+        writeln!(self.dest, "    xor eax, eax")?;
+        writeln!(self.dest, "    ret")?;
+        writeln!(self.dest, "rt_bignum_finish_top:")?;
+        writeln!(self.dest, "    cmp rcx, 1")?;
+        writeln!(self.dest, "    ja rt_bignum_finish_big")?;
+        writeln!(self.dest, "    mov r10, QWORD PTR [rax+16]")?;
+        writeln!(self.dest, "    mov r11, {}", (1i64 << (63 - FIXNUM_SHIFT)) - 1)?;
+        writeln!(self.dest, "    add r11, rdx")?;
+        writeln!(self.dest, "    cmp r10, r11")?;
+        writeln!(self.dest, "    ja rt_bignum_finish_big")?;
+        writeln!(self.dest, "    neg rdx")?;
+        writeln!(self.dest, "    xor r10, rdx")?;
+        writeln!(self.dest, "    sub r10, rdx")?;
+        writeln!(self.dest, "    lea rax, [8*r10]")?;
+        writeln!(self.dest, "    ret")?;
+        writeln!(self.dest, "rt_bignum_finish_big:")?;
+        writeln!(self.dest, "    mov QWORD PTR [rax+8], rdx")?;
+        writeln!(self.dest, "    lea r10, [rcx+1]")?;
+        writeln!(self.dest, "    shl r10, 11")?;
+        writeln!(self.dest, "    or r10, {}", header(BIGNUM_TYPE, 0))?;
+        writeln!(self.dest, "    mov QWORD PTR [rax], r10")?;
+        writeln!(self.dest, "    or rax, {}", OBJ_TAG)?;
+        writeln!(self.dest, "    ret")?;
+
+        // returns -1, 0 or 1 in eax as A is below, equal to or above B
+        writeln!(self.dest, "rt_mag_cmp:")?;
+        writeln!(self.dest, "    cmp rcx, r9")?;
+        writeln!(self.dest, "    jne rt_mag_cmp_differ")?;
+        writeln!(self.dest, "    mov r10, rcx")?;
+        writeln!(self.dest, "rt_mag_cmp_loop:")?;
+        writeln!(self.dest, "    test r10, r10")?;
+        writeln!(self.dest, "    jz rt_mag_cmp_equal")?;
+        writeln!(self.dest, "    dec r10")?;
+        writeln!(self.dest, "    mov rax, QWORD PTR [rsi+8*r10]")?;
+        writeln!(self.dest, "    cmp rax, QWORD PTR [r8+8*r10]")?;
+        writeln!(self.dest, "    je rt_mag_cmp_loop")?;
+        writeln!(self.dest, "rt_mag_cmp_differ:")?;
+        writeln!(self.dest, "    sbb eax, eax")?;
+        writeln!(self.dest, "    or eax, 1")?;
+        writeln!(self.dest, "    ret")?;
+        writeln!(self.dest, "rt_mag_cmp_equal:")?;
+        writeln!(self.dest, "    xor eax, eax")?;
+        writeln!(self.dest, "    ret")?;
+
+        // A + B into rcx + 1 limbs and A - B into rcx limbs, where A has at least as many limbs
+        // as B and, for the difference, is not smaller. rdx is the carry, which is at most 1.
+        for (label, op) in [("rt_mag_add", "add"), ("rt_mag_sub", "sub")] {
+            writeln!(self.dest, "{}:", label)?;
+            writeln!(self.dest, "    xor r10d, r10d")?;
+            writeln!(self.dest, "    xor edx, edx")?;
+            writeln!(self.dest, "{}_loop:", label)?;
+            writeln!(self.dest, "    cmp r10, rcx")?;
+            writeln!(self.dest, "    jae {}_done", label)?;
+            writeln!(self.dest, "    xor r11d, r11d")?;
+            writeln!(self.dest, "    cmp r10, r9")?;
+            writeln!(self.dest, "    jae {}_limb", label)?;
+            writeln!(self.dest, "    mov r11, QWORD PTR [r8+8*r10]")?;
+            writeln!(self.dest, "{}_limb:", label)?;
+            writeln!(self.dest, "    mov rax, QWORD PTR [rsi+8*r10]")?;
+            writeln!(self.dest, "    {} rax, rdx", op)?;
+            writeln!(self.dest, "    setc dl")?;
+            writeln!(self.dest, "    {} rax, r11", op)?;
+            writeln!(self.dest, "    adc rdx, 0")?;
+            writeln!(self.dest, "    mov QWORD PTR [rdi+8*r10], rax")?;
+            writeln!(self.dest, "    inc r10")?;
+            writeln!(self.dest, "    jmp {}_loop", label)?;
+            writeln!(self.dest, "{}_done:", label)?;
+            if op == "add" {
+                writeln!(self.dest, "    mov QWORD PTR [rdi+8*r10], rdx")?;
+            }
+            writeln!(self.dest, "    ret")?;
+        }
+
+        // A * B into rcx + r9 limbs by long multiplication. r10 and r11 index the limbs of A
+        // and B, and rbx is the carry.
+        writeln!(self.dest, "rt_mag_mul:")?;
+        writeln!(self.dest, "    push rbx")?;
+        writeln!(self.dest, "    push r12")?;
+        writeln!(self.dest, "    lea r11, [rcx+r9]")?;
+        writeln!(self.dest, "    xor eax, eax")?;
+        writeln!(self.dest, "rt_mag_mul_zero:")?;
+        writeln!(self.dest, "    test r11, r11")?;
+        writeln!(self.dest, "    jz rt_mag_mul_start")?;
+        writeln!(self.dest, "    dec r11")?;
+        writeln!(self.dest, "    mov QWORD PTR [rdi+8*r11], rax")?;
+        writeln!(self.dest, "    jmp rt_mag_mul_zero")?;
+        writeln!(self.dest, "rt_mag_mul_start:")?;
+        writeln!(self.dest, "    xor r10d, r10d")?;
+        writeln!(self.dest, "rt_mag_mul_outer:")?;
+        writeln!(self.dest, "    cmp r10, rcx")?;
+        writeln!(self.dest, "    jae rt_mag_mul_done")?;
+        writeln!(self.dest, "    xor ebx, ebx")?;
+        writeln!(self.dest, "    xor r11d, r11d")?;
+        writeln!(self.dest, "rt_mag_mul_inner:")?;
+        writeln!(self.dest, "    cmp r11, r9")?;
+        writeln!(self.dest, "    jae rt_mag_mul_next")?;
+        writeln!(self.dest, "    mov rax, QWORD PTR [rsi+8*r10]")?;
+        writeln!(self.dest, "    mul QWORD PTR [r8+8*r11]")?;
+        writeln!(self.dest, "    add rax, rbx")?;
+        writeln!(self.dest, "    adc rdx, 0")?;
+        writeln!(self.dest, "    lea r12, [r10+r11]")?;
+        writeln!(self.dest, "    add QWORD PTR [rdi+8*r12], rax")?;
+        writeln!(self.dest, "    adc rdx, 0")?;
+        writeln!(self.dest, "    mov rbx, rdx")?;
+        writeln!(self.dest, "    inc r11")?;
+        writeln!(self.dest, "    jmp rt_mag_mul_inner")?;
+        writeln!(self.dest, "rt_mag_mul_next:")?;
+        writeln!(self.dest, "    lea r12, [r10+r9]")?;
+        writeln!(self.dest, "    mov QWORD PTR [rdi+8*r12], rbx")?;
+        writeln!(self.dest, "    inc r10")?;
+        writeln!(self.dest, "    jmp rt_mag_mul_outer")?;
+        writeln!(self.dest, "rt_mag_mul_done:")?;
+        writeln!(self.dest, "    pop r12")?;
+        writeln!(self.dest, "    pop rbx")?;
+        writeln!(self.dest, "    ret")?;
+
+        // divides the rcx limbs in rsi by the word in r8 into rdi from the highest limb, which
+        // may be in place. the remainder is left in rdx.
+        writeln!(self.dest, "rt_mag_div_word:")?;
+        writeln!(self.dest, "    xor edx, edx")?;
+        writeln!(self.dest, "    mov r10, rcx")?;
+        writeln!(self.dest, "rt_mag_div_word_loop:")?;
+        writeln!(self.dest, "    test r10, r10")?;
+        writeln!(self.dest, "    jz rt_mag_div_word_done")?;
+        writeln!(self.dest, "    dec r10")?;
+        writeln!(self.dest, "    mov rax, QWORD PTR [rsi+8*r10]")?;
+        writeln!(self.dest, "    div r8")?;
+        writeln!(self.dest, "    mov QWORD PTR [rdi+8*r10], rax")?;
+        writeln!(self.dest, "    jmp rt_mag_div_word_loop")?;
+        writeln!(self.dest, "rt_mag_div_word_done:")?;
+        writeln!(self.dest, "    ret")?;
+
+        // A / B into rcx limbs at rdi and the remainder into r9 + 1 limbs at r10 by binary long
+        // division. for each bit of A from the highest, rbx, the remainder is shifted left with
+        // the bit and B is subtracted from it if not larger, setting the bit of the quotient.
+        writeln!(self.dest, "rt_mag_divmod:")?;
+        writeln!(self.dest, "    push rbx")?;
+        writeln!(self.dest, "    push r13")?;
+        writeln!(self.dest, "    push r14")?;
+        writeln!(self.dest, "    xor eax, eax")?;
+        writeln!(self.dest, "    mov r11, rcx")?;
+        writeln!(self.dest, "rt_mag_divmod_zero_quotient:")?;
+        writeln!(self.dest, "    test r11, r11")?;
+        writeln!(self.dest, "    jz rt_mag_divmod_zero")?;
+        writeln!(self.dest, "    dec r11")?;
+        writeln!(self.dest, "    mov QWORD PTR [rdi+8*r11], rax")?;
+        writeln!(self.dest, "    jmp rt_mag_divmod_zero_quotient")?;
+        writeln!(self.dest, "rt_mag_divmod_zero:")?;
+        writeln!(self.dest, "    lea r11, [r9+1]")?;
+        writeln!(self.dest, "rt_mag_divmod_zero_remainder:")?;
+        writeln!(self.dest, "    dec r11")?;
+        writeln!(self.dest, "    mov QWORD PTR [r10+8*r11], rax")?;
+        writeln!(self.dest, "    jnz rt_mag_divmod_zero_remainder")?;
+        writeln!(self.dest, "    mov rbx, rcx")?;
+        writeln!(self.dest, "    shl rbx, 6")?;
+        writeln!(self.dest, "rt_mag_divmod_bit:")?;
+        writeln!(self.dest, "    test rbx, rbx")?;
+        writeln!(self.dest, "    jz rt_mag_divmod_done")?;
+        writeln!(self.dest, "    dec rbx")?;
+        writeln!(self.dest, "    lea r13, [r9+1]")?;
+        writeln!(self.dest, "    xor r14d, r14d")?;
+        writeln!(self.dest, "    bt QWORD PTR [rsi], rbx")?;
+        writeln!(self.dest, "rt_mag_divmod_shift:")?;
+        writeln!(self.dest, "    rcl QWORD PTR [r10+8*r14], 1")?;
+        writeln!(self.dest, "    inc r14")?;
+        writeln!(self.dest, "    dec r13")?;
+        writeln!(self.dest, "    jnz rt_mag_divmod_shift")?;
+        writeln!(self.dest, "    cmp QWORD PTR [r10+8*r9], 0")?;
+        writeln!(self.dest, "    jne rt_mag_divmod_sub")?;
+        writeln!(self.dest, "    mov r13, r9")?;
+        writeln!(self.dest, "rt_mag_divmod_cmp:")?;
+        writeln!(self.dest, "    dec r13")?;
+        writeln!(self.dest, "    js rt_mag_divmod_sub")?;
+        writeln!(self.dest, "    mov rax, QWORD PTR [r10+8*r13]")?;
+        writeln!(self.dest, "    cmp rax, QWORD PTR [r8+8*r13]")?;
+        writeln!(self.dest, "    ja rt_mag_divmod_sub")?;
+        writeln!(self.dest, "    jb rt_mag_divmod_bit")?;
+        writeln!(self.dest, "    jmp rt_mag_divmod_cmp")?;
+        writeln!(self.dest, "rt_mag_divmod_sub:")?;
+        writeln!(self.dest, "    mov r13, r9")?;
+        writeln!(self.dest, "    xor r14d, r14d")?;
+        writeln!(self.dest, "rt_mag_divmod_sub_loop:")?;
+        writeln!(self.dest, "    mov rax, QWORD PTR [r8+8*r14]")?;
+        writeln!(self.dest, "    sbb QWORD PTR [r10+8*r14], rax")?;
+        writeln!(self.dest, "    inc r14")?;
+        writeln!(self.dest, "    dec r13")?;
+        writeln!(self.dest, "    jnz rt_mag_divmod_sub_loop")?;
+        writeln!(self.dest, "    sbb QWORD PTR [r10+8*r14], 0")?;
+        writeln!(self.dest, "    bts QWORD PTR [rdi], rbx")?;
+        writeln!(self.dest, "    jmp rt_mag_divmod_bit")?;
+        writeln!(self.dest, "rt_mag_divmod_done:")?;
+        writeln!(self.dest, "    pop r14")?;
+        writeln!(self.dest, "    pop r13")?;
+        writeln!(self.dest, "    pop rbx")?;
+        writeln!(self.dest, "    ret")?;
+
+        // writes the decimal digits of the bignum in rdi, with its sign, to the bytes before rsi
+        // and returns where they start in rax. the limbs are divided on a copy on the stack by
+        // 10^18 to get the digits 18 at a time, from the lowest.
+        writeln!(self.dest, "rt_bignum_digits:")?;
+        writeln!(self.dest, "    push rbp")?;
+        writeln!(self.dest, "    mov rbp, rsp")?;
+        writeln!(self.dest, "    push rbx")?;
+        writeln!(self.dest, "    push r12")?;
+        writeln!(self.dest, "    mov rbx, rsi")?;
+        writeln!(self.dest, "    mov r12, QWORD PTR [rdi+{}]", 8 - OBJ_TAG)?;
+        writeln!(self.dest, "    mov rcx, QWORD PTR [rdi-{}]", OBJ_TAG)?;
+        writeln!(self.dest, "    shr rcx, 11")?;
+        writeln!(self.dest, "    dec rcx")?;
+        writeln!(self.dest, "    lea rax, [8*rcx]")?;
+        writeln!(self.dest, "    sub rsp, rax")?;
+        writeln!(self.dest, "    xor r10d, r10d")?;
+        writeln!(self.dest, "rt_bignum_digits_copy:")?;
+        writeln!(self.dest, "    mov rax, QWORD PTR [rdi+8*r10+{}]", 16 - OBJ_TAG)?;
+        writeln!(self.dest, "    mov QWORD PTR [rsp+8*r10], rax")?;
+        writeln!(self.dest, "    inc r10")?;
+        writeln!(self.dest, "    cmp r10, rcx")?;
+        writeln!(self.dest, "    jb rt_bignum_digits_copy")?;
+        writeln!(self.dest, "    mov rdi, rsp")?;
+        writeln!(self.dest, "    mov rsi, rsp")?;
+        writeln!(self.dest, "    mov r8, 1000000000000000000")?;
+        writeln!(self.dest, "    mov r11, 10")?;
+        writeln!(self.dest, "rt_bignum_digits_chunk:")?;
+        writeln!(self.dest, "    call rt_mag_div_word")?;
+        writeln!(self.dest, "rt_bignum_digits_trim:")?;
+        writeln!(self.dest, "    test rcx, rcx")?;
+        writeln!(self.dest, "    jz rt_bignum_digits_trimmed")?;
+        writeln!(self.dest, "    cmp QWORD PTR [rsp+8*rcx-8], 0")?;
+        writeln!(self.dest, "    jne rt_bignum_digits_trimmed")?;
+        writeln!(self.dest, "    dec rcx")?;
+        writeln!(self.dest, "    jmp rt_bignum_digits_trim")?;
+        writeln!(self.dest, "rt_bignum_digits_trimmed:")?;
+        writeln!(self.dest, "    mov rax, rdx")?;
+        writeln!(self.dest, "    mov r9, 18")?;
+        // the chunks below the highest have all their 18 digits, with leading zeros
+        writeln!(self.dest, "rt_bignum_digits_digit:")?;
+        writeln!(self.dest, "    xor edx, edx")?;
+        writeln!(self.dest, "    div r11")?;
+        writeln!(self.dest, "    add edx, {}", b'0')?;
+        writeln!(self.dest, "    dec rbx")?;
+        writeln!(self.dest, "    mov BYTE PTR [rbx], dl")?;
+        writeln!(self.dest, "    dec r9")?;
+        writeln!(self.dest, "    test rcx, rcx")?;
+        writeln!(self.dest, "    jz rt_bignum_digits_last")?;
+        writeln!(self.dest, "    test r9, r9")?;
+        writeln!(self.dest, "    jnz rt_bignum_digits_digit")?;
+        writeln!(self.dest, "    jmp rt_bignum_digits_chunk")?;
+        writeln!(self.dest, "rt_bignum_digits_last:")?;
+        writeln!(self.dest, "    test rax, rax")?;
+        writeln!(self.dest, "    jnz rt_bignum_digits_digit")?;
+        writeln!(self.dest, "    test r12, r12")?;
+        writeln!(self.dest, "    jz rt_bignum_digits_done")?;
+        writeln!(self.dest, "    dec rbx")?;
+        writeln!(self.dest, "    mov BYTE PTR [rbx], {}", b'-')?;
+        writeln!(self.dest, "rt_bignum_digits_done:")?;
+        writeln!(self.dest, "    mov rax, rbx")?;
+        writeln!(self.dest, "    lea rsp, [rbp-16]")?;
+        writeln!(self.dest, "    pop r12")?;
+        writeln!(self.dest, "    pop rbx")?;
+        writeln!(self.dest, "    pop rbp")?;
+        writeln!(self.dest, "    ret")?;
+        Ok(())
+    }
+
+    // jumps to the label unless both rdi and rsi are fixnums
+    fn gen_both_fixnums(&mut self, label: &str) -> io::Result<()> {
+        writeln!(self.dest, "    mov rax, rdi")?;
+        writeln!(self.dest, "    or rax, rsi")?;
+        writeln!(self.dest, "    test rax, {}", TAG_MASK)?;
+        writeln!(self.dest, "    jnz {}", label)?;
+        Ok(())
+    }
+
+    // takes the views of the numbers on the stack at [rbp-8] and [rbp-16], A in rsi, rcx and
+    // rdx, and B in r8, r9 and r11
+    fn gen_bignum_views(&mut self) -> io::Result<()> {
+        writeln!(self.dest, "    mov rax, QWORD PTR [rbp-16]")?;
+        writeln!(self.dest, "    lea rdi, [rip+rt_bignum_y]")?;
+        writeln!(self.dest, "    call rt_bignum_view")?;
+        writeln!(self.dest, "    mov r8, rsi")?;
+        writeln!(self.dest, "    mov r9, rcx")?;
+        writeln!(self.dest, "    mov r11, rdx")?;
+        writeln!(self.dest, "    mov rax, QWORD PTR [rbp-8]")?;
+        writeln!(self.dest, "    lea rdi, [rip+rt_bignum_x]")?;
+        writeln!(self.dest, "    call rt_bignum_view")?;
+        Ok(())
+    }
+
+    // called first by a procedure with a rest argument, with the number of the arguments in rcx
+    // and the number of the required ones in rdx. it conses the surplus arguments into a list
+    // and moves the return address and the required arguments so that the list takes the place
+    // of the surplus. the closure, the counts and the list are kept on the stack while allocating.
+    fn gen_rest_args(&mut self) -> io::Result<()> {
+        writeln!(self.dest, "rt_rest_args:")?;
+        writeln!(self.dest, "    push rax")?;
+        writeln!(self.dest, "    shl rcx, {}", FIXNUM_SHIFT)?;
+        writeln!(self.dest, "    push rcx")?;
+        writeln!(self.dest, "    shl rdx, {}", FIXNUM_SHIFT)?;
+        writeln!(self.dest, "    push rdx")?;
+        writeln!(self.dest, "    push rcx")?;
+        writeln!(self.dest, "    push {}", NIL)?;
+        writeln!(self.dest, "rt_rest_args_loop:")?;
+        writeln!(self.dest, "    mov rcx, QWORD PTR [rsp+8]")?;
+        writeln!(self.dest, "    cmp rcx, QWORD PTR [rsp+16]")?;
+        writeln!(self.dest, "    jle rt_rest_args_move")?;
+        writeln!(self.dest, "    sub QWORD PTR [rsp+8], {}", 1 << FIXNUM_SHIFT)?;
+        writeln!(self.dest, "    mov rdi, 16")?;
+        writeln!(self.dest, "    call rt_alloc")?;
+        writeln!(self.dest, "    mov rcx, QWORD PTR [rsp+8]")?;
+        writeln!(self.dest, "    mov rdi, QWORD PTR [rsp+rcx+56]")?;
+        writeln!(self.dest, "    mov QWORD PTR [rax], rdi")?;
+        writeln!(self.dest, "    mov rdi, QWORD PTR [rsp]")?;
+        writeln!(self.dest, "    mov QWORD PTR [rax+8], rdi")?;
+        writeln!(self.dest, "    or rax, {}", PAIR_TAG)?;
+        writeln!(self.dest, "    mov QWORD PTR [rsp], rax")?;
+        writeln!(self.dest, "    jmp rt_rest_args_loop")?;
+
+        // rsi is the list, r11 our return address and r8 the distance to move by.
+        // the words from the caller's return address to the last required argument are moved.
+        writeln!(self.dest, "rt_rest_args_move:")?;
+        writeln!(self.dest, "    pop rsi")?;
+        writeln!(self.dest, "    pop rcx")?;
+        writeln!(self.dest, "    pop rdx")?;
+        writeln!(self.dest, "    pop rcx")?;
+        writeln!(self.dest, "    pop rax")?;
+        writeln!(self.dest, "    pop r11")?;
+        writeln!(self.dest, "    sar rdx, {}", FIXNUM_SHIFT)?;
+        writeln!(self.dest, "    sar rcx, {}", FIXNUM_SHIFT)?;
+        writeln!(self.dest, "    lea r8, [rcx-1]")?;
+        writeln!(self.dest, "    sub r8, rdx")?;
+        writeln!(self.dest, "    lea rdi, [rsp+8*r8]")?;
+        writeln!(self.dest, "    test r8, r8")?;
+        writeln!(self.dest, "    jz rt_rest_args_done")?;
+        writeln!(self.dest, "    js rt_rest_args_down")?;
+        writeln!(self.dest, "    mov r9, rdx")?;
+        writeln!(self.dest, "rt_rest_args_up:")?;
+        writeln!(self.dest, "    mov r10, QWORD PTR [rsp+8*r9]")?;
+        writeln!(self.dest, "    mov QWORD PTR [rdi+8*r9], r10")?;
+        writeln!(self.dest, "    dec r9")?;
+        writeln!(self.dest, "    jns rt_rest_args_up")?;
+        writeln!(self.dest, "    jmp rt_rest_args_done")?;
+        writeln!(self.dest, "rt_rest_args_down:")?;
+        writeln!(self.dest, "    xor r9d, r9d")?;
+        writeln!(self.dest, "rt_rest_args_down_loop:")?;
+        writeln!(self.dest, "    mov r10, QWORD PTR [rsp+8*r9]")?;
+        writeln!(self.dest, "    mov QWORD PTR [rdi+8*r9], r10")?;
+        writeln!(self.dest, "    inc r9")?;
+        writeln!(self.dest, "    cmp r9, rdx")?;
+        writeln!(self.dest, "    jbe rt_rest_args_down_loop")?;
+        writeln!(self.dest, "rt_rest_args_done:")?;
+        writeln!(self.dest, "    mov rsp, rdi")?;
+        writeln!(self.dest, "    mov QWORD PTR [rsp+8*rdx+8], rsi")?;
+        writeln!(self.dest, "    jmp r11")?;
+        Ok(())
+    }
+
+    // a symbol holds its name. symbols are interned in the list `rt_symbol_table`,
+    // so symbols of the same name are the same object.
+    fn gen_symbols(&mut self) -> io::Result<()> {
+        self.gen_type_predicate("rt_is_symbol", SYMBOL_TYPE)?;
+
+        // the name is copied, so that changing the string does not change the symbol
+        self.gen_routine("rt_symbol_to_string")?;
+        writeln!(self.dest, "    mov rax, QWORD PTR [rsp+8]")?;
+        self.check_type("rax", SYMBOL_TYPE)?;
+        writeln!(self.dest, "    mov rax, QWORD PTR [rax+{}]", 8 - OBJ_TAG)?;
+        writeln!(self.dest, "    mov QWORD PTR [rsp+8], rax")?;
+        writeln!(self.dest, "    jmp rt_string_copy")?;
+
+        // looks up the name in the table, and makes a new symbol with a copy of it if it is not there
+        self.gen_routine("rt_string_to_symbol")?;
+        writeln!(self.dest, "    mov r10, QWORD PTR [rsp+8]")?;
+        self.check_type("r10", STRING_TYPE)?;
+        writeln!(self.dest, "    mov rdx, QWORD PTR [rip+rt_symbol_table]")?;
+        writeln!(self.dest, "rt_string_to_symbol_loop:")?;
+        writeln!(self.dest, "    cmp rdx, {}", NIL)?;
+        writeln!(self.dest, "    je rt_string_to_symbol_new")?;
+        writeln!(self.dest, "    mov rax, QWORD PTR [rdx-{}]", PAIR_TAG)?;
+        writeln!(self.dest, "    mov rsi, QWORD PTR [rax+{}]", 8 - OBJ_TAG)?;
+        writeln!(self.dest, "    mov rcx, QWORD PTR [rsi-{}]", OBJ_TAG)?;
+        writeln!(self.dest, "    cmp rcx, QWORD PTR [r10-{}]", OBJ_TAG)?;
+        writeln!(self.dest, "    jne rt_string_to_symbol_next")?;
+        writeln!(self.dest, "    shr rcx, 8")?;
+        writeln!(self.dest, "    add rsi, {}", 8 - OBJ_TAG)?;
+        writeln!(self.dest, "    lea rdi, [r10+{}]", 8 - OBJ_TAG)?;
+        writeln!(self.dest, "    repe cmpsb")?;
+        writeln!(self.dest, "    jne rt_string_to_symbol_next")?;
+        writeln!(self.dest, "    ret 8")?;
+        writeln!(self.dest, "rt_string_to_symbol_next:")?;
+        writeln!(self.dest, "    mov rdx, QWORD PTR [rdx+{}]", 8 - PAIR_TAG)?;
+        writeln!(self.dest, "    jmp rt_string_to_symbol_loop")?;
+        writeln!(self.dest, "rt_string_to_symbol_new:")?;
+        writeln!(self.dest, "    push QWORD PTR [rsp+8]")?;
+        writeln!(self.dest, "    call rt_string_copy")?;
+        writeln!(self.dest, "    push rax")?;
+        writeln!(self.dest, "    mov rdi, 16")?;
+        writeln!(self.dest, "    call rt_alloc")?;
+        writeln!(self.dest, "    mov QWORD PTR [rax], {}", header(SYMBOL_TYPE, 1))?;
+        writeln!(self.dest, "    pop rdi")?;
+        writeln!(self.dest, "    mov QWORD PTR [rax+8], rdi")?;
+        writeln!(self.dest, "    or rax, {}", OBJ_TAG)?;
+        writeln!(self.dest, "    push rax")?;
+        writeln!(self.dest, "    mov rdi, 16")?;
+        writeln!(self.dest, "    call rt_alloc")?;
+        writeln!(self.dest, "    pop rdi")?;
+        writeln!(self.dest, "    mov QWORD PTR [rax], rdi")?;
+        writeln!(self.dest, "    mov rsi, QWORD PTR [rip+rt_symbol_table]")?;
+        writeln!(self.dest, "    mov QWORD PTR [rax+8], rsi")?;
+        writeln!(self.dest, "    or rax, {}", PAIR_TAG)?;
+        writeln!(self.dest, "    mov QWORD PTR [rip+rt_symbol_table], rax")?;
+        writeln!(self.dest, "    mov rax, rdi")?;
+        writeln!(self.dest, "    ret 8")?;
+        Ok(())
+    }
+
+    // the output goes through stdio to the stream in rt_print_stream, which is stdout until an error.
+    // `rt_print` writes the value in rdi, in the external representation if rsi is 1.
+    // the helpers align the stack before calling the C library.
+    fn gen_output(&mut self) -> io::Result<()> {
+        self.gen_routine("rt_display")?;
+        writeln!(self.dest, "    mov rdi, QWORD PTR [rsp+8]")?;
+        writeln!(self.dest, "    xor esi, esi")?;
+        writeln!(self.dest, "    call rt_print")?;
+        writeln!(self.dest, "    mov rax, {}", VOID)?;
+        writeln!(self.dest, "    ret 8")?;
+
+        self.gen_routine("rt_write_datum")?;
+        writeln!(self.dest, "    mov rdi, QWORD PTR [rsp+8]")?;
+        writeln!(self.dest, "    mov esi, 1")?;
+        writeln!(self.dest, "    call rt_print")?;
+        writeln!(self.dest, "    mov rax, {}", VOID)?;
+        writeln!(self.dest, "    ret 8")?;
+
+        self.gen_routine("rt_newline")?;
+        writeln!(self.dest, "    mov edi, {}", b'\n')?;
+        writeln!(self.dest, "    call rt_print_byte")?;
+        writeln!(self.dest, "    mov rax, {}", VOID)?;
+        writeln!(self.dest, "    ret")?;
+
+        self.gen_routine("rt_write_char")?;
+        writeln!(self.dest, "    mov rdi, QWORD PTR [rsp+8]")?;
+        self.check_char("rdi")?;
+        writeln!(self.dest, "    shr rdi, {}", CHAR_SHIFT)?;
+        writeln!(self.dest, "    call rt_print_utf8")?;
+        writeln!(self.dest, "    mov rax, {}", VOID)?;
+        writeln!(self.dest, "    ret 8")?;
+
+        self.gen_routine("rt_write_string")?;
+        writeln!(self.dest, "    mov rdi, QWORD PTR [rsp+8]")?;
+        self.check_type("rdi", STRING_TYPE)?;
+        writeln!(self.dest, "    mov rsi, QWORD PTR [rdi-{}]", OBJ_TAG)?;
+        writeln!(self.dest, "    shr rsi, 8")?;
+        writeln!(self.dest, "    add rdi, {}", 8 - OBJ_TAG)?;
+        writeln!(self.dest, "    call rt_print_chars")?;
+        writeln!(self.dest, "    mov rax, {}", VOID)?;
+        writeln!(self.dest, "    ret 8")?;
+
+        // rbx is the value and r12 is the flag
+        writeln!(self.dest, "rt_print:")?;
+        writeln!(self.dest, "    push rbx")?;
+        writeln!(self.dest, "    push r12")?;
+        writeln!(self.dest, "    mov rbx, rdi")?;
+        writeln!(self.dest, "    mov r12, rsi")?;
+        writeln!(self.dest, "    mov rax, rbx")?;
+        writeln!(self.dest, "    and eax, {}", TAG_MASK)?;
+        writeln!(self.dest, "    cmp eax, {}", FIXNUM_TAG)?;
+        writeln!(self.dest, "    je rt_print_fixnum")?;
+        writeln!(self.dest, "    cmp eax, {}", PAIR_TAG)?;
+        writeln!(self.dest, "    je rt_print_pair")?;
+        writeln!(self.dest, "    cmp eax, {}", OBJ_TAG)?;
+        writeln!(self.dest, "    je rt_print_object")?;
+        writeln!(self.dest, "    lea rdi, [rip+rt_print_proc_str]")?;
+        writeln!(self.dest, "    cmp eax, {}", PROC_TAG)?;
+        writeln!(self.dest, "    je rt_print_text")?;
+        writeln!(self.dest, "    movzx eax, bl")?;
+        writeln!(self.dest, "    cmp eax, {}", CHAR_TAG)?;
+        writeln!(self.dest, "    je rt_print_char")?;
+        for (label, value) in [("true", TRUE), ("false", FALSE), ("nil", NIL), ("void", VOID)] {
+            writeln!(self.dest, "    lea rdi, [rip+rt_print_{}_str]", label)?;
+            writeln!(self.dest, "    cmp rbx, {}", value)?;
+            writeln!(self.dest, "    je rt_print_text")?;
+        }
+        writeln!(self.dest, "    lea rdi, [rip+rt_print_unknown_str]")?;
+        writeln!(self.dest, "rt_print_text:")?;
+        writeln!(self.dest, "    call rt_print_format")?;
+        writeln!(self.dest, "rt_print_done:")?;
+        writeln!(self.dest, "    pop r12")?;
+        writeln!(self.dest, "    pop rbx")?;
+        writeln!(self.dest, "    ret")?;
+
+        writeln!(self.dest, "rt_print_fixnum:")?;
+        writeln!(self.dest, "    lea rdi, [rip+rt_print_long_str]")?;
+        writeln!(self.dest, "    mov rsi, rbx")?;
+        writeln!(self.dest, "    sar rsi, {}", FIXNUM_SHIFT)?;
+        writeln!(self.dest, "    jmp rt_print_text")?;
+
+        // the cars are printed recursively and the cdrs in a loop
+        writeln!(self.dest, "rt_print_pair:")?;
+        writeln!(self.dest, "    mov edi, {}", b'(')?;
+        writeln!(self.dest, "    call rt_print_byte")?;
+        writeln!(self.dest, "rt_print_pair_loop:")?;
+        writeln!(self.dest, "    mov rdi, QWORD PTR [rbx-{}]", PAIR_TAG)?;
+        writeln!(self.dest, "    mov rsi, r12")?;
+        writeln!(self.dest, "    call rt_print")?;
+        writeln!(self.dest, "    mov rbx, QWORD PTR [rbx+{}]", 8 - PAIR_TAG)?;
+        writeln!(self.dest, "    cmp rbx, {}", NIL)?;
+        writeln!(self.dest, "    je rt_print_pair_done")?;
+        writeln!(self.dest, "    mov rax, rbx")?;
+        writeln!(self.dest, "    and eax, {}", TAG_MASK)?;
+        writeln!(self.dest, "    cmp eax, {}", PAIR_TAG)?;
+        writeln!(self.dest, "    jne rt_print_pair_dot")?;
+        writeln!(self.dest, "    mov edi, {}", b' ')?;
+        writeln!(self.dest, "    call rt_print_byte")?;
+        writeln!(self.dest, "    jmp rt_print_pair_loop")?;
+        writeln!(self.dest, "rt_print_pair_dot:")?;
+        writeln!(self.dest, "    lea rdi, [rip+rt_print_dot_str]")?;
+        writeln!(self.dest, "    call rt_print_format")?;
+        writeln!(self.dest, "    mov rdi, rbx")?;
+        writeln!(self.dest, "    mov rsi, r12")?;
+        writeln!(self.dest, "    call rt_print")?;
+        writeln!(self.dest, "rt_print_pair_done:")?;
+        writeln!(self.dest, "    mov edi, {}", b')')?;
+        writeln!(self.dest, "    call rt_print_byte")?;
+        writeln!(self.dest, "    jmp rt_print_done")?;
+
+        // r12 holds the code of the character once the flag is no longer needed
+        writeln!(self.dest, "rt_print_char:")?;
+        writeln!(self.dest, "    mov rdi, rbx")?;
+        writeln!(self.dest, "    shr rdi, {}", CHAR_SHIFT)?;
+        writeln!(self.dest, "    test r12, r12")?;
+        writeln!(self.dest, "    jnz rt_print_char_write")?;
+        writeln!(self.dest, "    call rt_print_utf8")?;
+        writeln!(self.dest, "    jmp rt_print_done")?;
+        writeln!(self.dest, "rt_print_char_write:")?;
+        writeln!(self.dest, "    mov r12, rdi")?;
+        for (name, c) in CHAR_NAMES {
+            writeln!(self.dest, "    lea rdi, [rip+rt_print_char_{}_str]", name)?;
+            writeln!(self.dest, "    cmp r12, {}", c as u32)?;
+            writeln!(self.dest, "    je rt_print_text")?;
+        }
+        writeln!(self.dest, "    lea rdi, [rip+rt_print_char_hex_str]")?;
+        writeln!(self.dest, "    mov rsi, r12")?;
+        writeln!(self.dest, "    cmp r12, {}", b' ')?;
+        writeln!(self.dest, "    jb rt_print_text")?;
+        writeln!(self.dest, "    lea rdi, [rip+rt_print_char_str]")?;
+        writeln!(self.dest, "    call rt_print_format")?;
+        writeln!(self.dest, "    mov rdi, r12")?;
+        writeln!(self.dest, "    call rt_print_utf8")?;
+        writeln!(self.dest, "    jmp rt_print_done")?;
+
+        writeln!(self.dest, "rt_print_object:")?;
+        writeln!(self.dest, "    mov rax, QWORD PTR [rbx-{}]", OBJ_TAG)?;
+        writeln!(self.dest, "    and eax, 255")?;
+        writeln!(self.dest, "    cmp eax, {}", header(STRING_TYPE, 0))?;
+        writeln!(self.dest, "    je rt_print_string")?;
+        writeln!(self.dest, "    cmp eax, {}", header(SYMBOL_TYPE, 0))?;
+        writeln!(self.dest, "    je rt_print_symbol")?;
+        writeln!(self.dest, "    cmp eax, {}", header(VECTOR_TYPE, 0))?;
+        writeln!(self.dest, "    je rt_print_vector")?;
+        writeln!(self.dest, "    cmp eax, {}", header(BYTEVECTOR_TYPE, 0))?;
+        writeln!(self.dest, "    je rt_print_bytevector")?;
+        writeln!(self.dest, "    cmp eax, {}", header(BIGNUM_TYPE, 0))?;
+        writeln!(self.dest, "    je rt_print_bignum")?;
+        writeln!(self.dest, "    lea rdi, [rip+rt_print_unknown_str]")?;
+        writeln!(self.dest, "    jmp rt_print_text")?;
+
+        // the digits are written to a buffer on the stack, with fewer than 24 for each limb
+        writeln!(self.dest, "rt_print_bignum:")?;
+        writeln!(self.dest, "    push rbp")?;
+        writeln!(self.dest, "    mov rbp, rsp")?;
+        writeln!(self.dest, "    mov rax, rbx")?;
+        writeln!(self.dest, "    call rt_bignum_size")?;
+        writeln!(self.dest, "    imul rax, rax, 24")?;
+        writeln!(self.dest, "    sub rsp, rax")?;
+        writeln!(self.dest, "    mov rdi, rbx")?;
+        writeln!(self.dest, "    mov rsi, rbp")?;
+        writeln!(self.dest, "    call rt_bignum_digits")?;
+        writeln!(self.dest, "    mov rdi, rax")?;
+        writeln!(self.dest, "    mov rsi, rbp")?;
+        writeln!(self.dest, "    sub rsi, rax")?;
+        writeln!(self.dest, "    call rt_print_chars")?;
+        writeln!(self.dest, "    leave")?;
+        writeln!(self.dest, "    jmp rt_print_done")?;
+
+        // a written symbol is between bars unless it reads back as the same symbol: it is not
+        // empty or ".", its first byte can begin it and is not a sign followed by a digit, and
+        // its other bytes can be in it. rbx is the current byte and r12 the end of the name.
+        writeln!(self.dest, "rt_print_symbol:")?;
+        writeln!(self.dest, "    mov rdi, QWORD PTR [rbx+{}]", 8 - OBJ_TAG)?;
+        writeln!(self.dest, "    mov rsi, QWORD PTR [rdi-{}]", OBJ_TAG)?;
+        writeln!(self.dest, "    shr rsi, 8")?;
+        writeln!(self.dest, "    add rdi, {}", 8 - OBJ_TAG)?;
+        writeln!(self.dest, "    test r12, r12")?;
+        writeln!(self.dest, "    jz rt_print_symbol_plain")?;
+        writeln!(self.dest, "    lea r8, [rip+rt_symbol_chars]")?;
+        writeln!(self.dest, "    test rsi, rsi")?;
+        writeln!(self.dest, "    jz rt_print_symbol_bars")?;
+        writeln!(self.dest, "    movzx eax, BYTE PTR [rdi]")?;
+        writeln!(self.dest, "    test BYTE PTR [r8+rax], 2")?;
+        writeln!(self.dest, "    jz rt_print_symbol_bars")?;
+        writeln!(self.dest, "    cmp rsi, 1")?;
+        writeln!(self.dest, "    jne rt_print_symbol_sign")?;
+        writeln!(self.dest, "    cmp eax, {}", b'.')?;
+        writeln!(self.dest, "    je rt_print_symbol_bars")?;
+        writeln!(self.dest, "    jmp rt_print_symbol_plain")?;
+        writeln!(self.dest, "rt_print_symbol_sign:")?;
+        writeln!(self.dest, "    cmp eax, {}", b'+')?;
+        writeln!(self.dest, "    je rt_print_symbol_digit")?;
+        writeln!(self.dest, "    cmp eax, {}", b'-')?;
+        writeln!(self.dest, "    jne rt_print_symbol_check")?;
+        writeln!(self.dest, "rt_print_symbol_digit:")?;
+        writeln!(self.dest, "    movzx eax, BYTE PTR [rdi+1]")?;
+        writeln!(self.dest, "    sub eax, {}", b'0')?;
+        writeln!(self.dest, "    cmp eax, 9")?;
+        writeln!(self.dest, "    jbe rt_print_symbol_bars")?;
+        writeln!(self.dest, "rt_print_symbol_check:")?;
+        writeln!(self.dest, "    mov ecx, 1")?;
+        writeln!(self.dest, "rt_print_symbol_check_loop:")?;
+        writeln!(self.dest, "    cmp rcx, rsi")?;
+        writeln!(self.dest, "    jae rt_print_symbol_plain")?;
+        writeln!(self.dest, "    movzx eax, BYTE PTR [rdi+rcx]")?;
+        writeln!(self.dest, "    test BYTE PTR [r8+rax], 1")?;
+        writeln!(self.dest, "    jz rt_print_symbol_bars")?;
+        writeln!(self.dest, "    inc rcx")?;
+        writeln!(self.dest, "    jmp rt_print_symbol_check_loop")?;
+        writeln!(self.dest, "rt_print_symbol_plain:")?;
+        writeln!(self.dest, "    call rt_print_chars")?;
+        writeln!(self.dest, "    jmp rt_print_done")?;
+        writeln!(self.dest, "rt_print_symbol_bars:")?;
+        writeln!(self.dest, "    mov rbx, rdi")?;
+        writeln!(self.dest, "    lea r12, [rdi+rsi]")?;
+        writeln!(self.dest, "    mov edi, {}", b'|')?;
+        writeln!(self.dest, "    call rt_print_byte")?;
+        writeln!(self.dest, "rt_print_symbol_loop:")?;
+        writeln!(self.dest, "    cmp rbx, r12")?;
+        writeln!(self.dest, "    jae rt_print_symbol_done")?;
+        writeln!(self.dest, "    movzx edi, BYTE PTR [rbx]")?;
+        writeln!(self.dest, "    cmp edi, {}", b'|')?;
+        writeln!(self.dest, "    je rt_print_symbol_escape")?;
+        writeln!(self.dest, "    cmp edi, {}", b'\\')?;
+        writeln!(self.dest, "    jne rt_print_symbol_next")?;
+        writeln!(self.dest, "rt_print_symbol_escape:")?;
+        writeln!(self.dest, "    mov edi, {}", b'\\')?;
+        writeln!(self.dest, "    call rt_print_byte")?;
+        writeln!(self.dest, "    movzx edi, BYTE PTR [rbx]")?;
+        writeln!(self.dest, "rt_print_symbol_next:")?;
+        writeln!(self.dest, "    call rt_print_utf8")?;
+        writeln!(self.dest, "    inc rbx")?;
+        writeln!(self.dest, "    jmp rt_print_symbol_loop")?;
+        writeln!(self.dest, "rt_print_symbol_done:")?;
+        writeln!(self.dest, "    mov edi, {}", b'|')?;
+        writeln!(self.dest, "    call rt_print_byte")?;
+        writeln!(self.dest, "    jmp rt_print_done")?;
+
+        // rbx is the current element and the end of the vector is on the stack
+        writeln!(self.dest, "rt_print_vector:")?;
+        writeln!(self.dest, "    mov rax, QWORD PTR [rbx-{}]", OBJ_TAG)?;
+        writeln!(self.dest, "    shr rax, 8")?;
+        writeln!(self.dest, "    lea rax, [rbx+8*rax+{}]", 8 - OBJ_TAG)?;
+        writeln!(self.dest, "    push rax")?;
+        writeln!(self.dest, "    add rbx, {}", 8 - OBJ_TAG)?;
+        writeln!(self.dest, "    mov edi, {}", b'#')?;
+        writeln!(self.dest, "    call rt_print_byte")?;
+        writeln!(self.dest, "    mov edi, {}", b'(')?;
+        writeln!(self.dest, "    call rt_print_byte")?;
+        writeln!(self.dest, "    cmp rbx, QWORD PTR [rsp]")?;
+        writeln!(self.dest, "    jae rt_print_vector_done")?;
+        writeln!(self.dest, "rt_print_vector_loop:")?;
+        writeln!(self.dest, "    mov rdi, QWORD PTR [rbx]")?;
+        writeln!(self.dest, "    mov rsi, r12")?;
+        writeln!(self.dest, "    call rt_print")?;
+        writeln!(self.dest, "    add rbx, 8")?;
+        writeln!(self.dest, "    cmp rbx, QWORD PTR [rsp]")?;
+        writeln!(self.dest, "    jae rt_print_vector_done")?;
+        writeln!(self.dest, "    mov edi, {}", b' ')?;
+        writeln!(self.dest, "    call rt_print_byte")?;
+        writeln!(self.dest, "    jmp rt_print_vector_loop")?;
+        writeln!(self.dest, "rt_print_vector_done:")?;
+        writeln!(self.dest, "    add rsp, 8")?;
+        writeln!(self.dest, "    mov edi, {}", b')')?;
+        writeln!(self.dest, "    call rt_print_byte")?;
+        writeln!(self.dest, "    jmp rt_print_done")?;
+
+        // bytes look the same written or displayed, so r12 becomes the end of the bytevector.
+        // rbx is the current byte.
+        writeln!(self.dest, "rt_print_bytevector:")?;
+        writeln!(self.dest, "    mov r12, QWORD PTR [rbx-{}]", OBJ_TAG)?;
+        writeln!(self.dest, "    shr r12, 8")?;
+        writeln!(self.dest, "    add rbx, {}", 8 - OBJ_TAG)?;
+        writeln!(self.dest, "    add r12, rbx")?;
+        writeln!(self.dest, "    lea rdi, [rip+rt_print_bytevector_str]")?;
+        writeln!(self.dest, "    call rt_print_format")?;
+        writeln!(self.dest, "    cmp rbx, r12")?;
+        writeln!(self.dest, "    jae rt_print_bytevector_done")?;
+        writeln!(self.dest, "rt_print_bytevector_loop:")?;
+        writeln!(self.dest, "    lea rdi, [rip+rt_print_long_str]")?;
+        writeln!(self.dest, "    movzx esi, BYTE PTR [rbx]")?;
+        writeln!(self.dest, "    call rt_print_format")?;
+        writeln!(self.dest, "    inc rbx")?;
+        writeln!(self.dest, "    cmp rbx, r12")?;
+        writeln!(self.dest, "    jae rt_print_bytevector_done")?;
+        writeln!(self.dest, "    mov edi, {}", b' ')?;
+        writeln!(self.dest, "    call rt_print_byte")?;
+        writeln!(self.dest, "    jmp rt_print_bytevector_loop")?;
+        writeln!(self.dest, "rt_print_bytevector_done:")?;
+        writeln!(self.dest, "    mov edi, {}", b')')?;
+        writeln!(self.dest, "    call rt_print_byte")?;
+        writeln!(self.dest, "    jmp rt_print_done")?;
+
+        // written strings are quoted and their special characters escaped.
+        // rbx is the current byte and r12 the end of the string.
+        writeln!(self.dest, "rt_print_string:")?;
+        writeln!(self.dest, "    mov rsi, QWORD PTR [rbx-{}]", OBJ_TAG)?;
+        writeln!(self.dest, "    shr rsi, 8")?;
+        writeln!(self.dest, "    lea rdi, [rbx+{}]", 8 - OBJ_TAG)?;
+        writeln!(self.dest, "    test r12, r12")?;
+        writeln!(self.dest, "    jnz rt_print_string_write")?;
+        writeln!(self.dest, "    call rt_print_chars")?;
+        writeln!(self.dest, "    jmp rt_print_done")?;
+        writeln!(self.dest, "rt_print_string_write:")?;
+        writeln!(self.dest, "    mov rbx, rdi")?;
+        writeln!(self.dest, "    lea r12, [rdi+rsi]")?;
+        writeln!(self.dest, "    mov edi, {}", b'"')?;
+        writeln!(self.dest, "    call rt_print_byte")?;
+        writeln!(self.dest, "rt_print_string_loop:")?;
+        writeln!(self.dest, "    cmp rbx, r12")?;
+        writeln!(self.dest, "    jae rt_print_string_done")?;
+        writeln!(self.dest, "    movzx edi, BYTE PTR [rbx]")?;
+        for (i, (byte, _)) in STRING_ESCAPES.iter().enumerate() {
+            writeln!(self.dest, "    cmp edi, {}", byte)?;
+            writeln!(self.dest, "    je rt_print_string_escape_{}", i)?;
+        }
+        writeln!(self.dest, "rt_print_string_next:")?;
+        writeln!(self.dest, "    call rt_print_utf8")?;
+        writeln!(self.dest, "    inc rbx")?;
+        writeln!(self.dest, "    jmp rt_print_string_loop")?;
+        for (i, (_, escaped)) in STRING_ESCAPES.iter().enumerate() {
+            writeln!(self.dest, "rt_print_string_escape_{}:", i)?;
+            writeln!(self.dest, "    mov edi, {}", b'\\')?;
+            writeln!(self.dest, "    call rt_print_byte")?;
+            writeln!(self.dest, "    mov edi, {}", escaped)?;
+            writeln!(self.dest, "    jmp rt_print_string_next")?;
+        }
+        writeln!(self.dest, "rt_print_string_done:")?;
+        writeln!(self.dest, "    mov edi, {}", b'"')?;
+        writeln!(self.dest, "    call rt_print_byte")?;
+        writeln!(self.dest, "    jmp rt_print_done")?;
+
+        // fprintf with the format in rdi and the arguments in rsi and rdx
+        writeln!(self.dest, "rt_print_format:")?;
+        writeln!(self.dest, "    push rbp")?;
+        writeln!(self.dest, "    mov rbp, rsp")?;
+        writeln!(self.dest, "    and rsp, -16")?;
+        writeln!(self.dest, "    xor eax, eax")?;
+        writeln!(self.dest, "    mov rcx, rdx")?;
+        writeln!(self.dest, "    mov rdx, rsi")?;
+        writeln!(self.dest, "    mov rsi, rdi")?;
+        writeln!(self.dest, "    mov rdi, [rip+rt_print_stream]")?;
+        writeln!(self.dest, "    call fprintf")?;
+        writeln!(self.dest, "    leave")?;
+        writeln!(self.dest, "    ret")?;
+
+        writeln!(self.dest, "rt_print_byte:")?;
+        writeln!(self.dest, "    push rbp")?;
+        writeln!(self.dest, "    mov rbp, rsp")?;
+        writeln!(self.dest, "    and rsp, -16")?;
+        writeln!(self.dest, "    mov rsi, [rip+rt_print_stream]")?;
+        writeln!(self.dest, "    call fputc")?;
+        writeln!(self.dest, "    leave")?;
+        writeln!(self.dest, "    ret")?;
+
+        // writes the rsi characters at rdi, a byte each
+        writeln!(self.dest, "rt_print_chars:")?;
+        writeln!(self.dest, "    push rbx")?;
+        writeln!(self.dest, "    push r12")?;
+        writeln!(self.dest, "    mov rbx, rdi")?;
+        writeln!(self.dest, "    lea r12, [rdi+rsi]")?;
+        writeln!(self.dest, "rt_print_chars_loop:")?;
+        writeln!(self.dest, "    cmp rbx, r12")?;
+        writeln!(self.dest, "    jae rt_print_chars_done")?;
+        writeln!(self.dest, "    movzx edi, BYTE PTR [rbx]")?;
+        writeln!(self.dest, "    call rt_print_utf8")?;
+        writeln!(self.dest, "    inc rbx")?;
+        writeln!(self.dest, "    jmp rt_print_chars_loop")?;
+        writeln!(self.dest, "rt_print_chars_done:")?;
+        writeln!(self.dest, "    pop r12")?;
+        writeln!(self.dest, "    pop rbx")?;
+        writeln!(self.dest, "    ret")?;
+
+        // writes the character code in rdi encoded in utf-8
+        writeln!(self.dest, "rt_print_utf8:")?;
+        writeln!(self.dest, "    cmp edi, 0x80")?;
+        writeln!(self.dest, "    jb rt_print_byte")?;
+        writeln!(self.dest, "    push rbx")?;
+        writeln!(self.dest, "    mov ebx, edi")?;
+        writeln!(self.dest, "    cmp ebx, 0x800")?;
+        writeln!(self.dest, "    jae rt_print_utf8_3")?;
+        writeln!(self.dest, "    shr edi, 6")?;
+        writeln!(self.dest, "    or edi, 0xc0")?;
+        writeln!(self.dest, "    call rt_print_byte")?;
+        writeln!(self.dest, "    jmp rt_print_utf8_1")?;
+        writeln!(self.dest, "rt_print_utf8_3:")?;
+        writeln!(self.dest, "    cmp ebx, 0x10000")?;
+        writeln!(self.dest, "    jae rt_print_utf8_4")?;
+        writeln!(self.dest, "    shr edi, 12")?;
+        writeln!(self.dest, "    or edi, 0xe0")?;
+        writeln!(self.dest, "    call rt_print_byte")?;
+        writeln!(self.dest, "    jmp rt_print_utf8_2")?;
+        writeln!(self.dest, "rt_print_utf8_4:")?;
+        writeln!(self.dest, "    shr edi, 18")?;
+        writeln!(self.dest, "    or edi, 0xf0")?;
+        writeln!(self.dest, "    call rt_print_byte")?;
+        writeln!(self.dest, "    mov edi, ebx")?;
+        writeln!(self.dest, "    shr edi, 12")?;
+        writeln!(self.dest, "    and edi, 0x3f")?;
+        writeln!(self.dest, "    or edi, 0x80")?;
+        writeln!(self.dest, "    call rt_print_byte")?;
+        writeln!(self.dest, "rt_print_utf8_2:")?;
+        writeln!(self.dest, "    mov edi, ebx")?;
+        writeln!(self.dest, "    shr edi, 6")?;
+        writeln!(self.dest, "    and edi, 0x3f")?;
+        writeln!(self.dest, "    or edi, 0x80")?;
+        writeln!(self.dest, "    call rt_print_byte")?;
+        writeln!(self.dest, "rt_print_utf8_1:")?;
+        writeln!(self.dest, "    mov edi, ebx")?;
+        writeln!(self.dest, "    and edi, 0x3f")?;
+        writeln!(self.dest, "    or edi, 0x80")?;
+        writeln!(self.dest, "    call rt_print_byte")?;
+        writeln!(self.dest, "    pop rbx")?;
+        writeln!(self.dest, "    ret")?;
+        Ok(())
+    }
+
+    // writes the header of a new string in rax of the length in the register, and its last zero byte
+    fn gen_string_header(&mut self, len: &str) -> io::Result<()> {
+        writeln!(self.dest, "    mov r10, {}", len)?;
+        writeln!(self.dest, "    shl r10, 8")?;
+        writeln!(self.dest, "    or r10, {}", header(STRING_TYPE, 0))?;
+        writeln!(self.dest, "    mov QWORD PTR [rax], r10")?;
+        writeln!(self.dest, "    mov BYTE PTR [rax+{}+8], 0", len)?;
+        Ok(())
+    }
+
+    // returns the value of a constant, adding the objects it is made of to the static data
+    fn gen_datum(&mut self, datum: &Datum) -> String {
+        match datum {
+            Datum::Bool(val) => (if *val { TRUE } else { FALSE }).to_string(),
+            Datum::Int(val) => (*val << FIXNUM_SHIFT).to_string(),
+            Datum::Char(c) => (((*c as i64) << CHAR_SHIFT) | CHAR_TAG).to_string(),
+            Datum::Nil => NIL.to_string(),
+            Datum::Str(string) => {
+                self.strings.push(string.clone());
+                format!("string_{}+{}", self.strings.len() - 1, OBJ_TAG)
+            },
+            Datum::Symbol(name) => {
+                let id = match self.symbols.iter().position(|symbol| symbol == name) {
+                    Some(id) => id,
+                    None => {
+                        self.symbols.push(name.clone());
+                        self.symbols.len() - 1
+                    },
+                };
+                format!("symbol_{}+{}", id, OBJ_TAG)
+            },
+            Datum::Pair(car, cdr) => {
+                let car = self.gen_datum(car);
+                let cdr = self.gen_datum(cdr);
+                let label = self.datum_label();
+                self.data.push(format!("    .quad {}, {}", car, cdr));
+                format!("{}+{}", label, PAIR_TAG)
+            },
+            Datum::Vector(items) => {
+                let items: Vec<String> = items.iter().map(|item| self.gen_datum(item)).collect();
+                let label = self.datum_label();
+                self.data.push(format!("    .quad {}", header(VECTOR_TYPE, items.len())));
+                for item in items {
+                    self.data.push(format!("    .quad {}", item));
+                }
+                format!("{}+{}", label, OBJ_TAG)
+            },
+            Datum::BigInt(negative, limbs) => {
+                let label = self.datum_label();
+                self.data.push(format!("    .quad {}", header(BIGNUM_TYPE, 8 * (limbs.len() + 1))));
+                self.data.push(format!("    .quad {}", *negative as i64));
+                for limb in limbs {
+                    self.data.push(format!("    .quad {}", limb));
+                }
+                self.data.push("    .zero 8".to_string());
+                format!("{}+{}", label, OBJ_TAG)
+            },
+            Datum::Bytevector(bytes) => {
+                let label = self.datum_label();
+                self.data.push(format!("    .quad {}", header(BYTEVECTOR_TYPE, bytes.len())));
+                if !bytes.is_empty() {
+                    let bytes: Vec<String> = bytes.iter().map(|byte| byte.to_string()).collect();
+                    self.data.push(format!("    .byte {}", bytes.join(", ")));
+                }
+                self.data.push(format!("    .zero {}", 8 - bytes.len() % 8));
+                format!("{}+{}", label, OBJ_TAG)
+            },
+        }
+    }
+
+    fn datum_label(&mut self) -> String {
+        let label = format!("datum_{}", self.data.len());
+        self.data.push("    .p2align 3".to_string());
+        self.data.push(format!("{}:", label));
+        label
+    }
+
+    // turns the condition of the flags into #t or #f in rax
+    fn gen_bool(&mut self, set: &str) -> io::Result<()> {
+        writeln!(self.dest, "    {} al", set)?;
+        writeln!(self.dest, "    movzb rax, al")?;
+        writeln!(self.dest, "    shl rax, 3")?;
+        writeln!(self.dest, "    or rax, {}", FALSE)?;
+        Ok(())
+    }
+
+    // starts the routine of a primitive procedure. the errors raised in it name the procedure.
+    fn gen_routine(&mut self, label: &str) -> io::Result<()> {
+        writeln!(self.dest, "{}:", label)?;
+        self.routine = label.to_string();
+        Ok(())
+    }
+
+    // jumps to the error routine with the value of the operand unless the flags satisfy `skip`
+    fn gen_error(&mut self, skip: &str, error: &str, value: Option<&str>) -> io::Result<()> {
+        let label = self.if_num;
+        self.if_num += 1;
+        writeln!(self.dest, "    {} .L{}", skip, label)?;
+        if let Some(value) = value {
+            writeln!(self.dest, "    mov rsi, {}", value)?;
+        }
+        writeln!(self.dest, "    lea rdi, [rip+{}_info]", self.routine)?;
+        writeln!(self.dest, "    jmp {}", error)?;
+        writeln!(self.dest, ".L{}:", label)?;
+        Ok(())
+    }
+
+    // raises a type error unless the value in `reg` has the tag
+    fn check_tag(&mut self, reg: &str, tag: i64) -> io::Result<()> {
+        writeln!(self.dest, "    mov r11, {}", reg)?;
+        writeln!(self.dest, "    and r11, {}", TAG_MASK)?;
+        writeln!(self.dest, "    cmp r11, {}", tag)?;
+        self.gen_error("je", "rt_type_error", Some(reg))?;
+        Ok(())
+    }
+
+    fn check_type(&mut self, reg: &str, type_: i64) -> io::Result<()> {
+        self.check_tag(reg, OBJ_TAG)?;
+        writeln!(self.dest, "    mov r11, QWORD PTR [{}-{}]", reg, OBJ_TAG)?;
+        writeln!(self.dest, "    and r11, 255")?;
+        writeln!(self.dest, "    cmp r11, {}", header(type_, 0))?;
+        self.gen_error("je", "rt_type_error", Some(reg))?;
+        Ok(())
+    }
+
+    // raises a type error unless the value in `reg` is a fixnum or a bignum
+    fn check_number(&mut self, reg: &str) -> io::Result<()> {
+        let label = self.if_num;
+        self.if_num += 1;
+        writeln!(self.dest, "    test {}, {}", reg, TAG_MASK)?;
+        writeln!(self.dest, "    jz .L{}", label)?;
+        self.check_type(reg, BIGNUM_TYPE)?;
+        writeln!(self.dest, ".L{}:", label)?;
+        Ok(())
+    }
+
+    fn check_char(&mut self, reg: &str) -> io::Result<()> {
+        writeln!(self.dest, "    mov r11, {}", reg)?;
+        writeln!(self.dest, "    and r11, 255")?;
+        writeln!(self.dest, "    cmp r11, {}", CHAR_TAG)?;
+        self.gen_error("je", "rt_type_error", Some(reg))?;
+        Ok(())
+    }
+
+    // raises an error if the object in `reg` is a constant, which lies outside the heap. the
+    // collector does not scan the constants, so they must not point to objects in the heap.
+    fn check_mutable(&mut self, reg: &str) -> io::Result<()> {
+        writeln!(self.dest, "    mov r11, {}", reg)?;
+        writeln!(self.dest, "    sub r11, QWORD PTR [rip+rt_heap_start]")?;
+        writeln!(self.dest, "    cmp r11, QWORD PTR [rip+rt_heap_size]")?;
+        self.gen_error("jb", "rt_constant_error", Some(reg))?;
+        Ok(())
+    }
+
+    // raises a byte error unless the fixnum in `reg` is a byte
+    fn check_byte(&mut self, reg: &str) -> io::Result<()> {
+        self.check_tag(reg, FIXNUM_TAG)?;
+        writeln!(self.dest, "    cmp {}, {}", reg, 255 << FIXNUM_SHIFT)?;
+        self.gen_error("jbe", "rt_byte_error", Some(reg))?;
+        Ok(())
+    }
+
+    // raises a byte error unless the character in `reg` fits in a byte of a string
+    fn check_string_char(&mut self, reg: &str) -> io::Result<()> {
+        self.check_char(reg)?;
+        writeln!(self.dest, "    cmp {}, {}", reg, (0xff << CHAR_SHIFT) | CHAR_TAG)?;
+        self.gen_error("jbe", "rt_byte_error", Some(reg))?;
+        Ok(())
+    }
+
+    // raises a range error unless the fixnum in `reg` is the length of a new object. a
+    // negative length compares above the limit.
+    fn check_length(&mut self, reg: &str) -> io::Result<()> {
+        self.check_tag(reg, FIXNUM_TAG)?;
+        writeln!(self.dest, "    mov r11, {}", MAX_LENGTH << FIXNUM_SHIFT)?;
+        writeln!(self.dest, "    cmp {}, r11", reg)?;
+        self.gen_error("jbe", "rt_range_error", Some(reg))?;
+        Ok(())
+    }
+
+    // raises a range error with the second argument unless the fixnum in `index` is below the
+    // length of the object in `obj`, and turns the index into an integer
+    fn check_index(&mut self, obj: &str, index: &str) -> io::Result<()> {
+        self.check_tag(index, FIXNUM_TAG)?;
+        writeln!(self.dest, "    sar {}, {}", index, FIXNUM_SHIFT)?;
+        writeln!(self.dest, "    mov rcx, QWORD PTR [{}-{}]", obj, OBJ_TAG)?;
+        writeln!(self.dest, "    shr rcx, 8")?;
+        writeln!(self.dest, "    cmp {}, rcx", index)?;
+        self.gen_error("jb", "rt_range_error", Some("QWORD PTR [rsp+16]"))?;
+        Ok(())
+    }
+
+    // raises an arity error if a routine with optional arguments is given more than `max`
+    fn check_max_args(&mut self, max: usize) -> io::Result<()> {
+        let label = self.if_num;
+        self.if_num += 1;
+        writeln!(self.dest, "    cmp rcx, {}", max)?;
+        writeln!(self.dest, "    jbe .L{}", label)?;
+        writeln!(self.dest, "    lea rdx, [rip+{}_max_info]", self.routine)?;
+        writeln!(self.dest, "    jmp rt_arity_error")?;
+        writeln!(self.dest, ".L{}:", label)?;
+        Ok(())
+    }
+
+    // returns whether the argument is a heap object of the type
+    fn gen_type_predicate(&mut self, name: &str, type_: i64) -> io::Result<()> {
+        self.gen_routine(name)?;
+        writeln!(self.dest, "    mov rdi, QWORD PTR [rsp+8]")?;
+        writeln!(self.dest, "    mov rax, {}", FALSE)?;
+        writeln!(self.dest, "    mov r11, rdi")?;
+        writeln!(self.dest, "    and r11, {}", TAG_MASK)?;
+        writeln!(self.dest, "    cmp r11, {}", OBJ_TAG)?;
+        writeln!(self.dest, "    jne {}_done", name)?;
+        writeln!(self.dest, "    mov r11, QWORD PTR [rdi-{}]", OBJ_TAG)?;
+        writeln!(self.dest, "    and r11, 255")?;
+        writeln!(self.dest, "    cmp r11, {}", header(type_, 0))?;
+        writeln!(self.dest, "    jne {}_done", name)?;
+        writeln!(self.dest, "    mov rax, {}", TRUE)?;
+        writeln!(self.dest, "{}_done:", name)?;
+        writeln!(self.dest, "    ret 8")?;
+        Ok(())
+    }
+
+    // compares each of the numbers or characters with the next with `compare`, which sets the
+    // flags as `cmp rdi, rsi` would for them, and returns whether `set` holds for every pair.
+    // every argument is checked. rcx is the number of the arguments and dl the result so far.
+    fn gen_compare(&mut self, name: &str, set: &str, compare: &str, check: fn(&mut CodeGen, &str) -> io::Result<()>) -> io::Result<()> {
+        self.gen_routine(name)?;
+        writeln!(self.dest, "    lea r9, [rsp+8]")?;
+        writeln!(self.dest, "    lea r8, [rcx-1]")?;
+        writeln!(self.dest, "    mov rsi, QWORD PTR [r9]")?;
+        check(self, "rsi")?;
+        writeln!(self.dest, "    mov edx, 1")?;
+        writeln!(self.dest, "{}_loop:", name)?;
+        writeln!(self.dest, "    test r8, r8")?;
+        writeln!(self.dest, "    jz {}_done", name)?;
+        writeln!(self.dest, "    mov rdi, QWORD PTR [r9]")?;
+        writeln!(self.dest, "    mov rsi, QWORD PTR [r9+8]")?;
+        check(self, "rsi")?;
+        writeln!(self.dest, "    {}", compare)?;
+        writeln!(self.dest, "    {} al", set)?;
+        writeln!(self.dest, "    and dl, al")?;
+        writeln!(self.dest, "    add r9, 8")?;
+        writeln!(self.dest, "    dec r8")?;
+        writeln!(self.dest, "    jmp {}_loop", name)?;
+        writeln!(self.dest, "{}_done:", name)?;
+        writeln!(self.dest, "    test dl, dl")?;
+        self.gen_bool("setnz")?;
+        writeln!(self.dest, "    pop rdx")?;
+        writeln!(self.dest, "    lea rsp, [rsp+8*rcx]")?;
+        writeln!(self.dest, "    jmp rdx")?;
+        Ok(())
+    }
+
+    // checks that rax is a procedure taking the number of the arguments, which is put in rcx
+    // if given and is already there otherwise
+    fn gen_check_call(&mut self, args_num: Option<usize>) -> io::Result<()> {
+        let label = self.if_num;
+        self.if_num += 1;
+        writeln!(self.dest, "    mov r11, rax")?;
+        writeln!(self.dest, "    and r11, {}", TAG_MASK)?;
+        writeln!(self.dest, "    cmp r11, {}", PROC_TAG)?;
+        writeln!(self.dest, "    je .L{}", label)?;
+        writeln!(self.dest, "    mov rsi, rax")?;
+        writeln!(self.dest, "    jmp rt_call_error")?;
+        writeln!(self.dest, ".L{}:", label)?;
+
+        let label = self.if_num;
+        self.if_num += 1;
+        writeln!(self.dest, "    mov rdx, QWORD PTR [rax+{}]", 16 - PROC_TAG)?;
+        match args_num {
+            Some(args_num) => {
+                writeln!(self.dest, "    mov ecx, {}", args_num)?;
+                writeln!(self.dest, "    cmp QWORD PTR [rdx], {}", (args_num as i64) << FIXNUM_SHIFT)?;
+            },
+            None => {
+                writeln!(self.dest, "    lea r11, [8*rcx]")?;
+                writeln!(self.dest, "    cmp QWORD PTR [rdx], r11")?;
+            },
+        }
+        writeln!(self.dest, "    je .L{}", label)?;
+        writeln!(self.dest, "    call rt_check_arity")?;
+        writeln!(self.dest, ".L{}:", label)?;
+        Ok(())
+    }
+
+    // adds the information of a procedure to the static data: its arity, which is the number
+    // of the arguments or -(n + 1) if it takes n arguments and the rest, and its name
+    fn gen_proc_info(&mut self, label: &str, name: &str, args_num: usize, rest: bool) -> io::Result<()> {
+        let arity = if rest { -(args_num as i64) } else { args_num as i64 };
+        self.strings.push(name.to_string());
+        self.data.push("    .p2align 3".to_string());
+        self.data.push(format!("{}_info:", label));
+        self.data.push(format!("    .quad {}", arity << FIXNUM_SHIFT));
+        self.data.push(format!("    .quad string_{}+{}", self.strings.len() - 1, OBJ_TAG));
+        Ok(())
+    }
+
+    // a procedure is called with its closure in rax, the number of the arguments in rcx
+    // and the arguments on the stack. the closure is laid out as [header, code, info, free variables...]
+    fn gen_proc(&mut self, proc: Lambda) -> io::Result<()> {
         let id = self.lambda_num;
         self.lambda_num += 1;
-        writeln!(self.dest, "_{}:", id).unwrap();
-        writeln!(self.dest, "    push rbp").unwrap();
-        writeln!(self.dest, "    mov rbp, rsp").unwrap();
-        writeln!(self.dest, "    sub rsp, {}", 8 * (proc.free_num + proc.local_num)).unwrap();
+        let label = format!("_{}", id);
+        let name = proc.name.as_deref().unwrap_or("#<procedure>");
+        self.gen_proc_info(&label, name, proc.args_num, proc.rest)?;
+        writeln!(self.dest, "{}:", label)?;
+        if proc.rest {
+            writeln!(self.dest, "    mov edx, {}", proc.args_num - 1)?;
+            writeln!(self.dest, "    call rt_rest_args")?;
+        }
+        writeln!(self.dest, "    push rbp")?;
+        writeln!(self.dest, "    mov rbp, rsp")?;
+        writeln!(self.dest, "    sub rsp, {}", 8 * (proc.free_num + proc.local_num))?;
+        self.args_num = proc.args_num;
 
         for i in 1..proc.free_num + 1 {
-            writeln!(self.dest, "    mov rax, QWORD PTR [rbp+{}]", 8 * (i + 1)).unwrap();
-            writeln!(self.dest, "    mov QWORD PTR [rbp-{}], rax", 8 * i).unwrap();
+            writeln!(self.dest, "    mov rdi, QWORD PTR [rax+{}]", 8 * (i + 2) - PROC_TAG as usize)?;
+            writeln!(self.dest, "    mov QWORD PTR [rbp-{}], rdi", 8 * i)?;
         }
         for i in 1..proc.args_num + 1 {
-            writeln!(self.dest, "    mov rax, QWORD PTR [rbp+{}]", 8 * (proc.free_num + i + 1)).unwrap();
-            writeln!(self.dest, "    mov QWORD PTR [rbp-{}], rax", 8 * (proc.free_num + i)).unwrap();
+            writeln!(self.dest, "    mov rdi, QWORD PTR [rbp+{}]", 8 * (i + 1))?;
+            writeln!(self.dest, "    mov QWORD PTR [rbp-{}], rdi", 8 * (proc.free_num + i))?;
+        }
+        // the collector must not see stale words in the slots of the locals
+        for i in proc.args_num + 1..proc.local_num + 1 {
+            writeln!(self.dest, "    mov QWORD PTR [rbp-{}], {}", 8 * (proc.free_num + i), VOID)?;
+        }
+        // variables both captured by inner procedures and assigned live in boxes shared with the closures
+        for var in proc.locals.iter() {
+            if let Var::Free(_, offset, true) = *var.borrow() {
+                writeln!(self.dest, "    mov rdi, 16")?;
+                writeln!(self.dest, "    call rt_alloc")?;
+                writeln!(self.dest, "    mov QWORD PTR [rax], {}", header(BOX_TYPE, 1))?;
+                writeln!(self.dest, "    mov rdi, QWORD PTR [rbp-{}]", 8 * proc.free_num + offset)?;
+                writeln!(self.dest, "    mov QWORD PTR [rax+8], rdi")?;
+                writeln!(self.dest, "    or rax, {}", OBJ_TAG)?;
+                writeln!(self.dest, "    mov QWORD PTR [rbp-{}], rax", 8 * proc.free_num + offset)?;
+            }
         }
 
         for expr in proc.body {
             match expr {
-                Node::Defn(defn) => self.gen_defn(defn, &proc.free_vars),
-                Node::Expr(expr) => self.gen_expr(expr, &proc.free_vars),
+                Node::Defn(defn) => self.gen_defn(defn, &proc.free_vars)?,
+                Node::Expr(expr) => self.gen_expr(expr, &proc.free_vars)?,
             }
         }
 
-        writeln!(self.dest, "    pop rax").unwrap();
-        writeln!(self.dest, "    mov rsp, rbp").unwrap();
-        writeln!(self.dest, "    pop rbp").unwrap();
-        writeln!(self.dest, "    ret").unwrap();
+        writeln!(self.dest, "    pop rax")?;
+        writeln!(self.dest, "    mov rsp, rbp")?;
+        writeln!(self.dest, "    pop rbp")?;
+        writeln!(self.dest, "    ret {}", 8 * self.args_num)?;
+        Ok(())
     }
 
-    fn gen_node(&mut self, node: Node) {
+    fn gen_node(&mut self, node: Node) -> io::Result<()> {
         match node {
             Node::Defn(defn) => self.gen_defn(defn, &FVs::new()),
             Node::Expr(expr) => self.gen_expr(expr, &FVs::new()),
         }
     }
 
-    fn gen_defn(&mut self, defn: Defn, free_vars: &FVs) {
+    fn gen_defn(&mut self, defn: Defn, free_vars: &FVs) -> io::Result<()> {
+        self.gen_expr(defn.expr, free_vars)?;
+        self.gen_store(&defn.var, free_vars)?;
+        Ok(())
+    }
+
+    // pops a value and stores it to the variable
+    fn gen_store(&mut self, var: &Rc<RefCell<Var>>, free_vars: &FVs) -> io::Result<()> {
         let fv_num = free_vars.len();
-        self.gen_expr(defn.expr, free_vars);
-        
-        match *defn.var.borrow() {
+        match *var.borrow() {
             Var::Global(ref name) => {
-                writeln!(self.dest, "    pop rax").unwrap();
-                writeln!(self.dest, "    mov [rip+{}], rax", name.clone()).unwrap();
+                writeln!(self.dest, "    pop rax")?;
+                writeln!(self.dest, "    mov [rip+{}], rax", mangle(name))?;
             },
-            Var::Free(_, offset) => {
-                writeln!(self.dest, "    mov rdi, 1").unwrap();
-                writeln!(self.dest, "    mov rsi, 8").unwrap();
-                writeln!(self.dest, "    call calloc").unwrap();
-                writeln!(self.dest, "    pop rdi").unwrap();
-                writeln!(self.dest, "    mov QWORD PTR [rax], rdi").unwrap();
-                writeln!(self.dest, "    mov QWORD PTR [rbp-{}], rax", 8 * fv_num + offset).unwrap();
+            Var::Free(ref name, offset, _) => {
+                writeln!(self.dest, "    pop rdi")?;
+                if free_vars.include(name.clone()) {
+                    writeln!(self.dest, "    mov rax, QWORD PTR [rbp-{}]", free_vars.offset(name.clone()))?;
+                } else {
+                    writeln!(self.dest, "    mov rax, QWORD PTR [rbp-{}]", 8 * fv_num + offset)?;
+                }
+                writeln!(self.dest, "    mov QWORD PTR [rax+{}], rdi", 8 - OBJ_TAG)?;
             },
-            Var::Local(_, offset) => {
-                writeln!(self.dest, "    pop rax").unwrap();
-                writeln!(self.dest, "    mov QWORD PTR [rbp-{}], rax", 8 * fv_num + offset).unwrap();
+            Var::Local(_, offset, _) => {
+                writeln!(self.dest, "    pop rax")?;
+                writeln!(self.dest, "    mov QWORD PTR [rbp-{}], rax", 8 * fv_num + offset)?;
             },
         }
+        Ok(())
     }
 
-    fn gen_expr(&mut self, expr: Expr, free_vars: &FVs) {
+    fn gen_expr(&mut self, expr: Expr, free_vars: &FVs) -> io::Result<()> {
         match expr {
             Expr::Bool(val) => {
-                writeln!(self.dest, "    push {}", if val { 1 } else { 0 });
+                writeln!(self.dest, "    push {}", if val { TRUE } else { FALSE })?;
+            },
+            Expr::Void => {
+                writeln!(self.dest, "    push {}", VOID)?;
             },
             Expr::Int(val) => {
-                writeln!(self.dest, "    push {}", val).unwrap();
+                writeln!(self.dest, "    mov rax, {}", val << FIXNUM_SHIFT)?;
+                writeln!(self.dest, "    push rax")?;
+            },
+            Expr::Char(c) => {
+                writeln!(self.dest, "    mov rax, {}", ((c as i64) << CHAR_SHIFT) | CHAR_TAG)?;
+                writeln!(self.dest, "    push rax")?;
+            },
+            Expr::Quote(datum) => {
+                let value = self.gen_datum(&datum);
+                match datum {
+                    Datum::BigInt(_, _) | Datum::Str(_) | Datum::Symbol(_) | Datum::Pair(_, _) | Datum::Vector(_) |
+                    Datum::Bytevector(_) => {
+                        writeln!(self.dest, "    lea rax, [rip+{}]", value)?;
+                    },
+                    _ => {
+                        writeln!(self.dest, "    mov rax, {}", value)?;
+                    },
+                }
+                writeln!(self.dest, "    push rax")?;
+            },
+            Expr::Str(string) => {
+                writeln!(self.dest, "    lea rax, [rip+string_{}+{}]", self.strings.len(), OBJ_TAG)?;
+                writeln!(self.dest, "    push rax")?;
+                self.strings.push(string);
             },
             Expr::Proc(name, fvs) => {
-                writeln!(self.dest, "    mov rdi, 2").unwrap();
-                writeln!(self.dest, "    mov rsi, 8").unwrap();
-                writeln!(self.dest, "    call calloc").unwrap();
-
-                writeln!(self.dest, "    lea rdi, [rip+{}]", name).unwrap();
-                writeln!(self.dest, "    mov [rax], rdi").unwrap();
-                writeln!(self.dest, "    mov QWORD PTR [rax+8], 0").unwrap();
+                writeln!(self.dest, "    mov rdi, {}", 8 * (fvs.len() + 3))?;
+                writeln!(self.dest, "    call rt_alloc")?;
 
-                for (offset, name) in fvs.iter() {
-                    writeln!(self.dest, "    push rax").unwrap();
-
-                    writeln!(self.dest, "    mov rdi, 2").unwrap();
-                    writeln!(self.dest, "    mov rsi, 8").unwrap();
-                    writeln!(self.dest, "    call calloc").unwrap();
+                writeln!(self.dest, "    mov QWORD PTR [rax], {}", header(CLOSURE_TYPE, fvs.len() + 2))?;
+                writeln!(self.dest, "    lea rdi, [rip+{}]", name)?;
+                writeln!(self.dest, "    mov [rax+8], rdi")?;
+                writeln!(self.dest, "    lea rdi, [rip+{}_info]", name)?;
+                writeln!(self.dest, "    mov [rax+16], rdi")?;
 
+                for (i, (offset, name)) in fvs.iter().enumerate() {
                     if free_vars.include(name.clone()) {
-                        writeln!(self.dest, "    mov rdi, [rbp-{}]", free_vars.offset(name.clone())).unwrap();
+                        writeln!(self.dest, "    mov rdi, [rbp-{}]", free_vars.offset(name.clone()))?;
                     } else {
                         let fv_num = free_vars.len();
-                        writeln!(self.dest, "    mov rdi, [rbp-{}]", fv_num + offset).unwrap();
+                        writeln!(self.dest, "    mov rdi, [rbp-{}]", 8 * fv_num + offset)?;
                     }
-
-                    // writeln!(self.dest, "    mov rdi, [rbp-{}]", /*offset*/  free_vars.offset(name.clone())).unwrap();
-                    // writeln!(self.dest, "    mov rdi, [rdi]").unwrap();
-                    writeln!(self.dest, "    pop rsi").unwrap();
-                    writeln!(self.dest, "    mov [rax], rdi",).unwrap();
-                    writeln!(self.dest, "    mov [rax+8], rsi").unwrap();
+                    writeln!(self.dest, "    mov [rax+{}], rdi", 8 * (i + 3))?;
                 }
 
-                writeln!(self.dest, "    push rax").unwrap();
+                writeln!(self.dest, "    or rax, {}", PROC_TAG)?;
+                writeln!(self.dest, "    push rax")?;
             },
             Expr::Var(var) => {
                 match *var.borrow() {
                     Var::Global(ref name) => {
-                        writeln!(self.dest, "    mov rax, [rip+{}]", name.clone()).unwrap();
-                        writeln!(self.dest, "    push rax").unwrap();
+                        writeln!(self.dest, "    mov rax, [rip+{}]", mangle(name))?;
+                        writeln!(self.dest, "    push rax")?;
                     },
-                    Var::Free(ref name, _) => {
-                        writeln!(self.dest, "    push QWORD PTR [rbp-{}]", free_vars.offset(name.clone())).unwrap();
-                        // writeln!(self.dest, "    mov rax, QWORD PTR [rbp-{}]", free_vars.offset(name.clone())).unwrap();
-                        // writeln!(self.dest, "    push [rax]").unwrap();
+                    Var::Free(ref name, offset, assigned) => {
+                        if free_vars.include(name.clone()) {
+                            writeln!(self.dest, "    mov rax, QWORD PTR [rbp-{}]", free_vars.offset(name.clone()))?;
+                        } else {
+                            let fv_num = free_vars.len();
+                            writeln!(self.dest, "    mov rax, QWORD PTR [rbp-{}]", 8 * fv_num + offset)?;
+                        }
+                        if assigned {
+                            writeln!(self.dest, "    mov rax, QWORD PTR [rax+{}]", 8 - OBJ_TAG)?;
+                        }
+                        writeln!(self.dest, "    push rax")?;
                     },
-                    Var::Local(_, offset) => {
+                    Var::Local(_, offset, _) => {
                         let fv_num = free_vars.len();
-                        writeln!(self.dest, "    push QWORD PTR [rbp-{}]", 8 * fv_num + offset).unwrap();
+                        writeln!(self.dest, "    push QWORD PTR [rbp-{}]", 8 * fv_num + offset)?;
                     },
                 }
             },
-            Expr::Call { proc, params } => {
+            Expr::Call { proc, params, tail } => {
                 let params_num = params.len();
                 for param in params.into_iter().rev() {
-                    self.gen_expr(param, free_vars);
+                    self.gen_expr(param, free_vars)?;
                 }
 
-                self.gen_expr((*proc).clone(), free_vars);
-                writeln!(self.dest, "    pop rax").unwrap();
+                self.gen_expr((*proc).clone(), free_vars)?;
+                writeln!(self.dest, "    pop rax")?;
+                // the arity is checked before the frame is reused
+                self.gen_check_call(Some(params_num))?;
 
-                let call_num = self.call_num;
-                self.call_num += 1;
-
-                writeln!(self.dest, "begin{}:", call_num).unwrap();
-                writeln!(self.dest, "    cmp rax, 0").unwrap();
-                writeln!(self.dest, "    je end{}", call_num).unwrap();
-
-                writeln!(self.dest, "    push [rax]").unwrap(); //
-                writeln!(self.dest, "    mov rax, [rax+8]").unwrap();
-                writeln!(self.dest, "    jmp begin{}", call_num).unwrap();
-
-                writeln!(self.dest, "end{}:", call_num).unwrap();
-                
-                writeln!(self.dest, "    pop rax").unwrap();
-                writeln!(self.dest, "    call rax").unwrap();
-                
-                writeln!(self.dest, "    add rsp, {}", 8 * params_num).unwrap();
-                writeln!(self.dest, "    push rax").unwrap();
+                if tail {
+                    // overwrite the arguments of the current frame with the new ones,
+                    // then jump to the callee with the caller's return address
+                    let base = 16 + 8 * (self.args_num as i64 - params_num as i64);
+                    writeln!(self.dest, "    mov r8, [rbp+8]")?;
+                    writeln!(self.dest, "    mov r9, [rbp]")?;
+                    for i in (0..params_num as i64).rev() {
+                        writeln!(self.dest, "    mov rdx, [rsp+{}]", 8 * i)?;
+                        writeln!(self.dest, "    mov [rbp{:+}], rdx", base + 8 * i)?;
+                    }
+                    writeln!(self.dest, "    lea rsp, [rbp{:+}]", base)?;
+                    writeln!(self.dest, "    push r8")?;
+                    writeln!(self.dest, "    mov rbp, r9")?;
+                    writeln!(self.dest, "    jmp QWORD PTR [rax+{}]", 8 - PROC_TAG)?;
+                } else {
+                    // the callee pops its arguments
+                    writeln!(self.dest, "    call QWORD PTR [rax+{}]", 8 - PROC_TAG)?;
+                    writeln!(self.dest, "    push rax")?;
+                }
             },
             Expr::If { test, conseq, alter } => {
                 let label1 = self.if_num;
                 let label2 = self.if_num + 1;
                 self.if_num += 2;
 
-                self.gen_expr((*test).clone(), free_vars);
+                self.gen_expr((*test).clone(), free_vars)?;
 
-                writeln!(self.dest, "    pop rax").unwrap();
-                writeln!(self.dest, "    cmp rax, 0").unwrap();
-                writeln!(self.dest, "    je .L{}", label1).unwrap();
+                writeln!(self.dest, "    pop rax")?;
+                writeln!(self.dest, "    cmp rax, {}", FALSE)?;
+                writeln!(self.dest, "    je .L{}", label1)?;
 
-                self.gen_expr((*conseq).clone(), free_vars);
-                writeln!(self.dest, "    jmp .L{}", label2).unwrap();
+                self.gen_expr((*conseq).clone(), free_vars)?;
+                writeln!(self.dest, "    jmp .L{}", label2)?;
 
-                writeln!(self.dest, ".L{}:", label1).unwrap();
+                writeln!(self.dest, ".L{}:", label1)?;
 
-                self.gen_expr((*alter).clone(), free_vars);
+                self.gen_expr((*alter).clone(), free_vars)?;
 
-                writeln!(self.dest, ".L{}:", label2).unwrap();
+                writeln!(self.dest, ".L{}:", label2)?;
+            },
+            Expr::Set { var, expr } => {
+                self.gen_expr((*expr).clone(), free_vars)?;
+                self.gen_store(&var, free_vars)?;
+                writeln!(self.dest, "    push {}", VOID)?;
+            },
+            Expr::Or(exprs) => {
+                let label = self.if_num;
+                self.if_num += 1;
+
+                let last = exprs.len() - 1;
+                for (i, expr) in exprs.into_iter().enumerate() {
+                    self.gen_expr(expr, free_vars)?;
+                    if i < last {
+                        writeln!(self.dest, "    cmp QWORD PTR [rsp], {}", FALSE)?;
+                        writeln!(self.dest, "    jne .L{}", label)?;
+                        writeln!(self.dest, "    add rsp, 8")?;
+                    }
+                }
+
+                writeln!(self.dest, ".L{}:", label)?;
+            },
+            Expr::Begin(exprs) => {
+                let last = exprs.len() - 1;
+                for (i, expr) in exprs.into_iter().enumerate() {
+                    self.gen_expr(expr, free_vars)?;
+                    if i < last {
+                        writeln!(self.dest, "    add rsp, 8")?;
+                    }
+                }
             },
         }
+        Ok(())
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,34 @@
+// a range of characters on a line of the source code. line and col start from 1.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Span {
+    pub line: usize,
+    pub col: usize,
+    pub len: usize,
+}
+
+#[derive(Debug)]
+pub struct CompileError {
+    pub msg: String,
+    pub span: Option<Span>,
+}
+
+impl CompileError {
+    pub fn new(msg: String, span: Option<Span>) -> Self {
+        CompileError { msg, span }
+    }
+
+    // renders the error as file:line:col followed by the source line with the span underlined
+    pub fn render(&self, file_name: &str, src: &str) -> String {
+        let mut text = format!("error: {}\n", self.msg);
+        if let Some(span) = self.span {
+            let line = src.lines().nth(span.line - 1).unwrap_or("");
+            let indent: String = line.chars().take(span.col - 1).map(|c| if c == '\t' { '\t' } else { ' ' }).collect();
+            let width = span.line.to_string().len();
+            text += &format!("{:width$}--> {}:{}:{}\n", "", file_name, span.line, span.col, width = width);
+            text += &format!("{:width$} |\n", "", width = width);
+            text += &format!("{} | {}\n", span.line, line);
+            text += &format!("{:width$} | {}{}\n", "", indent, "^".repeat(span.len.max(1)), width = width);
+        }
+        text
+    }
+}
@@ -1,16 +1,52 @@
-use Token::*;
+use std::iter::Peekable;
+use std::str::Chars;
+
+use crate::error::{CompileError, Span};
+use TokenKind::*;
 
 #[derive(Debug, PartialEq)]
-pub enum Token {
+pub enum TokenKind {
     Ident(String),
     Bool(bool),
-    Number(i32),
+    Number(i64),
+    BigNumber(bool, Vec<u64>),
+    Char(char),
+    Str(String),
     OpenPar,
+    VecOpen,
+    BytevecOpen,
     ClosePar,
+    Quote,
+    Quasiquote,
+    Unquote,
+    UnquoteSplicing,
     Period,
+    Eof,
+}
+
+#[derive(Debug)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub span: Span,
 }
 
-const OPERATORS: [char; 9] = ['=', '!', '<', '>', '+', '-', '*', '/', '_'];
+// the names of characters in #\name
+pub const CHAR_NAMES: [(&str, char); 9] = [
+    ("alarm", '\x07'),
+    ("backspace", '\x08'),
+    ("delete", '\x7f'),
+    ("escape", '\x1b'),
+    ("newline", '\n'),
+    ("null", '\0'),
+    ("return", '\r'),
+    ("space", ' '),
+    ("tab", '\t'),
+];
+
+// the largest magnitude of a positive fixnum, which has 61 bits
+const FIXNUM_MAX: u64 = (1 << 60) - 1;
+
+const OPERATORS: [char; 16] = ['=', '!', '<', '>', '+', '-', '*', '/', '_', '?', '$', '%', '&', ':', '^', '~'];
 
 fn is_operator(c: char) -> bool {
     for operator in OPERATORS {
@@ -21,53 +57,376 @@ fn is_operator(c: char) -> bool {
     false
 }
 
-pub fn tokenize(src: &str) -> Vec<Token> {
+// whether a word can begin with the character. the words beginning with a digit are numbers.
+pub fn is_initial(c: char) -> bool {
+    c.is_ascii_alphanumeric() || is_operator(c) || c == '.'
+}
+
+// whether the character can be in a word after its first one
+pub fn is_subsequent(c: char) -> bool {
+    is_initial(c) || c == '@'
+}
+
+// reads the characters keeping track of the line and the column
+struct Cursor<'a> {
+    chars: Peekable<Chars<'a>>,
+    line: usize,
+    col: usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn new(src: &'a str) -> Self {
+        Cursor { chars: src.chars().peekable(), line: 1, col: 1 }
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.chars.peek().copied()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.chars.next()?;
+        if c == '\n' {
+            self.line += 1;
+            self.col = 1;
+        } else {
+            self.col += 1;
+        }
+        Some(c)
+    }
+
+    // the span from the given position to the current one
+    fn span(&self, line: usize, col: usize) -> Span {
+        let len = if self.line == line { self.col - col } else { 1 };
+        Span { line, col, len }
+    }
+}
+
+pub fn tokenize(src: &str) -> Result<Vec<Token>, CompileError> {
     let mut token_list: Vec<Token> = Vec::new();
-    let mut src_iter = src.chars();
-    while let Some(mut c) = src_iter.next() {
+    let mut cursor = Cursor::new(src);
+    // the parenthesis depth, and the token index and depth of each pending '#;'
+    let mut depth = 0;
+    let mut datum_comments: Vec<(usize, usize, Span)> = Vec::new();
+    while let Some(c) = cursor.peek() {
+        let (line, col) = (cursor.line, cursor.col);
+
         if c.is_whitespace() {
+            cursor.next();
             continue;
         }
 
-        if c.is_ascii_alphabetic() || is_operator(c) {
-            let mut ident = c.to_string();
-            while let Some(d) = src_iter.next() {
-                if d.is_ascii_alphanumeric() || is_operator(d) {
-                    ident.push(d);
-                } else {
-                    c = d;
+        if c == ';' {
+            while let Some(d) = cursor.next() {
+                if d == '\n' {
                     break;
                 }
             }
-            token_list.push(Ident(ident));
+            continue;
+        }
+
+        let kind = if c == '#' {
+            cursor.next();
+            match cursor.peek() {
+                Some('|') => {
+                    cursor.next();
+                    skip_block_comment(&mut cursor, line, col)?;
+                    continue;
+                },
+                Some(';') => {
+                    cursor.next();
+                    datum_comments.push((token_list.len(), depth, cursor.span(line, col)));
+                    continue;
+                },
+                Some('(') => {
+                    cursor.next();
+                    VecOpen
+                },
+                Some('\\') => {
+                    cursor.next();
+                    read_char(&mut cursor, line, col)?
+                },
+                _ => {
+                    let word = read_word(&mut cursor);
+                    if word == "u8" && cursor.peek() == Some('(') {
+                        cursor.next();
+                        BytevecOpen
+                    } else {
+                        read_hash_word(&word, cursor.span(line, col))?
+                    }
+                },
+            }
+        } else if is_initial(c) {
+            // a word is a number if it starts with a digit or with a sign followed by a digit.
+            // a single '.' is the dot of a pair.
+            let word = read_word(&mut cursor);
+            let digits = word.strip_prefix(['+', '-']).unwrap_or(&word);
+            if word == "." {
+                Period
+            } else if digits.starts_with(|d: char| d.is_ascii_digit()) {
+                read_number(&word, 10, &word, cursor.span(line, col))?
+            } else {
+                Ident(word)
+            }
+        } else if c == '"' {
+            cursor.next();
+            read_string(&mut cursor, line, col)?
+        } else if c == '|' {
+            // an identifier between bars may hold any character
+            cursor.next();
+            Ident(read_delimited(&mut cursor, '|', "identifier", line, col)?)
+        } else if c == '(' {
+            cursor.next();
+            OpenPar
+        } else if c == ')' {
+            cursor.next();
+            ClosePar
+        } else if c == '\'' {
+            cursor.next();
+            Quote
+        } else if c == '`' {
+            cursor.next();
+            Quasiquote
+        } else if c == ',' {
+            cursor.next();
+            if cursor.peek() == Some('@') {
+                cursor.next();
+                UnquoteSplicing
+            } else {
+                Unquote
+            }
+        } else {
+            cursor.next();
+            return Err(CompileError::new(format!("unexpected character '{}'", c), Some(cursor.span(line, col))));
+        };
+
+        match kind {
+            OpenPar | VecOpen | BytevecOpen => depth += 1,
+            ClosePar => {
+                if let Some(&(_, comment_depth, span)) = datum_comments.last() {
+                    if comment_depth == depth {
+                        return Err(CompileError::new("expected datum after '#;'".to_string(), Some(span)));
+                    }
+                }
+                depth = depth.saturating_sub(1);
+            },
+            _ => {},
         }
+        let completes_datum = !matches!(kind, OpenPar | VecOpen | BytevecOpen | Quote | Quasiquote | Unquote | UnquoteSplicing);
+        token_list.push(Token { kind, span: cursor.span(line, col) });
 
-        if c.is_numeric() {
-            let mut number = c.to_digit(10).unwrap();
-            while let Some(d) = src_iter.next() {
-                if d.is_numeric() {
-                    number = 10 * number + d.to_digit(10).unwrap();
-                } else {
-                    c = d;
+        // drop the datum that has just been completed if it is commented out
+        if completes_datum {
+            while let Some(&(start, comment_depth, _)) = datum_comments.last() {
+                if comment_depth != depth || token_list.len() == start {
                     break;
                 }
+                token_list.truncate(start);
+                datum_comments.pop();
             }
-            token_list.push(Number(number as i32));
         }
-        
-        if c == '(' {
-            token_list.push(OpenPar);
-            continue;
+    }
+    if let Some(&(_, _, span)) = datum_comments.last() {
+        return Err(CompileError::new("expected datum after '#;'".to_string(), Some(span)));
+    }
+    token_list.push(Token { kind: Eof, span: Span { line: cursor.line, col: cursor.col, len: 1 } });
+    Ok(token_list)
+}
+
+// reads the characters which can make up an identifier. '.' and '@' may follow its first one.
+fn read_word(cursor: &mut Cursor) -> String {
+    let mut word = String::new();
+    while let Some(d) = cursor.peek() {
+        if is_subsequent(d) {
+            word.push(d);
+            cursor.next();
+        } else {
+            break;
         }
-        if c == ')' {
-            token_list.push(ClosePar);
-            continue;
+    }
+    word
+}
+
+// reads what follows '#': a boolean or a number with a radix prefix
+fn read_hash_word(word: &str, span: Span) -> Result<TokenKind, CompileError> {
+    match word {
+        "t" | "true" => return Ok(Bool(true)),
+        "f" | "false" => return Ok(Bool(false)),
+        _ => {},
+    }
+    let radix = match word.chars().next() {
+        Some('x') | Some('X') => 16,
+        Some('b') | Some('B') => 2,
+        Some('o') | Some('O') => 8,
+        Some('d') | Some('D') => 10,
+        _ => {
+            return Err(CompileError::new(format!("unknown syntax '#{}'", word), Some(span)));
+        },
+    };
+    read_number(&word[1..], radix, &format!("#{}", word), span)
+}
+
+// reads the digits in the radix with an optional sign, which make up the word. the magnitude
+// is built in 64-bit limbs from the lowest, and a number which does not fit in a fixnum is a
+// bignum.
+fn read_number(digits: &str, radix: u32, word: &str, span: Span) -> Result<TokenKind, CompileError> {
+    let (negative, digits) = match digits.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, digits.strip_prefix('+').unwrap_or(digits)),
+    };
+    if digits.is_empty() {
+        return Err(CompileError::new(format!("invalid number '{}'", word), Some(span)));
+    }
+    let mut limbs: Vec<u64> = Vec::new();
+    for c in digits.chars() {
+        let mut carry = match c.to_digit(radix) {
+            Some(digit) => digit as u128,
+            None => return Err(CompileError::new(format!("invalid number '{}'", word), Some(span))),
+        };
+        for limb in limbs.iter_mut() {
+            let value = *limb as u128 * radix as u128 + carry;
+            *limb = value as u64;
+            carry = value >> 64;
         }
-        if c == '.' {
-            token_list.push(Period);
-            continue;
+        if carry != 0 {
+            limbs.push(carry as u64);
+        }
+    }
+    match limbs[..] {
+        [] => Ok(Number(0)),
+        [limb] if limb <= FIXNUM_MAX + negative as u64 => {
+            Ok(Number(if negative { -(limb as i64) } else { limb as i64 }))
+        },
+        _ => Ok(BigNumber(negative, limbs)),
+    }
+}
+
+// reads a character literal after '#\\', either a single character or a character name
+fn read_char(cursor: &mut Cursor, line: usize, col: usize) -> Result<TokenKind, CompileError> {
+    let c = match cursor.next() {
+        Some(c) => c,
+        None => {
+            return Err(CompileError::new("expected character after '#\\'".to_string(), Some(cursor.span(line, col))));
+        },
+    };
+    let mut name = c.to_string();
+    if c.is_ascii_alphabetic() {
+        while let Some(d) = cursor.peek() {
+            if d.is_ascii_alphanumeric() || d == '-' {
+                name.push(d);
+                cursor.next();
+            } else {
+                break;
+            }
         }
     }
-    println!("{:?}", token_list);
-    token_list
-}
\ No newline at end of file
+    if name.chars().count() == 1 {
+        return Ok(Char(c));
+    }
+    let named = match CHAR_NAMES.iter().find(|(char_name, _)| *char_name == name) {
+        Some(&(_, c)) => Some(c),
+        None if c == 'x' || c == 'X' => u32::from_str_radix(&name[1..], 16).ok().and_then(char::from_u32),
+        None => None,
+    };
+    match named {
+        Some(c) => Ok(Char(c)),
+        None => Err(CompileError::new(format!("unknown character name '#\\{}'", name), Some(cursor.span(line, col)))),
+    }
+}
+
+// reads a string literal after its opening '"'
+fn read_string(cursor: &mut Cursor, line: usize, col: usize) -> Result<TokenKind, CompileError> {
+    Ok(Str(read_delimited(cursor, '"', "string", line, col)?))
+}
+
+// reads the characters up to the closing delimiter with the escapes of strings, in which the
+// delimiter is escaped by a backslash. the `what` names the token when it is not terminated.
+fn read_delimited(cursor: &mut Cursor, delim: char, what: &str, line: usize, col: usize) -> Result<String, CompileError> {
+    let mut string = String::new();
+    loop {
+        let (esc_line, esc_col) = (cursor.line, cursor.col);
+        match cursor.next() {
+            Some(c) if c == delim => break,
+            Some('\\') => {
+                let c = match cursor.next() {
+                    Some('n') => '\n',
+                    Some('t') => '\t',
+                    Some('r') => '\r',
+                    Some('a') => '\x07',
+                    Some('b') => '\x08',
+                    Some('0') => '\0',
+                    Some('\\') => '\\',
+                    Some(d) if d == delim => d,
+                    Some('x') | Some('X') => {
+                        let mut hex = String::new();
+                        while let Some(d) = cursor.next() {
+                            if d == ';' {
+                                break;
+                            }
+                            hex.push(d);
+                        }
+                        match u32::from_str_radix(&hex, 16).ok().and_then(char::from_u32) {
+                            Some(c) => c,
+                            None => {
+                                return Err(CompileError::new("invalid escape sequence".to_string(), Some(cursor.span(esc_line, esc_col))));
+                            },
+                        }
+                    },
+                    // a backslash at the end of a line skips the line break and the indentation
+                    Some(d) if d == '\n' || d == ' ' || d == '\t' => {
+                        let mut newline = d == '\n';
+                        while let Some(e) = cursor.peek() {
+                            if e == '\n' && !newline {
+                                newline = true;
+                            } else if e != ' ' && e != '\t' {
+                                break;
+                            }
+                            cursor.next();
+                        }
+                        continue;
+                    },
+                    _ => {
+                        return Err(CompileError::new("invalid escape sequence".to_string(), Some(cursor.span(esc_line, esc_col))));
+                    },
+                };
+                push_char(&mut string, c, what, cursor.span(esc_line, esc_col))?;
+            },
+            Some(c) => push_char(&mut string, c, what, cursor.span(esc_line, esc_col))?,
+            None => {
+                return Err(CompileError::new(format!("unterminated {}", what), Some(Span { line, col, len: 1 })));
+            },
+        }
+    }
+    Ok(string)
+}
+
+// strings and the names of symbols hold a byte for each character, so the characters
+// above \xff cannot be in them
+fn push_char(string: &mut String, c: char, what: &str, span: Span) -> Result<(), CompileError> {
+    if c > '\u{ff}' {
+        return Err(CompileError::new(format!("character out of range in {}", what), Some(span)));
+    }
+    string.push(c);
+    Ok(())
+}
+
+// skips a block comment after its opening '#|'. block comments can be nested.
+fn skip_block_comment(cursor: &mut Cursor, line: usize, col: usize) -> Result<(), CompileError> {
+    let mut nest = 1;
+    while nest > 0 {
+        match cursor.next() {
+            Some('|') if cursor.peek() == Some('#') => {
+                cursor.next();
+                nest -= 1;
+            },
+            Some('#') if cursor.peek() == Some('|') => {
+                cursor.next();
+                nest += 1;
+            },
+            Some(_) => {},
+            None => {
+                return Err(CompileError::new("unterminated block comment".to_string(), Some(Span { line, col, len: 2 })));
+            },
+        }
+    }
+    Ok(())
+}
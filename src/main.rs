@@ -1,30 +1,50 @@
+pub mod error;
 pub mod lexer;
 pub mod parser;
 pub mod codegen;
 
 use std::env;
 use std::fs;
+use std::process;
+use crate::error::CompileError;
 use crate::lexer::tokenize;
 use crate::parser::Parser;
 use crate::codegen::gen_asm;
 
 fn main() {
     let args: Vec<String> = env::args().collect();
+    if args.len() < 2 {
+        eprintln!("usage: {} <file>.scm", args[0]);
+        process::exit(1);
+    }
     let src_name: &str = &args[1];
-    let dst_name;
-    if src_name.ends_with(".scm") {
+    let dst_name = if src_name.ends_with(".scm") {
         let len = src_name.len();
-        dst_name = format!("{}.s", &src_name[..len-4]);
+        format!("{}.s", &src_name[..len-4])
     } else {
-        println!("{} is not scheme file", src_name);
-        return;
+        eprintln!("error: {} is not scheme file", src_name);
+        process::exit(1);
+    };
+
+    let src_code = match fs::read_to_string(src_name) {
+        Ok(src_code) => src_code,
+        Err(err) => {
+            eprintln!("error: could not read {}: {}", src_name, err);
+            process::exit(1);
+        },
+    };
+
+    if let Err(err) = compile(&src_code, dst_name) {
+        eprint!("{}", err.render(src_name, &src_code));
+        process::exit(1);
     }
+}
 
-    let src_code = &fs::read_to_string(src_name).expect("could not read the source file");
-    let token_list = tokenize(src_code);
+fn compile(src_code: &str, dst_name: String) -> Result<(), CompileError> {
+    let token_list = tokenize(src_code)?;
 
     let mut parser = Parser::new(token_list);
-    parser.parse_program();
+    parser.parse_program()?;
 
-    gen_asm(parser, dst_name);
-}
\ No newline at end of file
+    gen_asm(parser, dst_name)
+}
@@ -1,14 +1,158 @@
 use std::rc::Rc;
 use std::cell::RefCell;
-use std::collections::HashSet;
-use std::collections::hash_set::Iter;
+use std::slice::Iter;
 
-use crate::lexer::Token;
-use Token::*;
+use crate::error::CompileError;
+use crate::lexer::{Token, TokenKind};
+use TokenKind::*;
 // use Node::*;
 // use Expr::*;
 use Var::*;
 
+// the primitive procedures, the labels of their routines, the numbers of their arguments
+// and whether the last one is the list of the rest
+pub const PRIMITIVES: [(&str, &str, usize, bool); 139] = [
+    ("=", "rt_equal", 3, true),
+    ("!=", "rt_neq", 3, true),
+    ("<", "rt_lth", 3, true),
+    ("<=", "rt_leq", 3, true),
+    (">", "rt_gth", 3, true),
+    (">=", "rt_geq", 3, true),
+    ("+", "rt_add", 1, true),
+    ("-", "rt_sub", 2, true),
+    ("*", "rt_mul", 1, true),
+    ("/", "rt_div", 2, true),
+    ("quotient", "rt_quotient", 2, false),
+    ("remainder", "rt_remainder", 2, false),
+    ("modulo", "rt_modulo", 2, false),
+    ("abs", "rt_abs", 1, false),
+    ("min", "rt_min", 2, true),
+    ("max", "rt_max", 2, true),
+    ("gcd", "rt_gcd", 1, true),
+    ("lcm", "rt_lcm", 1, true),
+    ("expt", "rt_expt", 2, false),
+    ("zero?", "rt_is_zero", 1, false),
+    ("positive?", "rt_is_positive", 1, false),
+    ("negative?", "rt_is_negative", 1, false),
+    ("even?", "rt_is_even", 1, false),
+    ("odd?", "rt_is_odd", 1, false),
+    ("cons", "rt_cons", 2, false),
+    ("car", "rt_car", 1, false),
+    ("cdr", "rt_cdr", 1, false),
+    ("caar", "rt_caar", 1, false),
+    ("cadr", "rt_cadr", 1, false),
+    ("cdar", "rt_cdar", 1, false),
+    ("cddr", "rt_cddr", 1, false),
+    ("caaar", "rt_caaar", 1, false),
+    ("caadr", "rt_caadr", 1, false),
+    ("cadar", "rt_cadar", 1, false),
+    ("caddr", "rt_caddr", 1, false),
+    ("cdaar", "rt_cdaar", 1, false),
+    ("cdadr", "rt_cdadr", 1, false),
+    ("cddar", "rt_cddar", 1, false),
+    ("cdddr", "rt_cdddr", 1, false),
+    ("caaaar", "rt_caaaar", 1, false),
+    ("caaadr", "rt_caaadr", 1, false),
+    ("caadar", "rt_caadar", 1, false),
+    ("caaddr", "rt_caaddr", 1, false),
+    ("cadaar", "rt_cadaar", 1, false),
+    ("cadadr", "rt_cadadr", 1, false),
+    ("caddar", "rt_caddar", 1, false),
+    ("cadddr", "rt_cadddr", 1, false),
+    ("cdaaar", "rt_cdaaar", 1, false),
+    ("cdaadr", "rt_cdaadr", 1, false),
+    ("cdadar", "rt_cdadar", 1, false),
+    ("cdaddr", "rt_cdaddr", 1, false),
+    ("cddaar", "rt_cddaar", 1, false),
+    ("cddadr", "rt_cddadr", 1, false),
+    ("cdddar", "rt_cdddar", 1, false),
+    ("cddddr", "rt_cddddr", 1, false),
+    ("set-car!", "rt_set_car", 2, false),
+    ("set-cdr!", "rt_set_cdr", 2, false),
+    ("list", "rt_list", 1, true),
+    ("length", "rt_length", 1, false),
+    ("append", "rt_append", 1, true),
+    ("reverse", "rt_reverse", 1, false),
+    ("list-tail", "rt_list_tail", 2, false),
+    ("list-ref", "rt_list_ref", 2, false),
+    ("memq", "rt_memq", 2, false),
+    ("memv", "rt_memv", 2, false),
+    ("member", "rt_member", 2, false),
+    ("assq", "rt_assq", 2, false),
+    ("assv", "rt_assv", 2, false),
+    ("assoc", "rt_assoc", 2, false),
+    ("map", "rt_map", 3, true),
+    ("for-each", "rt_for_each", 3, true),
+    ("filter", "rt_filter", 2, false),
+    ("fold-left", "rt_fold_left", 4, true),
+    ("fold-right", "rt_fold_right", 4, true),
+    ("rem", "rt_rem", 2, false),
+    ("string?", "rt_is_string", 1, false),
+    ("make-string", "rt_make_string", 2, true),
+    ("string", "rt_string", 1, true),
+    ("string-length", "rt_string_length", 1, false),
+    ("string-ref", "rt_string_ref", 2, false),
+    ("string-set!", "rt_string_set", 3, false),
+    ("substring", "rt_substring", 3, false),
+    ("string-append", "rt_string_append", 1, true),
+    ("string-copy", "rt_string_copy", 1, false),
+    ("string=?", "rt_string_eq", 2, false),
+    ("string<?", "rt_string_lt", 2, false),
+    ("string->number", "rt_string_to_number", 1, false),
+    ("number->string", "rt_number_to_string", 1, false),
+    ("display", "rt_display", 1, false),
+    ("write", "rt_write_datum", 1, false),
+    ("newline", "rt_newline", 0, false),
+    ("write-char", "rt_write_char", 1, false),
+    ("write-string", "rt_write_string", 1, false),
+    ("eq?", "rt_eq", 2, false),
+    ("eqv?", "rt_eqv", 2, false),
+    ("equal?", "rt_is_equal", 2, false),
+    ("not", "rt_not", 1, false),
+    ("null?", "rt_is_null", 1, false),
+    ("pair?", "rt_is_pair", 1, false),
+    ("list?", "rt_is_list", 1, false),
+    ("number?", "rt_is_number", 1, false),
+    ("integer?", "rt_is_integer", 1, false),
+    ("boolean?", "rt_is_boolean", 1, false),
+    ("procedure?", "rt_is_procedure", 1, false),
+    ("char?", "rt_is_char", 1, false),
+    ("char->integer", "rt_char_to_integer", 1, false),
+    ("integer->char", "rt_integer_to_char", 1, false),
+    ("char=?", "rt_char_equal", 3, true),
+    ("char<?", "rt_char_lth", 3, true),
+    ("char<=?", "rt_char_leq", 3, true),
+    ("char>?", "rt_char_gth", 3, true),
+    ("char>=?", "rt_char_geq", 3, true),
+    ("char-upcase", "rt_char_upcase", 1, false),
+    ("char-downcase", "rt_char_downcase", 1, false),
+    ("char-alphabetic?", "rt_is_char_alphabetic", 1, false),
+    ("char-numeric?", "rt_is_char_numeric", 1, false),
+    ("char-whitespace?", "rt_is_char_whitespace", 1, false),
+    ("vector?", "rt_is_vector", 1, false),
+    ("make-vector", "rt_make_vector", 2, true),
+    ("vector", "rt_vector", 1, true),
+    ("vector-length", "rt_vector_length", 1, false),
+    ("vector-ref", "rt_vector_ref", 2, false),
+    ("vector-set!", "rt_vector_set", 3, false),
+    ("vector->list", "rt_vector_to_list", 1, false),
+    ("list->vector", "rt_list_to_vector", 1, false),
+    ("vector-fill!", "rt_vector_fill", 2, false),
+    ("vector-map", "rt_vector_map", 3, true),
+    ("vector-for-each", "rt_vector_for_each", 3, true),
+    ("bytevector?", "rt_is_bytevector", 1, false),
+    ("make-bytevector", "rt_make_bytevector", 2, true),
+    ("bytevector", "rt_bytevector", 1, true),
+    ("bytevector-length", "rt_bytevector_length", 1, false),
+    ("bytevector-u8-ref", "rt_bytevector_u8_ref", 2, false),
+    ("bytevector-u8-set!", "rt_bytevector_u8_set", 3, false),
+    ("bytevector-copy", "rt_bytevector_copy", 1, false),
+    ("symbol?", "rt_is_symbol", 1, false),
+    ("symbol->string", "rt_symbol_to_string", 1, false),
+    ("string->symbol", "rt_string_to_symbol", 1, false),
+    ("error", "rt_user_error", 2, true),
+];
+
 #[derive(Debug, Clone)]
 pub enum Node {
     Defn(Defn),
@@ -25,25 +169,122 @@ pub struct Defn {
 pub enum Expr {
     Var(Rc<RefCell<Var>>),
     Bool(bool),
-    Int(i32),
+    Int(i64),
+    Char(char),
+    Str(String),
+    Quote(Datum),
     Proc(String, FVs),
-    Call { proc: Rc<Expr>, params: Vec<Expr> },
+    Call { proc: Rc<Expr>, params: Vec<Expr>, tail: bool },
     If { test: Rc<Expr>, conseq: Rc<Expr>, alter: Rc<Expr> },
+    Set { var: Rc<RefCell<Var>>, expr: Rc<Expr> },
+    Or(Vec<Expr>),
+    Begin(Vec<Expr>),
+    Void,
+}
+
+// a constant written in the source
+#[derive(Debug, Clone)]
+pub enum Datum {
+    Bool(bool),
+    Int(i64),
+    BigInt(bool, Vec<u64>),
+    Char(char),
+    Str(String),
+    Symbol(String),
+    Nil,
+    Pair(Rc<Datum>, Rc<Datum>),
+    Vector(Vec<Datum>),
+    Bytevector(Vec<u8>),
+}
+
+impl Datum {
+    // makes a list of the items ending with the tail
+    fn list(items: Vec<Datum>, tail: Datum) -> Datum {
+        items.into_iter().rev().fold(tail, |cdr, car| Datum::Pair(Rc::new(car), Rc::new(cdr)))
+    }
+}
+
+// a quasiquoted template is a constant unless it contains an unquote
+enum Template {
+    Const(Datum),
+    Expr(Expr),
+}
+
+impl Template {
+    fn into_expr(self) -> Expr {
+        match self {
+            Template::Const(datum) => Expr::Quote(datum),
+            Template::Expr(expr) => expr,
+        }
+    }
+
+    fn cons(car: Template, cdr: Template) -> Template {
+        match (car, cdr) {
+            (Template::Const(car), Template::Const(cdr)) => Template::Const(Datum::Pair(Rc::new(car), Rc::new(cdr))),
+            (car, cdr) => Template::Expr(Expr::call("rt_cons", vec![car.into_expr(), cdr.into_expr()])),
+        }
+    }
+
+    // (name template)
+    fn form(name: &str, template: Template) -> Template {
+        let tail = Template::cons(template, Template::Const(Datum::Nil));
+        Template::cons(Template::Const(Datum::Symbol(name.to_string())), tail)
+    }
 }
 
+impl Expr {
+    // a call of a primitive procedure
+    fn call(label: &str, params: Vec<Expr>) -> Expr {
+        Expr::Call { proc: Rc::new(Expr::Proc(label.to_string(), FVs::new())), params, tail: false }
+    }
+
+    // marks the calls in tail position so that they reuse the current frame
+    fn set_tail(&mut self) {
+        match self {
+            Expr::Call { tail, .. } => {
+                *tail = true;
+            },
+            Expr::If { conseq, alter, .. } => {
+                Rc::make_mut(conseq).set_tail();
+                Rc::make_mut(alter).set_tail();
+            },
+            Expr::Or(exprs) | Expr::Begin(exprs) => {
+                if let Some(expr) = exprs.last_mut() {
+                    expr.set_tail();
+                }
+            },
+            _ => {},
+        }
+    }
+}
+
+// a local variable becomes free when an inner procedure refers to it.
+// the flag tells whether it is assigned by set! or define; free variables that are assigned live in boxes.
 #[derive(Debug, Clone)]
 pub enum Var {
     Global(String),
-    Free(String, usize),
-    Local(String, usize),
+    Free(String, usize, bool),
+    Local(String, usize, bool),
+}
+
+impl Var {
+    fn set_assigned(&mut self) {
+        match self {
+            Free(_, _, assigned) | Local(_, _, assigned) => *assigned = true,
+            Global(_) => {},
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct Lambda {
+    pub name: Option<String>,
     pub free_vars: FVs,
     pub free_num: usize,
     pub args_num: usize,
+    pub rest: bool,
     pub local_num: usize,
+    pub locals: Vec<Rc<RefCell<Var>>>,
     pub body: Vec<Node>,
 }
 
@@ -65,21 +306,43 @@ impl Env {
         &self.vec[0]
     }
 
+    fn frame(&self) -> Vec<Rc<RefCell<Var>>> {
+        self.vec.last().unwrap().iter().map(|var| var.1.clone()).collect()
+    }
+
     fn last(&self) -> Rc<RefCell<Var>> {
         self.vec.last().unwrap().last().unwrap().clone().1
     }
 
+    // returns the local of the name in the current frame, adding it if there is none.
+    // a defined local is assigned after the closures in its scope may have captured it.
+    fn declare_local(&mut self, name: String) -> Rc<RefCell<Var>> {
+        if let Some(var) = self.vec.last().unwrap().iter().find(|var| var.0 == name) {
+            return var.1.clone();
+        }
+        self.push_local(name);
+        let var = self.last();
+        var.borrow_mut().set_assigned();
+        var
+    }
+
     fn push_frame(&mut self) {
         self.vec.push(Vec::new());
     }
 
-    fn push_global(&mut self, name: String) {
-        let name2 = name.clone();
-        self.vec[0].push((name, Rc::new(RefCell::new(Var::Global(name2)))));
+    fn push_global(&mut self, name: String) -> Rc<RefCell<Var>> {
+        for global in &self.vec[0] {
+            if global.0 == name {
+                return global.1.clone();
+            }
+        }
+        let var = Rc::new(RefCell::new(Var::Global(name.clone())));
+        self.vec[0].push((name, var.clone()));
+        var
     }
     fn push_local(&mut self, name: String) {
         let offset = (self.vec.last().unwrap().len() + 1) * 8;
-        self.vec.last_mut().unwrap().push((name.clone(), Rc::new(RefCell::new(Var::Local(name.clone(), offset)))));
+        self.vec.last_mut().unwrap().push((name.clone(), Rc::new(RefCell::new(Var::Local(name.clone(), offset, false)))));
     }
 
     fn pop_frame(&mut self) {
@@ -93,14 +356,16 @@ impl Env {
                 if var.0 == name {
                     let mut is_free = false;
                     let mut offset = 0;
-                    if let Local(_, offset_) = *var.1.borrow() {
+                    let mut assigned = false;
+                    if let Local(_, offset_, assigned_) = *var.1.borrow() {
                         if 0 < i && i < self.vec.len() - 1 {
                             is_free = true;
                             offset = offset_;
+                            assigned = assigned_;
                         }
                     }
                     if is_free {
-                        *var.1.borrow_mut() = Free(name.clone(), offset);
+                        *var.1.borrow_mut() = Free(name.clone(), offset, assigned);
                     }
                     return Some(var.1.clone());
                 }
@@ -114,7 +379,7 @@ impl Env {
             let frame = &self.vec[i];
             for var in frame {
                 if var.0 == name {
-                    if let Free(_, offset) = *var.1.borrow() {
+                    if let Free(_, offset, _) = *var.1.borrow() {
                         return Some((offset, name.clone()));
                     }
                 }
@@ -136,30 +401,39 @@ impl Env {
     }
 }
 
-#[derive(Debug, Clone)]
+// the variables a procedure captures, with their offsets in the frames defining them.
+// they are kept in the order they are found, which is the order of the slots in the closure
+// and in the frame of the procedure, so the same program always compiles to the same code.
+#[derive(Debug, Clone, Default)]
 pub struct FVs {
-    pub set: HashSet<(usize, String)>,
+    pub vars: Vec<(usize, String)>,
 }
 
 impl FVs {
     pub fn new() -> Self {
-        FVs { set: HashSet::new() }
+        Self::default()
     }
 
     pub fn len(&self) -> usize {
-        self.set.len()
+        self.vars.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.vars.is_empty()
     }
 
     pub fn iter(&self) -> Iter<'_, (usize, String)> {
-        self.set.iter()
+        self.vars.iter()
     }
 
     fn insert(&mut self, fv: (usize, String)) {
-        self.set.insert(fv);
+        if !self.vars.contains(&fv) {
+            self.vars.push(fv);
+        }
     }
 
     pub fn offset(&self, name: String) -> usize {
-        for (i, fv) in self.set.iter().enumerate() {
+        for (i, fv) in self.vars.iter().enumerate() {
             if fv.1 == name {
                 return 8 * (i + 1);
             }
@@ -168,7 +442,7 @@ impl FVs {
     }
 
     pub fn include(&self, name: String) -> bool {
-        for (i, fv) in self.set.iter().enumerate() {
+        for fv in self.vars.iter() {
             if fv.1 == name {
                 return true;
             }
@@ -188,7 +462,7 @@ pub struct Parser {
 impl Parser {
     pub fn new(token_list: Vec<Token>) -> Self {
         Parser {
-            token_list: token_list,
+            token_list,
             pos: 0,
             env: Env::new(),
             proc_list: Vec::new(),
@@ -196,212 +470,829 @@ impl Parser {
         }
     }
 
-    pub fn parse_program(&mut self) {
-        while self.pos < self.token_list.len() {
-            let node = self.parse_global_expr();
+    pub fn parse_program(&mut self) -> Result<(), CompileError> {
+        self.declare_globals();
+        while self.token_list[self.pos].kind != Eof {
+            let node = self.parse_global_expr()?;
             self.node_list.push(node);
         }
-        println!("{:?}", self.node_list);
+        Ok(())
+    }
+
+    // registers every global definition in advance so that procedures can refer to each other
+    fn declare_globals(&mut self) {
+        for name in self.defined_names() {
+            self.env.push_global(name);
+        }
+    }
+
+    // looks ahead at the forms from the current token to the end of the body or the
+    // program and returns the names they define
+    fn defined_names(&self) -> Vec<String> {
+        let mut names = Vec::new();
+        let mut depth = 0;
+        let mut pos = self.pos;
+        loop {
+            match self.token_list[pos].kind {
+                OpenPar if depth == 0 && (pos == 0 || !matches!(self.token_list[pos - 1].kind, Quote | Quasiquote)) => {
+                    depth += 1;
+                    if let Ident(ref ident) = self.token_list[pos + 1].kind {
+                        if ident == "define" {
+                            let mut head = pos + 2;
+                            while self.token_list[head].kind == OpenPar {
+                                head += 1;
+                            }
+                            if let Ident(ref name) = self.token_list[head].kind {
+                                names.push(name.clone());
+                            }
+                        }
+                    }
+                },
+                OpenPar | VecOpen | BytevecOpen => depth += 1,
+                ClosePar if depth == 0 => break,
+                ClosePar => depth -= 1,
+                Eof => break,
+                _ => {},
+            }
+            pos += 1;
+        }
+        names
     }
 
-    fn parse_global_expr(&mut self) -> Node {
+    fn parse_global_expr(&mut self) -> Result<Node, CompileError> {
         if self.expect("(") {
             if self.expect("define") {
-                return Node::Defn(self.parse_defn_global());
+                return Ok(Node::Defn(self.parse_defn_global()?));
             } else {
                 self.pos -= 1;
             }
         }
-        return Node::Expr(self.parse_expr(&mut FVs::new()));
+        Ok(Node::Expr(self.parse_expr(&mut FVs::new())?))
     }
 
-    fn parse_local_expr(&mut self, fv: &mut FVs) -> Node {
+    fn parse_local_expr(&mut self, fv: &mut FVs) -> Result<Node, CompileError> {
         if self.expect("(") {
             if self.expect("define") {
-                return Node::Defn(self.parse_defn_local(fv));
+                return Ok(Node::Defn(self.parse_defn_local(fv)?));
             } else {
                 self.pos -= 1;
             }
         }
-        return Node::Expr(self.parse_expr(fv));
+        Ok(Node::Expr(self.parse_expr(fv)?))
     }
 
-    fn parse_defn_global(&mut self) -> Defn {
-        match self.token_list[self.pos] {
-            Ident(ref ident) => {
-                self.pos += 1;
-                let name = ident.clone();
-                self.env.push_global(name);
-                let expr = self.parse_expr(&mut FVs::new());
-                self.consume(")");
+    fn parse_defn_global(&mut self) -> Result<Defn, CompileError> {
+        let (name, depth) = self.parse_defn_head()?;
+        let var = self.env.push_global(name.clone());
+        let expr = self.parse_defn_value(&mut FVs::new(), depth)?;
+        self.name_lambda(&expr, name);
 
-                Defn { var: self.env.last(), expr }
-            },
-            _ => {
-                panic!("not identifier.");
-            },
+        Ok(Defn { var, expr })
+    }
+
+    // the locals defined in a body are declared before it is parsed, like letrec*
+    fn parse_defn_local(&mut self, fv: &mut FVs) -> Result<Defn, CompileError> {
+        let (name, depth) = self.parse_defn_head()?;
+        let var = self.env.declare_local(name.clone());
+        let expr = self.parse_defn_value(fv, depth)?;
+        self.name_lambda(&expr, name);
+
+        Ok(Defn { var, expr })
+    }
+
+    // parses the name of (define name expr), (define (name . formals) body ...) or of the
+    // curried (define ((name . formals) . formals) body ...), and the number of lists around it
+    fn parse_defn_head(&mut self) -> Result<(String, usize), CompileError> {
+        let mut depth = 0;
+        while self.expect("(") {
+            depth += 1;
         }
+        Ok((self.parse_ident()?, depth))
     }
 
-    fn parse_defn_local(&mut self, fv: &mut FVs) -> Defn {
-        match self.token_list[self.pos] {
-            Ident(ref ident) => {
-                self.pos += 1;
-                let name = ident.clone();
-                self.env.push_local(name);
-                let expr = self.parse_expr(fv);
-                self.consume(")");
+    // parses the rest of a definition after its name. each list around the name is a
+    // procedure whose formals follow it and which returns the procedure of the next one.
+    fn parse_defn_value(&mut self, fv: &mut FVs, depth: usize) -> Result<Expr, CompileError> {
+        if depth == 0 {
+            let expr = self.parse_expr(fv)?;
+            self.consume(")")?;
+            return Ok(expr);
+        }
 
-                Defn { var: self.env.last(), expr }
-            },
-            _ => {
-                panic!("not identifier.");
-            },
+        self.env.push_frame();
+        let (args_num, rest) = self.parse_formals_list(true)?;
+        if depth == 1 {
+            return self.parse_body(fv, FVs::new(), args_num, rest, Vec::new());
         }
+        let mut next_fv = FVs::new();
+        let expr = self.parse_defn_value(&mut next_fv, depth - 1)?;
+        Ok(self.make_lambda(fv, next_fv, args_num, rest, vec![Node::Expr(expr)]))
     }
 
-    fn parse_expr(&mut self, fv: &mut FVs) -> Expr {
-        match self.token_list[self.pos] {
+    fn parse_expr(&mut self, fv: &mut FVs) -> Result<Expr, CompileError> {
+        match self.token_list[self.pos].kind {
             Ident(ref ident) => {
                 self.pos += 1;
-                
-                if ident == "=" { return Expr::Proc("equal".to_string(), FVs::new()); }
-                if ident == "!=" { return Expr::Proc("neq".to_string(), FVs::new()); }
-                if ident == "<" { return Expr::Proc("lth".to_string(), FVs::new()); }
-                if ident == "<=" { return Expr::Proc("leq".to_string(), FVs::new()); }
-                if ident == ">" { return Expr::Proc("gth".to_string(), FVs::new()); }
-                if ident == ">=" { return Expr::Proc("geq".to_string(), FVs::new()); }
-                if ident == "+" { return Expr::Proc("add".to_string(), FVs::new()); }
-                if ident == "-" { return Expr::Proc("sub".to_string(), FVs::new()); }
-                if ident == "*" { return Expr::Proc("mul".to_string(), FVs::new()); }
-                if ident == "/" { return Expr::Proc("div".to_string(), FVs::new()); }
-
-                for std_proc in ["cons", "car", "cdr", "rem"] {
-                    if ident == std_proc {
-                        return Expr::Proc(ident.clone(), FVs::new());
-                    }
-                }
-                
-                match self.env.find(ident.clone()) {
-                    Some(var) => {
-                        if let Free(_, _) = *var.borrow() {
-                            fv.insert(self.env.find_fv(ident.clone()).unwrap());
-                        }
-                        Expr::Var(var)
-                    },
-                    None => {
-                        println!("variable '{}' is undefined", ident);
-                        panic!("");
+                let name = ident.clone();
+
+                // a binding of the name shadows the primitive
+                if self.env.find(name.clone()).is_none() {
+                    if let Some((_, label, _, _)) = PRIMITIVES.iter().find(|(primitive, _, _, _)| *primitive == name) {
+                        return Ok(Expr::Proc(label.to_string(), FVs::new()));
                     }
                 }
+                Ok(Expr::Var(self.find_var(fv, name)?))
             },
             Bool(ref value) => {
                 self.pos += 1;
-                Expr::Bool(*value)
+                Ok(Expr::Bool(*value))
             },
             Number(ref number) => {
                 self.pos += 1;
-                Expr::Int(*number)
+                Ok(Expr::Int(*number))
+            },
+            BigNumber(..) => Ok(Expr::Quote(self.parse_datum()?)),
+            Char(c) => {
+                self.pos += 1;
+                Ok(Expr::Char(c))
+            },
+            Str(ref string) => {
+                self.pos += 1;
+                Ok(Expr::Str(string.clone()))
+            },
+            VecOpen | BytevecOpen => {
+                Ok(Expr::Quote(self.parse_datum()?))
+            },
+            Quote => {
+                self.pos += 1;
+                Ok(Expr::Quote(self.parse_datum()?))
+            },
+            Quasiquote => {
+                self.pos += 1;
+                Ok(self.parse_quasi(fv, 1)?.into_expr())
+            },
+            Unquote | UnquoteSplicing => {
+                self.error("unquote outside quasiquote".to_string())
             },
             OpenPar => {
                 self.pos += 1;
 
+                if self.expect("quote") {
+                    let datum = self.parse_datum()?;
+                    self.consume(")")?;
+                    return Ok(Expr::Quote(datum));
+                }
+
+                if self.expect("quasiquote") {
+                    let template = self.parse_quasi(fv, 1)?;
+                    self.consume(")")?;
+                    return Ok(template.into_expr());
+                }
+
                 if self.expect("lambda") {
                     self.env.push_frame();
+                    let (args_num, rest) = self.parse_formals()?;
+                    return self.parse_body(fv, FVs::new(), args_num, rest, Vec::new());
+                }
 
-                    let mut args_num = 0;
-                    self.consume("(");
-                    while let Ident(ref ident) = self.token_list[self.pos] {
-                        self.env.push_local(ident.clone());
-                        args_num += 1;
+                if self.expect("let") {
+                    if let Ident(ref ident) = self.token_list[self.pos].kind {
+                        let name = ident.clone();
                         self.pos += 1;
+                        return self.parse_named_let(fv, name);
                     }
-                    self.consume(")");
-    
-                    let mut body: Vec<Node> = Vec::new();
-                    let mut next_fv = FVs::new();
-                    while self.token_list[self.pos] != ClosePar {
-                        body.push(self.parse_local_expr(&mut next_fv));
+
+                    let (names, inits) = self.parse_bindings(fv)?;
+                    self.env.push_frame();
+                    for name in names.iter() {
+                        self.env.push_local(name.clone());
                     }
+                    let proc = Rc::new(self.parse_body(fv, FVs::new(), names.len(), false, Vec::new())?);
+                    return Ok(Expr::Call { proc, params: inits, tail: false });
+                }
+
+                if self.expect("let*") {
+                    self.consume("(")?;
+                    return self.parse_let_star(fv);
+                }
 
-                    self.consume(")");
+                if self.expect("letrec") || self.expect("letrec*") {
+                    return self.parse_letrec(fv);
+                }
 
-                    let free_num = next_fv.len();
-                    let local_num = self.env.local_num();
-                    self.env.pop_frame();
+                if self.expect("if") {
+                    let test = Rc::new(self.parse_expr(fv)?);
+                    let conseq = Rc::new(self.parse_expr(fv)?);
+                    let alter = Rc::new(if self.token_list[self.pos].kind == ClosePar { Expr::Void } else { self.parse_expr(fv)? });
+                    self.consume(")")?;
+                    return Ok(Expr::If { test, conseq, alter });
+                }
 
-                    for (_, name) in next_fv.iter() {
-                        if self.env.is_free(name.clone()) {
-                            let free_var = self.env.find_fv(name.clone()).unwrap();
-                            fv.insert(free_var.clone());
-                        }
+                if self.expect("set!") {
+                    let name = self.parse_ident()?;
+                    let var = self.find_var(fv, name)?;
+                    var.borrow_mut().set_assigned();
+                    let expr = Rc::new(self.parse_expr(fv)?);
+                    self.consume(")")?;
+                    return Ok(Expr::Set { var, expr });
+                }
+
+                if self.expect("begin") {
+                    return self.parse_begin(fv);
+                }
+
+                if self.expect("cond") {
+                    return self.parse_cond(fv);
+                }
+
+                if self.expect("case") {
+                    return self.parse_case(fv);
+                }
+
+                if self.expect("and") {
+                    return self.parse_and(fv);
+                }
+
+                if self.expect("or") {
+                    let mut exprs = Vec::new();
+                    while self.token_list[self.pos].kind != ClosePar {
+                        exprs.push(self.parse_expr(fv)?);
                     }
-    
-                    let id = self.proc_list.len();
-                    self.proc_list.push(Lambda { free_vars: next_fv.clone(), free_num, args_num, local_num, body });
-                    return Expr::Proc(format!("_{}", id), next_fv);
+                    self.consume(")")?;
+                    return match exprs.len() {
+                        0 => Ok(Expr::Bool(false)),
+                        1 => Ok(exprs.pop().unwrap()),
+                        _ => Ok(Expr::Or(exprs)),
+                    };
                 }
 
-                if self.expect("if") {
-                    let test = Rc::new(self.parse_expr(fv));
-                    let conseq = Rc::new(self.parse_expr(fv));
-                    let alter = Rc::new(self.parse_expr(fv));
-                    self.consume(")");
-                    return Expr::If { test, conseq, alter };
+                if self.expect("when") {
+                    let test = Rc::new(self.parse_expr(fv)?);
+                    let conseq = Rc::new(self.parse_begin(fv)?);
+                    return Ok(Expr::If { test, conseq, alter: Rc::new(Expr::Void) });
+                }
+
+                if self.expect("unless") {
+                    let test = Rc::new(self.parse_expr(fv)?);
+                    let alter = Rc::new(self.parse_begin(fv)?);
+                    return Ok(Expr::If { test, conseq: Rc::new(Expr::Void), alter });
                 }
 
-                let proc = Rc::new(self.parse_expr(fv));
+                let proc = Rc::new(self.parse_expr(fv)?);
                 let mut params: Vec<Expr> = Vec::new();
-                while self.token_list[self.pos] != ClosePar {
-                    params.push(self.parse_expr(fv));
+                while self.token_list[self.pos].kind != ClosePar {
+                    params.push(self.parse_expr(fv)?);
                 }
-                self.consume(")");
+                self.consume(")")?;
 
-                return Expr::Call { proc, params };
+                Ok(Expr::Call { proc, params, tail: false })
             },
             ClosePar => {
-                panic!("too much ')'");
+                self.error("unexpected ')'".to_string())
             },
             Period => {
-                panic!("'.' is invalid");
+                self.error("unexpected '.'".to_string())
+            },
+            Eof => {
+                self.error("unexpected end of input".to_string())
             },
         }
     }
 
-    fn expect(&mut self, name: &str) -> bool {
-        match self.token_list[self.pos] {
-            Ident(ref ident) if *ident == name => {
+    // looks up a variable and records it as free in the current procedure if it is
+    fn find_var(&mut self, fv: &mut FVs, name: String) -> Result<Rc<RefCell<Var>>, CompileError> {
+        match self.env.find(name.clone()) {
+            Some(var) => {
+                if self.env.is_free(name.clone()) {
+                    fv.insert(self.env.find_fv(name.clone()).unwrap());
+                }
+                Ok(var)
+            },
+            None => {
+                Err(CompileError::new(format!("variable '{}' is undefined", name), Some(self.token_list[self.pos - 1].span)))
+            }
+        }
+    }
+
+    // parses the rest of a body in the frame pushed by the caller and closes the frame
+    fn parse_body(&mut self, fv: &mut FVs, mut next_fv: FVs, args_num: usize, rest: bool, mut body: Vec<Node>) -> Result<Expr, CompileError> {
+        for name in self.defined_names() {
+            self.env.declare_local(name);
+        }
+        while self.token_list[self.pos].kind != ClosePar {
+            body.push(self.parse_local_expr(&mut next_fv)?);
+        }
+        // the value of the procedure is that of the last expression
+        match body.last_mut() {
+            Some(Node::Expr(expr)) => expr.set_tail(),
+            _ => return self.error("expected expression".to_string()),
+        }
+        self.consume(")")?;
+
+        Ok(self.make_lambda(fv, next_fv, args_num, rest, body))
+    }
+
+    // makes a procedure taking args_num arguments. if rest is true, the last one is the
+    // list of the arguments after the others.
+    fn make_lambda(&mut self, fv: &mut FVs, next_fv: FVs, args_num: usize, rest: bool, body: Vec<Node>) -> Expr {
+        let free_num = next_fv.len();
+        let local_num = self.env.local_num();
+        let locals = self.env.frame();
+        self.env.pop_frame();
+
+        for (_, name) in next_fv.iter() {
+            if self.env.is_free(name.clone()) {
+                let free_var = self.env.find_fv(name.clone()).unwrap();
+                fv.insert(free_var.clone());
+            }
+        }
+
+        let id = self.proc_list.len();
+        self.proc_list.push(Lambda { name: None, free_vars: next_fv.clone(), free_num, args_num, rest, local_num, locals, body });
+        Expr::Proc(format!("_{}", id), next_fv)
+    }
+
+    // names the procedure of a lambda expression after the variable it is bound to,
+    // so that the errors raised on calling it can tell which procedure it is
+    fn name_lambda(&mut self, expr: &Expr, name: String) {
+        if let Expr::Proc(label, _) = expr {
+            if let Some(id) = label.strip_prefix('_').and_then(|id| id.parse::<usize>().ok()) {
+                self.proc_list[id].name = Some(name);
+            }
+        }
+    }
+
+    // parses (a b ...), (a b . rest) or args, adds the arguments to the current frame
+    // and returns their number and whether the last one is a rest argument
+    fn parse_formals(&mut self) -> Result<(usize, bool), CompileError> {
+        if let Ident(ref ident) = self.token_list[self.pos].kind {
+            self.env.push_local(ident.clone());
+            self.pos += 1;
+            return Ok((1, true));
+        }
+
+        self.consume("(")?;
+        self.parse_formals_list(false)
+    }
+
+    // parses the formals after the '(' of a list. in (define (name . rest) ...) the name
+    // comes before them, so the rest argument may be the first one.
+    fn parse_formals_list(&mut self, after_name: bool) -> Result<(usize, bool), CompileError> {
+        let mut args_num = 0;
+        while !self.expect(")") {
+            if self.token_list[self.pos].kind == Period && (args_num > 0 || after_name) {
                 self.pos += 1;
-                true
+                let name = self.parse_ident()?;
+                self.env.push_local(name);
+                self.consume(")")?;
+                return Ok((args_num + 1, true));
+            }
+            let name = self.parse_ident()?;
+            self.env.push_local(name);
+            args_num += 1;
+        }
+        Ok((args_num, false))
+    }
+
+    // parses ((name init) ...) and returns the names and the initial values
+    fn parse_bindings(&mut self, fv: &mut FVs) -> Result<(Vec<String>, Vec<Expr>), CompileError> {
+        let mut names = Vec::new();
+        let mut inits = Vec::new();
+        self.consume("(")?;
+        while self.expect("(") {
+            let name = self.parse_ident()?;
+            let init = self.parse_expr(fv)?;
+            self.name_lambda(&init, name.clone());
+            names.push(name);
+            inits.push(init);
+            self.consume(")")?;
+        }
+        self.consume(")")?;
+        Ok((names, inits))
+    }
+
+    // (let name ((var init) ...) body) is parsed as ((letrec ((name (lambda (var ...) body))) name) init ...)
+    fn parse_named_let(&mut self, fv: &mut FVs, name: String) -> Result<Expr, CompileError> {
+        let (names, inits) = self.parse_bindings(fv)?;
+
+        self.env.push_frame();
+        self.env.push_local(name.clone());
+        let var = self.env.last();
+        var.borrow_mut().set_assigned();
+
+        let mut next_fv = FVs::new();
+        self.env.push_frame();
+        for name in names.iter() {
+            self.env.push_local(name.clone());
+        }
+        let expr = self.parse_body(&mut next_fv, FVs::new(), names.len(), false, Vec::new())?;
+        self.name_lambda(&expr, name);
+
+        let body = vec![Node::Defn(Defn { var: var.clone(), expr }), Node::Expr(Expr::Var(var))];
+        let proc = Rc::new(self.make_lambda(fv, next_fv, 0, false, body));
+        let proc = Rc::new(Expr::Call { proc, params: Vec::new(), tail: false });
+        Ok(Expr::Call { proc, params: inits, tail: false })
+    }
+
+    // (let* ((a x) (b y)) body) is parsed as (let ((a x)) (let* ((b y)) body))
+    fn parse_let_star(&mut self, fv: &mut FVs) -> Result<Expr, CompileError> {
+        if self.expect(")") {
+            self.env.push_frame();
+            let proc = Rc::new(self.parse_body(fv, FVs::new(), 0, false, Vec::new())?);
+            return Ok(Expr::Call { proc, params: Vec::new(), tail: false });
+        }
+
+        self.consume("(")?;
+        let name = self.parse_ident()?;
+        let init = self.parse_expr(fv)?;
+        self.name_lambda(&init, name.clone());
+        self.consume(")")?;
+
+        self.env.push_frame();
+        self.env.push_local(name);
+        let proc = if self.expect(")") {
+            self.parse_body(fv, FVs::new(), 1, false, Vec::new())?
+        } else {
+            let mut next_fv = FVs::new();
+            let mut expr = self.parse_let_star(&mut next_fv)?;
+            expr.set_tail();
+            self.make_lambda(fv, next_fv, 1, false, vec![Node::Expr(expr)])
+        };
+        Ok(Expr::Call { proc: Rc::new(proc), params: vec![init], tail: false })
+    }
+
+    // (letrec ((a x) (b y)) body) is parsed as ((lambda () (define a x) (define b y) body))
+    fn parse_letrec(&mut self, fv: &mut FVs) -> Result<Expr, CompileError> {
+        self.env.push_frame();
+        let mut vars = Vec::new();
+        for name in self.binding_names() {
+            self.env.push_local(name);
+            let var = self.env.last();
+            var.borrow_mut().set_assigned();
+            vars.push(var);
+        }
+
+        let mut next_fv = FVs::new();
+        let mut body = Vec::new();
+        self.consume("(")?;
+        for var in vars {
+            self.consume("(")?;
+            let name = self.parse_ident()?;
+            let expr = self.parse_expr(&mut next_fv)?;
+            self.name_lambda(&expr, name);
+            self.consume(")")?;
+            body.push(Node::Defn(Defn { var, expr }));
+        }
+        self.consume(")")?;
+
+        let proc = Rc::new(self.parse_body(fv, next_fv, 0, false, body)?);
+        Ok(Expr::Call { proc, params: Vec::new(), tail: false })
+    }
+
+    // parses expressions up to ')' and evaluates them in order
+    fn parse_begin(&mut self, fv: &mut FVs) -> Result<Expr, CompileError> {
+        let mut exprs = Vec::new();
+        while self.token_list[self.pos].kind != ClosePar {
+            exprs.push(self.parse_expr(fv)?);
+        }
+        self.consume(")")?;
+        match exprs.len() {
+            0 => Ok(Expr::Void),
+            1 => Ok(exprs.pop().unwrap()),
+            _ => Ok(Expr::Begin(exprs)),
+        }
+    }
+
+    // (and a b ...) is parsed as (if a (and b ...) #f)
+    fn parse_and(&mut self, fv: &mut FVs) -> Result<Expr, CompileError> {
+        if self.expect(")") {
+            return Ok(Expr::Bool(true));
+        }
+        let test = self.parse_expr(fv)?;
+        if self.expect(")") {
+            return Ok(test);
+        }
+        let conseq = self.parse_and(fv)?;
+        Ok(Expr::If { test: Rc::new(test), conseq: Rc::new(conseq), alter: Rc::new(Expr::Bool(false)) })
+    }
+
+    // each clause of cond becomes an if whose alternative is the rest of the clauses
+    fn parse_cond(&mut self, fv: &mut FVs) -> Result<Expr, CompileError> {
+        if self.expect(")") {
+            return Ok(Expr::Void);
+        }
+        self.consume("(")?;
+        if self.expect("else") {
+            let expr = self.parse_begin(fv)?;
+            self.consume(")")?;
+            return Ok(expr);
+        }
+
+        let test = self.parse_expr(fv)?;
+        if self.expect(")") {
+            let alter = self.parse_cond(fv)?;
+            return Ok(Expr::Or(vec![test, alter]));
+        }
+
+        // (test => f) is parsed as (let ((t test)) (if t (f t) rest))
+        if self.expect("=>") {
+            self.env.push_frame();
+            self.env.push_local("(cond)".to_string());
+            let var = self.env.last();
+
+            let mut next_fv = FVs::new();
+            let proc = Rc::new(self.parse_expr(&mut next_fv)?);
+            self.consume(")")?;
+            let alter = Rc::new(self.parse_cond(&mut next_fv)?);
+
+            let conseq = Rc::new(Expr::Call { proc, params: vec![Expr::Var(var.clone())], tail: false });
+            let mut expr = Expr::If { test: Rc::new(Expr::Var(var)), conseq, alter };
+            expr.set_tail();
+            let proc = Rc::new(self.make_lambda(fv, next_fv, 1, false, vec![Node::Expr(expr)]));
+            return Ok(Expr::Call { proc, params: vec![test], tail: false });
+        }
+
+        let conseq = Rc::new(self.parse_begin(fv)?);
+        let alter = Rc::new(self.parse_cond(fv)?);
+        Ok(Expr::If { test: Rc::new(test), conseq, alter })
+    }
+
+    // (case key clause ...) is parsed as (let ((k key)) (cond ((or (eqv? k datum) ...) ...) ...))
+    fn parse_case(&mut self, fv: &mut FVs) -> Result<Expr, CompileError> {
+        let key = self.parse_expr(fv)?;
+
+        self.env.push_frame();
+        self.env.push_local("(case)".to_string());
+        let var = self.env.last();
+
+        let mut next_fv = FVs::new();
+        let mut expr = self.parse_case_clauses(&mut next_fv, &var)?;
+        expr.set_tail();
+        let proc = Rc::new(self.make_lambda(fv, next_fv, 1, false, vec![Node::Expr(expr)]));
+        Ok(Expr::Call { proc, params: vec![key], tail: false })
+    }
+
+    fn parse_case_clauses(&mut self, fv: &mut FVs, key: &Rc<RefCell<Var>>) -> Result<Expr, CompileError> {
+        if self.expect(")") {
+            return Ok(Expr::Void);
+        }
+        self.consume("(")?;
+        if self.expect("else") {
+            let expr = self.parse_case_body(fv, key)?;
+            self.consume(")")?;
+            return Ok(expr);
+        }
+
+        let mut tests = Vec::new();
+        self.consume("(")?;
+        while !self.expect(")") {
+            let params = vec![Expr::Var(key.clone()), Expr::Quote(self.parse_datum()?)];
+            tests.push(Expr::call("rt_eqv", params));
+        }
+        let test = Rc::new(match tests.len() {
+            0 => Expr::Bool(false),
+            1 => tests.pop().unwrap(),
+            _ => Expr::Or(tests),
+        });
+
+        let conseq = Rc::new(self.parse_case_body(fv, key)?);
+        let alter = Rc::new(self.parse_case_clauses(fv, key)?);
+        Ok(Expr::If { test, conseq, alter })
+    }
+
+    fn parse_case_body(&mut self, fv: &mut FVs, key: &Rc<RefCell<Var>>) -> Result<Expr, CompileError> {
+        if self.expect("=>") {
+            let proc = Rc::new(self.parse_expr(fv)?);
+            self.consume(")")?;
+            return Ok(Expr::Call { proc, params: vec![Expr::Var(key.clone())], tail: false });
+        }
+        self.parse_begin(fv)
+    }
+
+    fn parse_datum(&mut self) -> Result<Datum, CompileError> {
+        let datum = match self.token_list[self.pos].kind {
+            Bool(value) => Datum::Bool(value),
+            Number(number) => Datum::Int(number),
+            BigNumber(negative, ref limbs) => Datum::BigInt(negative, limbs.clone()),
+            Char(c) => Datum::Char(c),
+            Str(ref string) => Datum::Str(string.clone()),
+            Ident(ref ident) => Datum::Symbol(ident.clone()),
+            Quote | Quasiquote | Unquote | UnquoteSplicing => {
+                let (name, _) = self.abbrev().unwrap();
+                self.pos += 1;
+                let datum = self.parse_datum()?;
+                return Ok(Datum::list(vec![Datum::Symbol(name.to_string()), datum], Datum::Nil));
             },
-            OpenPar if name == "(" => {
+            OpenPar => {
                 self.pos += 1;
-                true
+                let mut items = Vec::new();
+                let mut tail = Datum::Nil;
+                while !self.expect(")") {
+                    if self.token_list[self.pos].kind == Period && !items.is_empty() {
+                        self.pos += 1;
+                        tail = self.parse_datum()?;
+                        self.consume(")")?;
+                        break;
+                    }
+                    items.push(self.parse_datum()?);
+                }
+                return Ok(Datum::list(items, tail));
             },
-            ClosePar if name == ")" => {
+            VecOpen => {
                 self.pos += 1;
-                true
+                let mut items = Vec::new();
+                while !self.expect(")") {
+                    items.push(self.parse_datum()?);
+                }
+                return Ok(Datum::Vector(items));
+            },
+            BytevecOpen => {
+                self.pos += 1;
+                let mut bytes = Vec::new();
+                while !self.expect(")") {
+                    match self.token_list[self.pos].kind {
+                        Number(number) if (0..256).contains(&number) => bytes.push(number as u8),
+                        _ => return self.error("expected byte".to_string()),
+                    }
+                    self.pos += 1;
+                }
+                return Ok(Datum::Bytevector(bytes));
             },
             _ => {
-                false
+                return self.error("expected datum".to_string());
+            },
+        };
+        self.pos += 1;
+        Ok(datum)
+    }
+
+    // the name of 'x, `x, ,x or ,@x at the current token, or of their long forms like
+    // (quote x). the flag tells if it is a long form.
+    fn abbrev(&self) -> Option<(&'static str, bool)> {
+        let name = match self.token_list[self.pos].kind {
+            Quote => "quote",
+            Quasiquote => "quasiquote",
+            Unquote => "unquote",
+            UnquoteSplicing => "unquote-splicing",
+            OpenPar => {
+                let name = match self.token_list[self.pos + 1].kind {
+                    Ident(ref ident) => ident.as_str(),
+                    _ => return None,
+                };
+                return ["quote", "quasiquote", "unquote", "unquote-splicing"].into_iter()
+                    .find(|form| *form == name)
+                    .map(|form| (form, true));
+            },
+            _ => return None,
+        };
+        Some((name, false))
+    }
+
+    // parses a quasiquoted template nested in `depth` quasiquotes. the parts that are not
+    // unquoted stay constant and the others are built with cons and append.
+    fn parse_quasi(&mut self, fv: &mut FVs, depth: usize) -> Result<Template, CompileError> {
+        if let Some((name, long)) = self.abbrev() {
+            if name == "unquote-splicing" && depth == 1 {
+                return self.error("unquote-splicing outside a list".to_string());
+            }
+            self.pos += if long { 2 } else { 1 };
+            let template = match name {
+                "unquote" if depth == 1 => Template::Expr(self.parse_expr(fv)?),
+                "unquote" | "unquote-splicing" => Template::form(name, self.parse_quasi(fv, depth - 1)?),
+                "quasiquote" => Template::form(name, self.parse_quasi(fv, depth + 1)?),
+                _ => Template::form(name, self.parse_quasi(fv, depth)?),
+            };
+            if long {
+                self.consume(")")?;
+            }
+            return Ok(template);
+        }
+
+        match self.token_list[self.pos].kind {
+            OpenPar => {
+                self.pos += 1;
+                let mut items = Vec::new();
+                let mut tail = Template::Const(Datum::Nil);
+                while !self.expect(")") {
+                    if self.token_list[self.pos].kind == Period && !items.is_empty() {
+                        self.pos += 1;
+                        tail = self.parse_quasi(fv, depth)?;
+                        self.consume(")")?;
+                        break;
+                    }
+                    if let Some(("unquote-splicing", long)) = self.abbrev() {
+                        if depth == 1 {
+                            self.pos += if long { 2 } else { 1 };
+                            items.push((Template::Expr(self.parse_expr(fv)?), true));
+                            if long {
+                                self.consume(")")?;
+                            }
+                            continue;
+                        }
+                    }
+                    items.push((self.parse_quasi(fv, depth)?, false));
+                }
+
+                let mut template = tail;
+                for (item, splice) in items.into_iter().rev() {
+                    template = if splice {
+                        Template::Expr(Expr::call("rt_append", vec![item.into_expr(), template.into_expr()]))
+                    } else {
+                        Template::cons(item, template)
+                    };
+                }
+                Ok(template)
+            },
+            VecOpen => {
+                let span = self.token_list[self.pos].span;
+                self.pos += 1;
+                let mut items = Vec::new();
+                while !self.expect(")") {
+                    match self.parse_quasi(fv, depth)? {
+                        Template::Const(datum) => items.push(datum),
+                        Template::Expr(_) => {
+                            return Err(CompileError::new("unquote in a vector template is not supported".to_string(), Some(span)));
+                        },
+                    }
+                }
+                Ok(Template::Const(Datum::Vector(items)))
+            },
+            _ => {
+                Ok(Template::Const(self.parse_datum()?))
             },
         }
     }
 
-    fn consume(&mut self, name: &str) {
-        match self.token_list[self.pos] {
+    // looks ahead at ((name init) ...) and returns the names
+    fn binding_names(&self) -> Vec<String> {
+        let mut names = Vec::new();
+        let mut depth = 0;
+        let mut pos = self.pos;
+        loop {
+            match self.token_list[pos].kind {
+                OpenPar | VecOpen | BytevecOpen => {
+                    depth += 1;
+                    if depth == 2 {
+                        if let Ident(ref name) = self.token_list[pos + 1].kind {
+                            names.push(name.clone());
+                        }
+                    }
+                },
+                ClosePar => {
+                    depth -= 1;
+                    if depth == 0 {
+                        break;
+                    }
+                },
+                Eof => break,
+                _ => {},
+            }
+            pos += 1;
+        }
+        names
+    }
+
+    fn parse_ident(&mut self) -> Result<String, CompileError> {
+        match self.token_list[self.pos].kind {
+            Ident(ref ident) => {
+                self.pos += 1;
+                Ok(ident.clone())
+            },
+            _ => {
+                self.error("expected identifier".to_string())
+            },
+        }
+    }
+
+    fn expect(&mut self, name: &str) -> bool {
+        match self.token_list[self.pos].kind {
             Ident(ref ident) if *ident == name => {
                 self.pos += 1;
+                true
             },
             OpenPar if name == "(" => {
                 self.pos += 1;
+                true
             },
             ClosePar if name == ")" => {
                 self.pos += 1;
+                true
             },
             _ => {
-                println!("{} is unexpected", name);
-            }
+                false
+            },
+        }
+    }
+
+    fn consume(&mut self, name: &str) -> Result<(), CompileError> {
+        if self.expect(name) {
+            Ok(())
+        } else {
+            self.error(format!("expected '{}'", name))
         }
     }
+
+    fn error<T>(&self, msg: String) -> Result<T, CompileError> {
+        Err(CompileError::new(msg, Some(self.token_list[self.pos].span)))
+    }
 }
\ No newline at end of file
@@ -0,0 +1,141 @@
+mod common;
+
+use common::{compile_error, error, values};
+
+#[test]
+fn add() {
+    assert_eq!(values("add", &["(+)", "(+ 5)", "(+ 1 2)", "(+ 1 2 3 4)", "(let ((f +)) (f 1 2 3))"]),
+               ["0", "5", "3", "10", "6"]);
+}
+
+#[test]
+fn sub() {
+    assert_eq!(values("sub", &["(- 5)", "(- 0)", "(- 10 3)", "(- 10 1 2 3)", "(- 3 10)"]),
+               ["-5", "0", "7", "4", "-7"]);
+}
+
+#[test]
+fn mul() {
+    assert_eq!(values("mul", &["(*)", "(* 4)", "(* 6 7)", "(* 2 3 4)", "(* (- 3) 5)", "(* 5 0)"]),
+               ["1", "4", "42", "24", "-15", "0"]);
+}
+
+#[test]
+fn div() {
+    assert_eq!(values("div", &["(/ 8 2)", "(/ (- 8) 2)", "(/ 100 5 2)", "(/ 1)", "(/ (- 1))"]),
+               ["4", "-4", "10", "1", "-1"]);
+    assert_eq!(error("div_inexact", "(/ 7 2)"), "error: /: quotient is not an integer\n");
+    assert_eq!(error("div_inverse", "(/ 2)"), "error: /: quotient is not an integer\n");
+    assert_eq!(error("div_big_inexact", "(/ (expt 10 30) 7)"), "error: /: quotient is not an integer\n");
+}
+
+#[test]
+fn quotient() {
+    assert_eq!(values("quotient", &["(quotient 17 5)", "(quotient (- 17) 5)", "(quotient 17 (- 5))", "(quotient 4 5)"]),
+               ["3", "-3", "-3", "0"]);
+}
+
+#[test]
+fn remainder() {
+    assert_eq!(values("remainder", &["(remainder 17 5)", "(remainder (- 17) 5)", "(remainder 17 (- 5))",
+                                     "(remainder 15 5)", "(rem 17 5)"]),
+               ["2", "-2", "2", "0", "2"]);
+}
+
+#[test]
+fn modulo() {
+    assert_eq!(values("modulo", &["(modulo 17 5)", "(modulo (- 17) 5)", "(modulo 17 (- 5))",
+                                  "(modulo (- 17) (- 5))", "(modulo 15 (- 5))"]),
+               ["2", "3", "-3", "-2", "0"]);
+}
+
+#[test]
+fn abs() {
+    assert_eq!(values("abs", &["(abs (- 7))", "(abs 7)", "(abs 0)"]), ["7", "7", "0"]);
+}
+
+#[test]
+fn min_max() {
+    assert_eq!(values("min_max", &["(min 3)", "(min 3 1 2)", "(min (- 4) 2)", "(max 3)", "(max 3 9 2)", "(max (- 4) (- 2))"]),
+               ["3", "1", "-4", "3", "9", "-2"]);
+}
+
+#[test]
+fn gcd() {
+    assert_eq!(values("gcd", &["(gcd)", "(gcd 12)", "(gcd (- 12))", "(gcd 12 18)", "(gcd (- 12) 18 8)", "(gcd 0 5)"]),
+               ["0", "12", "12", "6", "2", "5"]);
+}
+
+#[test]
+fn lcm() {
+    assert_eq!(values("lcm", &["(lcm)", "(lcm 4 6)", "(lcm (- 4) 6)", "(lcm 3 5 7)", "(lcm 0 5)", "(lcm 0 0)"]),
+               ["1", "12", "12", "105", "0", "0"]);
+}
+
+#[test]
+fn expt() {
+    assert_eq!(values("expt", &["(expt 2 10)", "(expt 3 0)", "(expt (- 2) 3)", "(expt 0 0)", "(expt 7 1)"]),
+               ["1024", "1", "-8", "1", "7"]);
+}
+
+#[test]
+fn predicates() {
+    assert_eq!(values("predicates", &["(zero? 0)", "(zero? 3)", "(positive? 3)", "(positive? 0)", "(negative? (- 1))",
+                                      "(negative? 0)", "(even? 4)", "(even? (- 3))", "(odd? 3)", "(odd? 0)"]),
+               ["#t", "#f", "#t", "#f", "#t", "#f", "#t", "#f", "#t", "#f"]);
+}
+
+#[test]
+fn comparisons() {
+    assert_eq!(values("comparisons", &["(< 1 2 3)", "(< 1 3 2)", "(<= 1 1 2)", "(> 3 2 1)", "(>= 3 3 4)", "(= 2 2 2)",
+                                       "(= 2 2 3)", "(< 1 2)", "(!= 1 2)", "(< 1 2 (expt 2 70))"]),
+               ["#t", "#f", "#t", "#t", "#f", "#t", "#f", "#t", "#t", "#t"]);
+    assert_eq!(error("compare_arity", "(< 1)"), "error: <: wrong number of arguments, expected at least 2, given 1\n");
+    assert_eq!(error("compare_type", "(< 2 1 'a)"), "error: <: wrong type argument a\n");
+}
+
+#[test]
+fn division_by_zero() {
+    assert_eq!(error("div_zero", "(/ 5 0)"), "error: /: division by zero\n");
+    assert_eq!(error("quotient_zero", "(quotient 5 0)"), "error: quotient: division by zero\n");
+    assert_eq!(error("remainder_zero", "(remainder 5 0)"), "error: remainder: division by zero\n");
+    assert_eq!(error("modulo_zero", "(modulo 5 0)"), "error: modulo: division by zero\n");
+}
+
+#[test]
+fn wrong_arguments() {
+    assert_eq!(error("add_type", "(+ 1 #t)"), "error: +: wrong type argument #t\n");
+    assert_eq!(error("sub_arity", "(-)"), "error: -: wrong number of arguments, expected at least 1, given 0\n");
+    assert_eq!(error("abs_arity", "(abs 1 2)"), "error: abs: wrong number of arguments, expected 1, given 2\n");
+    assert_eq!(error("expt_range", "(expt 2 (- 1))"), "error: expt: index out of range -1\n");
+}
+
+#[test]
+fn literals() {
+    assert_eq!(values("literals", &["-5", "+7", "(- 3 -4)", "#x-ff", "#b101", "1152921504606846975",
+                                    "-1152921504606846976", "(* -1073741824 1073741824)"]),
+               ["-5", "7", "7", "-255", "5", "1152921504606846975", "-1152921504606846976", "-1152921504606846976"]);
+    assert_eq!(values("string_to_number", &["(string->number \"-1152921504606846976\")",
+                                            "(string->number \"1152921504606846976\")",
+                                            "(string->number \"99999999999999999999\")"]),
+               ["-1152921504606846976", "1152921504606846976", "99999999999999999999"]);
+}
+
+#[test]
+fn literal_errors() {
+    let err = compile_error("literal_no_digits", "(write #x-)");
+    assert!(err.starts_with("error: invalid number '#x-'\n"), "{}", err);
+    let err = compile_error("literal_invalid", "(write 12abc)");
+    assert!(err.starts_with("error: invalid number '12abc'\n"), "{}", err);
+}
+
+#[test]
+fn fixnum_limits() {
+    assert_eq!(values("no_overflow", &["(+ 1152921504606846974 1)", "(expt 2 59)", "(expt -2 59)", "(abs (- 1152921504606846975))"]),
+               ["1152921504606846975", "576460752303423488", "-576460752303423488", "1152921504606846975"]);
+    assert_eq!(values("overflow", &["(+ 1152921504606846975 1)", "(- -1152921504606846976 1)", "(- -1152921504606846976)",
+                                    "(* 1073741824 1073741824)", "(quotient -1152921504606846976 -1)",
+                                    "(abs -1152921504606846976)", "(expt 3 40)"]),
+               ["1152921504606846976", "-1152921504606846977", "1152921504606846976", "1152921504606846976",
+                "1152921504606846976", "1152921504606846976", "12157665459056928801"]);
+}
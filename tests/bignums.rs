@@ -0,0 +1,87 @@
+mod common;
+
+use common::{error, output, values};
+
+const FACT: &str = "(define (fact n) (if (= n 0) 1 (* n (fact (- n 1)))))\n";
+
+fn fact_values(name: &str, exprs: &[&str]) -> Vec<String> {
+    let src: String = exprs.iter().map(|expr| format!("(write {})\n(newline)\n", expr)).collect();
+    output(name, &format!("{}{}", FACT, src)).lines().map(|line| line.to_string()).collect()
+}
+
+#[test]
+fn arithmetic() {
+    let lines = fact_values("bignum_arithmetic", &[
+        "(fact 30)",
+        "(- (fact 25))",
+        "(+ (fact 25) (- (fact 25)) 5)",
+        "(- (+ 1152921504606846975 1) 1)",
+        "(* (- (expt 2 64)) (expt 2 64))",
+        "(* 0 (fact 30))",
+        "(abs (- (fact 22)))",
+        "(list (max 1 (fact 21) 3) (min (- (fact 21)) 0))",
+    ]);
+    assert_eq!(lines, ["265252859812191058636308480000000", "-15511210043330985984000000", "5", "1152921504606846975",
+                       "-340282366920938463463374607431768211456", "0", "1124000727777607680000",
+                       "(51090942171709440000 -51090942171709440000)"]);
+}
+
+#[test]
+fn division() {
+    let lines = fact_values("bignum_division", &[
+        "(quotient (fact 30) (fact 28))",
+        "(remainder (fact 30) 1000000007)",
+        "(modulo (- (fact 30)) 1000000007)",
+        "(quotient (- (fact 30)) (fact 27))",
+        "(remainder (- (fact 30)) (+ (fact 27) 1))",
+        "(modulo (fact 30) (- (+ (fact 27) 1)))",
+        "(/ (expt 10 40) (expt 10 20))",
+        "(list (gcd (fact 30) (expt 2 100)) (lcm (expt 2 70) (expt 3 40)))",
+    ]);
+    assert_eq!(lines, ["870", "109361473", "890638534", "-24360", "-10888869450418352160767975641", "-24360",
+                       "100000000000000000000", "(67108864 14353237968448109868972222216943775514624)"]);
+    assert_eq!(error("bignum_zero_error", "(quotient 100000000000000000000 0)"), "error: quotient: division by zero\n");
+}
+
+#[test]
+fn comparisons() {
+    let lines = fact_values("bignum_comparisons", &[
+        "(list (< (fact 30) (fact 29)) (> (fact 30) 5) (= (fact 22) (fact 22)) (< (- (fact 30)) 0))",
+        "(list (< -1152921504606846977 -1152921504606846976) (>= 1152921504606846976 1152921504606846975))",
+        "(list (zero? (- (fact 30) (fact 30))) (positive? (fact 30)) (negative? (- (fact 30))))",
+        "(list (even? (fact 30)) (odd? (+ (fact 30) 1)) (number? (fact 30)) (integer? (fact 30)))",
+        "(list (eqv? (fact 25) (fact 25)) (eqv? (fact 25) (fact 26)) (equal? (list (fact 25)) (list (fact 25))))",
+        "(memv (fact 21) (list 1 (fact 21)))",
+    ]);
+    assert_eq!(lines, ["(#f #t #t #t)", "(#t #t)", "(#t #t #t)", "(#t #t #t #t)", "(#t #f #t)", "(51090942171709440000)"]);
+}
+
+#[test]
+fn conversions() {
+    let lines = values("bignum_conversions", &[
+        "123456789012345678901234567890",
+        "-100000000000000000000",
+        "#x-10000000000000000",
+        "'(1 100000000000000000000)",
+        "(list (expt 10 18) (expt 10 36) (- (expt 10 19) 1))",
+        "(number->string (expt 2 100))",
+        "(string->number \"-123456789012345678901234567890\")",
+        "(string->number \"1234567890123456789012x\")",
+    ]);
+    assert_eq!(lines, ["123456789012345678901234567890", "-100000000000000000000", "-18446744073709551616",
+                       "(1 100000000000000000000)",
+                       "(1000000000000000000 1000000000000000000000000000000000000 9999999999999999999)",
+                       "\"1267650600228229401496703205376\"", "-123456789012345678901234567890", "#f"]);
+}
+
+#[test]
+fn collection() {
+    let src = format!("{}{}", FACT, "
+(define (loop i acc keep)
+  (if (= i 20000)
+      (begin (write acc) (newline) (write (car keep)) (newline))
+      (loop (+ i 1) (+ acc (fact 25)) (cons (* (fact 22) i) (if (< i 100) keep '())))))
+(loop 0 0 '())
+");
+    assert_eq!(output("bignum_collection", &src), "310224200866619719680000000000\n22478890554824375992320000\n");
+}
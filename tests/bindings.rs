@@ -0,0 +1,52 @@
+mod common;
+
+use common::{output, values};
+
+#[test]
+fn shadowing() {
+    let lines = values("shadowing", &[
+        "(let ((car 5)) car)",
+        "(let ((x 1)) (let ((x 2) (y x)) (list x y)))",
+        "(let* ((list 1) (list (+ list 1))) list)",
+        "((lambda (max) (+ max 1)) 4)",
+        "(letrec ((length (lambda (l) (if (null? l) 10 (+ 1 (length (cdr l))))))) (length '(1 2)))",
+        "(let loop ((list '(1 2 3)) (sum 0)) (if (null? list) sum (loop (cdr list) (+ sum (car list)))))",
+        "(let ((+ -)) (+ 5 3))",
+        "(car '(1 2))",
+    ]);
+    assert_eq!(lines, ["5", "(2 1)", "2", "5", "12", "6", "2", "1"]);
+}
+
+#[test]
+fn shadowed_globals() {
+    let src = "(define (sum list) (if (null? list) 0 (+ (car list) (sum (cdr list)))))
+               (define (f car) (+ car 1))
+               (display (sum '(1 2 3))) (display (f 1)) (display (map car '((1) (2))))";
+    assert_eq!(output("shadowed_globals", src), "62(1 2)");
+}
+
+#[test]
+fn curried_definitions() {
+    let src = "(define ((adder n) m) (+ n m))
+               (define (((curry3 a) b) c) (list a b c))
+               (define add2 (adder 2))
+               (display (add2 3)) (display (((curry3 1) 2) 3))";
+    assert_eq!(output("curried_definitions", src), "5(1 2 3)");
+}
+
+// the internal definitions of a body see each other, and each sees the values of those before it
+#[test]
+fn internal_definitions() {
+    let src = "(define (parity n)
+                 (define (ev? n) (if (= n 0) #t (od? (- n 1))))
+                 (define (od? n) (if (= n 0) #f (ev? (- n 1))))
+                 (list (ev? n) (od? n)))
+               (define (sequential x)
+                 (define a (+ x 1))
+                 (define b (* a 2))
+                 (define (sum) (+ a b))
+                 (sum))
+               (display (parity 7)) (display (sequential 1))
+               (display (let () (define x 1) (define (f) x) (set! x 5) (f)))";
+    assert_eq!(output("internal_definitions", src), "(#f #t)65");
+}
@@ -0,0 +1,48 @@
+mod common;
+
+use common::{error, values};
+
+#[test]
+fn chars() {
+    let lines = values("chars", &[
+        "(list #\\a #\\space #\\newline #\\x41)",
+        "(char->integer #\\A)",
+        "(char->integer #\\x3bb)",
+        "(integer->char 97)",
+        "(char->integer (integer->char 1114111))",
+        "(list (char? #\\a) (char? 97) (char? \"a\"))",
+    ]);
+    assert_eq!(lines, ["(#\\a #\\space #\\newline #\\A)", "65", "955", "#\\a", "1114111", "(#t #f #f)"]);
+}
+
+#[test]
+fn comparisons() {
+    let lines = values("char_comparisons", &[
+        "(list (char=? #\\a #\\a) (char=? #\\a #\\b))",
+        "(list (char<? #\\a #\\b) (char<? #\\b #\\a) (char<? #\\a #\\a))",
+        "(list (char<=? #\\a #\\a) (char>? #\\x3bb #\\z) (char>=? #\\A #\\a))",
+        "(list (char<? #\\a #\\b #\\c) (char=? #\\a #\\a #\\b))",
+    ]);
+    assert_eq!(lines, ["(#t #f)", "(#t #f #f)", "(#t #t #f)", "(#t #f)"]);
+}
+
+#[test]
+fn conversions() {
+    let lines = values("char_conversions", &[
+        "(map char-upcase '(#\\a #\\z #\\A #\\1 #\\{))",
+        "(map char-downcase '(#\\A #\\Z #\\a #\\@ #\\[))",
+        "(map char-alphabetic? '(#\\a #\\Z #\\@ #\\[ #\\` #\\{ #\\1))",
+        "(map char-numeric? '(#\\0 #\\9 #\\/ #\\: #\\a))",
+        "(map char-whitespace? '(#\\space #\\newline #\\tab #\\return #\\a #\\x8 #\\xe))",
+    ]);
+    assert_eq!(lines, ["(#\\A #\\Z #\\A #\\1 #\\{)", "(#\\a #\\z #\\a #\\@ #\\[)", "(#t #t #f #f #f #f #f)",
+                       "(#t #t #f #f #f)", "(#t #t #t #t #f #f #f)"]);
+}
+
+#[test]
+fn errors() {
+    assert_eq!(error("char_type_error", "(char->integer 65)"), "error: char->integer: wrong type argument 65\n");
+    assert_eq!(error("surrogate_error", "(integer->char 55296)"), "error: integer->char: index out of range 55296\n");
+    assert_eq!(error("code_error", "(integer->char 1114112)"), "error: integer->char: index out of range 1114112\n");
+    assert_eq!(error("char_compare_error", "(char<? #\\a 1)"), "error: char<?: wrong type argument 1\n");
+}
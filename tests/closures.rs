@@ -0,0 +1,36 @@
+mod common;
+
+use std::fs;
+
+use common::{compile, output};
+
+// captures several variables of different frames, some of them assigned
+const COUNTERS: &str = "
+(define (make-counter start step)
+  (let ((count start) (calls 0))
+    (lambda ()
+      (set! calls (+ calls 1))
+      (let ((value count))
+        (set! count (+ count step))
+        (list value calls start step)))))
+(define (list . items) items)
+(define counter (make-counter 10 5))
+(counter)
+(display (counter))
+(define (adder a b c d) (lambda (x) (lambda (y) (+ a b c d x y))))
+(display (((adder 1 2 3 4) 5) 6))
+";
+
+#[test]
+fn captured_variables() {
+    assert_eq!(output("captured_variables", COUNTERS), "(15 2 10 5)21");
+}
+
+#[test]
+fn reproducible_output() {
+    let first = fs::read_to_string(compile("reproducible_output_1", COUNTERS)).unwrap();
+    for i in 2..6 {
+        let asm = fs::read_to_string(compile(&format!("reproducible_output_{}", i), COUNTERS)).unwrap();
+        assert_eq!(first, asm);
+    }
+}
@@ -0,0 +1,30 @@
+mod common;
+
+use common::{error, output};
+
+// allocates many times the size of the first heap while values are held in a global, in the
+// variables of closures and in the arguments evaluated before a call that collects
+const CHURN: &str = "
+(define (build n acc) (if (= n 0) acc (build (- n 1) (cons n acc))))
+(define (churn n) (if (= n 0) 0 (begin (build 1000 '()) (churn (- n 1)))))
+(define kept (build 3 '()))
+(define (make-getter l) (lambda () l))
+(define getter (make-getter (vector (build 2 '()) \"str\")))
+(define (lengths a b c) (list (length a) (vector-length b) c))
+(write (lengths (build 100 '()) (make-vector 5 (build 4 '())) (churn 2000)))
+(set! kept (cons (churn 1000) kept))
+(write kept)
+(write (getter))
+";
+
+#[test]
+fn live_values() {
+    assert_eq!(output("live_values", CHURN), "(100 5 0)(0 1 2 3)#((1 2) \"str\")");
+}
+
+#[test]
+fn out_of_memory() {
+    assert_eq!(error("memory_error", "(make-string 100000000000 #\\a)"), "error: out of memory\n");
+    assert_eq!(error("vector_memory_error", "(define v (make-vector 1000 0)) (make-vector (expt 2 40) v)"),
+               "error: out of memory\n");
+}
@@ -0,0 +1,69 @@
+// each test file uses only some of the helpers
+#![allow(dead_code)]
+
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+
+// what a compiled program wrote and its exit status
+pub struct Run {
+    pub stdout: String,
+    pub stderr: String,
+    pub status: i32,
+}
+
+// compiles the program with the compiler and returns the path of the assembly.
+// the files are named after the test so that the tests can run in parallel.
+pub fn compile(name: &str, src: &str) -> PathBuf {
+    let src_path = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(format!("{}.scm", name));
+    fs::write(&src_path, src).unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_scheme-compiler")).arg(&src_path).output().unwrap();
+    assert!(output.status.success(), "{} does not compile: {}", name, String::from_utf8_lossy(&output.stderr));
+    src_path.with_extension("s")
+}
+
+// compiles the program, checking that the compiler rejects it, and returns the error
+pub fn compile_error(name: &str, src: &str) -> String {
+    let src_path = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(format!("{}.scm", name));
+    fs::write(&src_path, src).unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_scheme-compiler")).arg(&src_path).output().unwrap();
+    assert!(!output.status.success(), "{} compiles", name);
+    String::from_utf8(output.stderr).unwrap()
+}
+
+// compiles the program, assembles it with cc and runs it
+pub fn run(name: &str, src: &str) -> Run {
+    let asm_path = compile(name, src);
+    let exe_path = asm_path.with_extension("");
+    let output = Command::new("cc").arg("-o").arg(&exe_path).arg(&asm_path).output().unwrap();
+    assert!(output.status.success(), "{} does not assemble: {}", name, String::from_utf8_lossy(&output.stderr));
+
+    let output = Command::new(&exe_path).output().unwrap();
+    Run {
+        stdout: String::from_utf8(output.stdout).unwrap(),
+        stderr: String::from_utf8(output.stderr).unwrap(),
+        status: output.status.code().unwrap(),
+    }
+}
+
+// runs the program and returns what it wrote, checking that it exits normally
+pub fn output(name: &str, src: &str) -> String {
+    let run = run(name, src);
+    assert_eq!(run.status, 0, "{} fails: {}", name, run.stderr);
+    run.stdout
+}
+
+// runs the program and returns the error it stops with
+pub fn error(name: &str, src: &str) -> String {
+    let run = run(name, src);
+    assert_eq!(run.status, 70, "{} does not fail", name);
+    run.stderr
+}
+
+// writes the value of each expression on its own line and returns the lines
+pub fn values(name: &str, exprs: &[&str]) -> Vec<String> {
+    let src: String = exprs.iter().map(|expr| format!("(write {})\n(newline)\n", expr)).collect();
+    output(name, &src).lines().map(|line| line.to_string()).collect()
+}
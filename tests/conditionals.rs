@@ -0,0 +1,61 @@
+mod common;
+
+use common::{output, values};
+
+#[test]
+fn cond() {
+    let lines = values("cond", &[
+        "(cond ((> 1 2) 'a) ((< 1 2) 'b) (else 'c))",
+        "(cond ((> 1 2) 'a) (else 'c))",
+        "(cond ((assv 2 '((1 . one) (2 . two))) => cdr) (else 'none))",
+        "(cond ((assv 3 '((1 . one))) => cdr) (else 'none))",
+        "(cond ((memv 2 '(1 2 3))) (else #f))",
+        "(cond (#f 1) (2 (display \"x\") 3))",
+    ]);
+    assert_eq!(lines, ["b", "c", "two", "none", "(2 3)", "x3"]);
+}
+
+#[test]
+fn case() {
+    let lines = values("case", &[
+        "(case (* 2 3) ((2 3 5 7) 'prime) ((1 4 6 8 9) 'composite))",
+        "(case 'b ((a) 1) ((b c) 2) (else 3))",
+        "(case #\\z ((#\\a) 1) (else 'other))",
+        "(case 10 ((1) 'one) (else => (lambda (x) (* x 2))))",
+    ]);
+    assert_eq!(lines, ["composite", "2", "other", "20"]);
+}
+
+#[test]
+fn and_or() {
+    let lines = values("and_or", &[
+        "(list (and) (and 1 2 3) (and 1 #f 3))",
+        "(list (or) (or #f 2 3) (or #f #f))",
+        "(and 1 (memv 2 '(1 2)))",
+    ]);
+    assert_eq!(lines, ["(#t 3 #f)", "(#f 2 #f)", "(2)"]);
+}
+
+// the operands after the one that decides the value are not evaluated, so (car '()) does not fail
+#[test]
+fn short_circuit() {
+    let src = "(define count 0)
+               (define (tick v) (set! count (+ count 1)) v)
+               (display (and #f (car '())))
+               (display (or 1 (car '())))
+               (display (and (tick 1) (tick #f) (tick 3)))
+               (display (or (tick #f) (tick 2) (tick 3)))
+               (display count)";
+    assert_eq!(output("short_circuit", src), "#f1#f24");
+}
+
+#[test]
+fn when_unless() {
+    let src = "(when (< 1 2) (display 'a) (display 'b))
+               (when (> 1 2) (display 'c))
+               (unless (> 1 2) (display 'd) (display 'e))
+               (unless (< 1 2) (display 'f))
+               (display (when (< 1 2) 'g))
+               (display (unless #f 'h))";
+    assert_eq!(output("when_unless", src), "abdegh");
+}
@@ -0,0 +1,122 @@
+mod common;
+
+use std::path::PathBuf;
+
+use common::{compile_error, output};
+
+// the whole rendered error, with the path of the source file replaced by its name
+fn diagnostic(name: &str, src: &str) -> String {
+    let path = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(format!("{}.scm", name));
+    compile_error(name, src).replace(&path.display().to_string(), "test.scm")
+}
+
+#[test]
+fn syntax_errors() {
+    assert_eq!(diagnostic("unexpected_close", "(display 1))"), "\
+error: unexpected ')'
+ --> test.scm:1:12
+  |
+1 | (display 1))
+  |            ^
+");
+    assert_eq!(diagnostic("unexpected_end", "(define (f x)\n  (+ x 1)"), "\
+error: unexpected end of input
+ --> test.scm:2:10
+  |
+2 |   (+ x 1)
+  |          ^
+");
+    assert_eq!(diagnostic("expected_identifier", "(define 1 2)"), "\
+error: expected identifier
+ --> test.scm:1:9
+  |
+1 | (define 1 2)
+  |         ^
+");
+    assert_eq!(diagnostic("expected_datum", "(quote)"), "\
+error: expected datum
+ --> test.scm:1:7
+  |
+1 | (quote)
+  |       ^
+");
+}
+
+#[test]
+fn lexical_errors() {
+    assert_eq!(diagnostic("unterminated_string", "(display \"abc"), "\
+error: unterminated string
+ --> test.scm:1:10
+  |
+1 | (display \"abc
+  |          ^
+");
+    assert_eq!(diagnostic("invalid_escape", "(display \"abc\\q\")"), "\
+error: invalid escape sequence
+ --> test.scm:1:14
+  |
+1 | (display \"abc\\q\")
+  |              ^^
+");
+}
+
+// block comments nest, and a datum comment skips the datum after it
+#[test]
+fn comments() {
+    let src = "#| outer #| inner |# still outer |#
+               (display 1) #;(display 2) #; (display
+               3) (display #;4 5)
+               ; a line comment (display 6)
+               (display (list 1 #;2 #; #;(3) 4 5))";
+    assert_eq!(output("comments", src), "15(1 5)");
+}
+
+#[test]
+fn comment_errors() {
+    assert_eq!(diagnostic("unterminated_block_comment", "(display 1)\n#| a #| b |#\n"), "\
+error: unterminated block comment
+ --> test.scm:2:1
+  |
+2 | #| a #| b |#
+  | ^^
+");
+    assert_eq!(diagnostic("missing_datum", "(display 1) #;"), "\
+error: expected datum after '#;'
+ --> test.scm:1:13
+  |
+1 | (display 1) #;
+  |             ^^
+");
+}
+
+// the gutter is as wide as the number of the line
+#[test]
+fn undefined_variable() {
+    let src = format!("{}(display\n\tundefined-var)", "\n".repeat(10));
+    assert_eq!(diagnostic("undefined_variable", &src), "\
+error: variable 'undefined-var' is undefined
+  --> test.scm:12:2
+   |
+12 | \tundefined-var)
+   | \t^^^^^^^^^^^^^
+");
+}
+
+// a body needs an expression after its definitions to give the value
+#[test]
+fn empty_bodies() {
+    assert_eq!(diagnostic("empty_let", "(let () )"), "\
+error: expected expression
+ --> test.scm:1:9
+  |
+1 | (let () )
+  |         ^
+");
+    for (name, src) in [("empty_named_let", "(let loop ((i 0)))"), ("empty_lambda", "((lambda ()))"),
+                        ("empty_let_star", "(let* ((a 1)))"), ("empty_letrec", "(letrec ((a 1)))"),
+                        ("only_definitions", "((lambda () (define x 1)))"), ("empty_define", "(define (f x))"),
+                        ("empty_curried_define", "(define ((f x) y))")] {
+        let err = diagnostic(name, src);
+        assert!(err.starts_with("error: expected expression\n"), "{}", err);
+    }
+}
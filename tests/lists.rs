@@ -0,0 +1,104 @@
+mod common;
+
+use common::{error, output, values};
+
+#[test]
+fn constructors() {
+    let lines = values("constructors", &[
+        "'()",
+        "(list)",
+        "(list 1 (list 2) \"three\")",
+        "(append)",
+        "(append '(1))",
+        "(append '(1 2) '() '(3) 4)",
+        "`(0 ,@(list 1 2) 3)",
+        "(reverse '(1 (2 3) 4))",
+        "(reverse '())",
+    ]);
+    assert_eq!(lines, ["()", "()", "(1 (2) \"three\")", "()", "(1)", "(1 2 3 . 4)", "(0 1 2 3)", "(4 (2 3) 1)", "()"]);
+}
+
+#[test]
+fn accessors() {
+    let lines = values("accessors", &[
+        "(length '())",
+        "(length '(1 2 3))",
+        "(list-tail '(1 2 3) 1)",
+        "(list-tail '(1 2 3) 3)",
+        "(list-ref '(a b c) 2)",
+        "(cadr '(1 2 3))",
+        "(cddr '(1 2 3))",
+        "(caar '((1) 2))",
+        "(caddr '(1 2 3))",
+        "(cdadr '(1 (2 3)))",
+        "(cadddr '(1 2 3 4))",
+    ]);
+    assert_eq!(lines, ["0", "3", "(2 3)", "()", "c", "2", "(3)", "1", "3", "(3)", "4"]);
+}
+
+#[test]
+fn searching() {
+    let lines = values("searching", &[
+        "(memq 'c '(a b c d))",
+        "(memq 'e '(a b c d))",
+        "(memv 2 '(1 2 3))",
+        "(member '(1) '(0 (1) 2))",
+        "(member \"b\" '(\"a\" \"b\"))",
+        "(assq 'b '((a . 1) (b . 2)))",
+        "(assv 3 '((1 . 2)))",
+        "(assoc \"b\" '((\"a\" . 1) (\"b\" . 2)))",
+    ]);
+    assert_eq!(lines, ["(c d)", "#f", "(2 3)", "((1) 2)", "(\"b\")", "(b . 2)", "#f", "(\"b\" . 2)"]);
+}
+
+#[test]
+fn higher_order() {
+    let lines = values("higher_order", &[
+        "(map (lambda (x) (* x x)) '(1 2 3))",
+        "(map + '(1 2 3) '(10 20))",
+        "(map car '())",
+        "(filter odd? '(1 2 3 4 5))",
+        "(fold-left cons '() '(1 2 3))",
+        "(fold-left (lambda (acc x y) (+ acc (* x y))) 0 '(1 2 3) '(4 5 6))",
+        "(fold-right cons '() '(1 2 3))",
+        "(fold-right list 'z '(1 2) '(3 4 5))",
+    ]);
+    assert_eq!(lines, ["(1 4 9)", "(11 22)", "()", "(1 3 5)", "(((() . 1) . 2) . 3)", "32", "(1 2 3)", "(1 3 (2 4 z))"]);
+
+    let src = "(for-each (lambda (x y) (display x) (display y)) '(a b c) '(1 2 3))";
+    assert_eq!(output("for_each", src), "a1b2c3");
+}
+
+#[test]
+fn mutation() {
+    let src = "(define p (list 1 2 3)) (set-car! p 10) (set-cdr! (cddr p) (list 4)) (write p)";
+    assert_eq!(output("mutation", src), "(10 2 3 4)");
+    assert_eq!(error("constant_car_error", "(set-car! '(1 2) 3)"), "error: set-car!: attempt to modify a constant (1 2)\n");
+    assert_eq!(error("constant_cdr_error", "(define x '(1 2)) (set-cdr! (cdr x) (list 5 6))"),
+               "error: set-cdr!: attempt to modify a constant (2)\n");
+}
+
+// the routines keep their lists on the stack while the callbacks allocate
+#[test]
+fn long_lists() {
+    let src = "(define (iota n) (let loop ((i n) (acc '())) (if (= i 0) acc (loop (- i 1) (cons i acc)))))
+               (define l (iota 100000))
+               (write (length (map (lambda (x) (list x x)) l)))
+               (newline)
+               (write (length (fold-right (lambda (x acc) (cons (list x) acc)) '() l)))
+               (newline)
+               (write (fold-left + 0 (filter even? (append l (reverse l)))))";
+    assert_eq!(output("long_lists", src), "100000\n100000\n5000100000");
+}
+
+#[test]
+fn errors() {
+    assert_eq!(error("cadr_error", "(cadr '(1))"), "error: cadr: wrong type argument ()\n");
+    assert_eq!(error("length_error", "(length '(1 . 2))"), "error: length: wrong type argument (1 . 2)\n");
+    assert_eq!(error("circular_length_error", "(define l (list 1 2 3)) (set-cdr! (cddr l) l) (length l)"),
+               "error: length: wrong type argument, circular list\n");
+    assert_eq!(error("list_ref_error", "(list-ref '(1) 1)"), "error: list-ref: index out of range 1\n");
+    assert_eq!(error("map_error", "(map 1 '(1))"), "error: attempt to call a non-procedure 1\n");
+    assert_eq!(error("map_arity_error", "(map (lambda (x y) x) '(1))"),
+               "error: #<procedure>: wrong number of arguments, expected 2, given 1\n");
+}
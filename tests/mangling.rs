@@ -0,0 +1,31 @@
+mod common;
+
+use common::output;
+
+#[test]
+fn runtime_names() {
+    let src = "(define add 1) (define main 2) (define cons 3) (define exit 4) (define rt_alloc 5)
+               (display (+ add main)) (display exit) (display rt_alloc) (display cons)";
+    assert_eq!(output("runtime_names", src), "3453");
+}
+
+#[test]
+fn special_characters() {
+    let src = "(define (list->vector x) (+ x 1)) (define (null? x) (zero? x)) (define $%&*/:<=>?^_~ 7)
+               (display (list->vector 1)) (display (null? 0)) (display $%&*/:<=>?^_~)";
+    assert_eq!(output("special_characters", src), "2#t7");
+}
+
+#[test]
+fn legal_identifiers() {
+    let src = "(define a.b 1) (define x@y 2) (define |a b| 3) (define ... 4) (define |x\\|y| 5)
+               (display (list a.b x@y |a b| ... |x\\|y| |a.b|)) (display '(1 . 2))";
+    assert_eq!(output("legal_identifiers", src), "(1 2 3 4 5 1)(1 . 2)");
+}
+
+#[test]
+fn escaped_names() {
+    let src = "(define a_b 1) (define g_a_5fb 2) (define a-b 3) (define string_0 4)
+               (display a_b) (display g_a_5fb) (display a-b) (display string_0)";
+    assert_eq!(output("escaped_names", src), "1234");
+}
@@ -0,0 +1,83 @@
+mod common;
+
+use common::values;
+
+#[test]
+fn type_predicates() {
+    let lines = values("type_predicates", &[
+        "(cons (null? '()) (cons (null? '(1)) (null? #f)))",
+        "(cons (pair? '(1)) (cons (pair? '(1 . 2)) (cons (pair? '()) (pair? #(1)))))",
+        "(cons (number? 1) (cons (number? (- 5)) (cons (number? #\\a) (number? \"1\"))))",
+        "(cons (integer? 0) (integer? 'a))",
+        "(cons (boolean? #t) (cons (boolean? #f) (cons (boolean? '()) (boolean? 0))))",
+        "(cons (procedure? car) (cons (procedure? (lambda (x) x)) (procedure? 'car)))",
+        "(cons (symbol? 'a) (cons (symbol? \"a\") (symbol? #\\a)))",
+        "(cons (string? \"\") (string? 'a))",
+        "(cons (char? #\\a) (cons (char? #\\space) (cons (char? 97) (char? \"a\"))))",
+        "(cons (vector? #()) (cons (vector? #(1 2)) (vector? '(1 2))))",
+    ]);
+    assert_eq!(lines, [
+        "(#t #f . #f)",
+        "(#t #t #f . #f)",
+        "(#t #t #f . #f)",
+        "(#t . #f)",
+        "(#t #t #f . #f)",
+        "(#t #t . #f)",
+        "(#t #f . #f)",
+        "(#t . #f)",
+        "(#t #t #f . #f)",
+        "(#t #t . #f)",
+    ]);
+}
+
+#[test]
+fn lists() {
+    let lines = values("lists", &[
+        "(list? '())",
+        "(list? '(1 2 3))",
+        "(list? '(1 2 . 3))",
+        "(list? 1)",
+        "(list? #(1))",
+        "(let ((x (list 1 2 3))) (set-cdr! (cddr x) x) (list? x))",
+    ]);
+    assert_eq!(lines, ["#t", "#t", "#f", "#f", "#f", "#f"]);
+}
+
+#[test]
+fn not() {
+    let lines = values("not", &["(not #f)", "(not #t)", "(not '())", "(not 0)"]);
+    assert_eq!(lines, ["#t", "#f", "#f", "#f"]);
+}
+
+#[test]
+fn equivalence() {
+    let lines = values("equivalence", &[
+        "(eq? 'a 'a)",
+        "(eq? '() '())",
+        "(eq? (cons 1 2) (cons 1 2))",
+        "(eqv? 100 100)",
+        "(eqv? #\\a #\\a)",
+        "(eqv? \"a\" (string-copy \"a\"))",
+        "(let ((p (cons 1 2))) (eqv? p p))",
+    ]);
+    assert_eq!(lines, ["#t", "#t", "#f", "#t", "#t", "#f", "#t"]);
+}
+
+#[test]
+fn equal() {
+    let lines = values("equal", &[
+        "(equal? '(1 (2 #(3 \"x\")) . 4) (cons 1 (cons (cons 2 (cons #(3 \"x\") '())) 4)))",
+        "(equal? '(1 (2 #(3 \"x\")) . 4) '(1 (2 #(3 \"y\")) . 4))",
+        "(equal? '(1 2) '(1 2 3))",
+        "(equal? \"abc\" (string-copy \"abc\"))",
+        "(equal? \"abc\" \"abd\")",
+        "(equal? \"abc\" \"ab\")",
+        "(equal? \"\" (string-copy \"\"))",
+        "(equal? #(1 2) #(1 2 3))",
+        "(equal? #() #())",
+        "(equal? \"a\" #(1))",
+        "(equal? 'a 'a)",
+        "(equal? 1 #\\a)",
+    ]);
+    assert_eq!(lines, ["#t", "#f", "#f", "#t", "#f", "#f", "#t", "#f", "#t", "#f", "#t", "#f"]);
+}
@@ -0,0 +1,60 @@
+mod common;
+
+use common::{compile_error, error, output, values};
+
+#[test]
+fn strings() {
+    let lines = values("strings", &[
+        "\"a\\tb\\\\c\\\"d\"",
+        "(string-length \"hello\")",
+        "(string-ref \"abc\" 1)",
+        "(substring \"hello\" 1 3)",
+        "(string-append)",
+        "(string-append \"a\" \"bc\" \"\" \"d\")",
+        "(make-string 3 #\\z)",
+        "(make-string 2)",
+        "(string #\\a #\\b)",
+        "(string)",
+        "(list (string=? \"ab\" (string #\\a #\\b)) (string<? \"ab\" \"abc\") (string<? \"b\" \"a\"))",
+    ]);
+    assert_eq!(lines, ["\"a\\tb\\\\c\\\"d\"", "5", "#\\b", "\"el\"", "\"\"", "\"abcd\"", "\"zzz\"", "\"  \"", "\"ab\"",
+                       "\"\"", "(#t #t #f)"]);
+}
+
+// a character above \x7f takes a byte in a string and is written in utf-8
+#[test]
+fn latin1_characters() {
+    let lines = values("latin1_characters", &[
+        "(string-length \"é\")",
+        "(string-ref \"aé\" 1)",
+        "(string #\\xff)",
+        "(string->symbol \"é\")",
+    ]);
+    assert_eq!(lines, ["1", "#\\é", "\"ÿ\"", "|é|"]);
+    assert_eq!(error("latin1_error", "(error \"café\" 1)"), "error: café 1\n");
+
+    let err = compile_error("wide_string", "(display \"λ\")");
+    assert!(err.starts_with("error: character out of range in string\n"), "{}", err);
+    let err = compile_error("wide_identifier", "(define |λ| 1)");
+    assert!(err.starts_with("error: character out of range in identifier\n"), "{}", err);
+}
+
+#[test]
+fn mutation() {
+    let src = "(define s (make-string 3 #\\a)) (string-set! s 1 #\\b) (display s)
+               (display (string-append (make-string 5000 #\\x) s))";
+    assert_eq!(output("string_mutation", src).len(), 5006);
+    assert_eq!(output("string_set", "(define s (string #\\a #\\b)) (string-set! s 0 #\\c) (display s)"), "cb");
+
+    assert_eq!(error("constant_string_error", "(string-set! \"abc\" 0 #\\x)"),
+               "error: string-set!: attempt to modify a constant \"abc\"\n");
+    assert_eq!(error("wide_char_error", "(string-set! (make-string 1) 0 #\\λ)"),
+               "error: string-set!: value out of range #\\λ\n");
+    assert_eq!(error("make_string_char_error", "(make-string 1 #\\x100)"),
+               "error: make-string: value out of range #\\Ā\n");
+    assert_eq!(error("string_char_error", "(string #\\a 1)"), "error: string: wrong type argument 1\n");
+    assert_eq!(error("string_append_error", "(string-append \"a\" 'b)"),
+               "error: string-append: wrong type argument b\n");
+    assert_eq!(error("make_string_arity_error", "(make-string 1 #\\a 2)"),
+               "error: make-string: wrong number of arguments, expected 2, given 3\n");
+}
@@ -0,0 +1,46 @@
+mod common;
+
+use common::{output, values};
+
+#[test]
+fn symbols() {
+    let lines = values("symbols", &[
+        "'abc",
+        "(list 'a.b 'x@y '+ '- '... '+a '+.5 '->x)",
+        "(symbol->string 'abc)",
+        "(eq? (string->symbol \"a b\") '|a b|)",
+        "(symbol? '|1|)",
+    ]);
+    assert_eq!(lines, ["abc", "(a.b x@y + - ... +a +.5 ->x)", "\"abc\"", "#t", "#t"]);
+}
+
+// the name of a symbol is its own string, apart from the string it was made from and the
+// strings given by symbol->string
+#[test]
+fn symbol_names() {
+    let src = "(define s (make-string 1 #\\q))
+               (define y (string->symbol s))
+               (string-set! s 0 #\\b)
+               (write y)
+               (write (eq? (string->symbol \"q\") y))
+               (define n (symbol->string y))
+               (string-set! n 0 #\\c)
+               (write (list n y (symbol->string y) (eq? (string->symbol \"q\") y)))";
+    assert_eq!(output("symbol_names", src), "q#t(\"c\" q \"q\" #t)");
+}
+
+// a written symbol that would not read back as itself is between bars
+#[test]
+fn written_symbols() {
+    let lines = values("written_symbols", &[
+        "'|a b|",
+        "(string->symbol \"\")",
+        "(list '|1x| '|-5| '|@x| (string->symbol \".\") '|#t|)",
+        "'|x\\|y\\\\z|",
+        "(string->symbol \"é\")",
+    ]);
+    assert_eq!(lines, ["|a b|", "||", "(|1x| |-5| |@x| |.| |#t|)", "|x\\|y\\\\z|", "|é|"]);
+
+    assert_eq!(output("displayed_symbols", "(display '|a b|) (display (string->symbol \"\")) (display '|x\\|y|)"),
+               "a bx|y");
+}
@@ -0,0 +1,31 @@
+mod common;
+
+use common::output;
+
+// ten million calls in tail position would overflow the stack if each took a frame
+const LOOPS: &str = "
+(define n 10000000)
+(define (count i) (if (= i 0) 'done (count (- i 1))))
+(define (count-cond i acc)
+  (cond ((= i 0) acc)
+        ((even? i) (count-cond (- i 1) (+ acc 1)))
+        (else (let ((j (- i 1))) (count-cond j acc)))))
+(define (count-let i)
+  (let ((j (- i 1)))
+    (if (< j 0) 'let (let* ((k j)) (begin (count-let k))))))
+(define (my-even? i) (if (= i 0) #t (my-odd? (- i 1))))
+(define (my-odd? i) (if (= i 0) #f (my-even? (- i 1))))
+(define (count-or i) (or (= i 0) (count-or (- i 1))))
+(write (count n))
+(write (count-cond n 0))
+(write (count-let n))
+(write (list (my-even? n) (my-odd? n)))
+(write (count-or n))
+(write (let loop ((i 0)) (if (= i n) i (loop (+ i 1)))))
+(write ((lambda (f) (f f n)) (lambda (self i) (if (= i 0) 'lambda (self self (- i 1))))))
+";
+
+#[test]
+fn constant_stack() {
+    assert_eq!(output("constant_stack", LOOPS), "done5000000let(#t #f)#t10000000lambda");
+}
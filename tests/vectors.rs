@@ -0,0 +1,96 @@
+mod common;
+
+use common::{error, output, values};
+
+#[test]
+fn vectors() {
+    let lines = values("vectors", &[
+        "#(1 \"two\" #\\3 (4))",
+        "(make-vector 3 'a)",
+        "(make-vector 0)",
+        "(vector 1 (vector 2) '(3))",
+        "(vector)",
+        "(vector-length #(1 2 3))",
+        "(vector-ref #(a b c) 2)",
+        "(vector->list #(1 2 3))",
+        "(vector->list #())",
+        "(list->vector '(1 (2) 3))",
+        "(list->vector '())",
+    ]);
+    assert_eq!(lines, ["#(1 \"two\" #\\3 (4))", "#(a a a)", "#()", "#(1 #(2) (3))", "#()", "3", "c", "(1 2 3)", "()",
+                       "#(1 (2) 3)", "#()"]);
+}
+
+#[test]
+fn vector_mutation() {
+    let src = "(define v (make-vector 3 0)) (vector-set! v 1 'x) (write v) (vector-fill! v 7) (write v)";
+    assert_eq!(output("vector_mutation", src), "#(0 x 0)#(7 7 7)");
+
+    assert_eq!(error("constant_vector_error", "(vector-set! #(1 2) 0 3)"),
+               "error: vector-set!: attempt to modify a constant #(1 2)\n");
+    assert_eq!(error("constant_fill_error", "(vector-fill! '#(1) 0)"),
+               "error: vector-fill!: attempt to modify a constant #(1)\n");
+    assert_eq!(error("constant_bytevector_error", "(bytevector-u8-set! #u8(1) 0 2)"),
+               "error: bytevector-u8-set!: attempt to modify a constant #u8(1)\n");
+}
+
+#[test]
+fn vector_map() {
+    let lines = values("vector_map", &[
+        "(vector-map (lambda (x) (* x x)) #(1 2 3))",
+        "(vector-map + #(1 2 3) #(10 20))",
+        "(vector-map car #())",
+    ]);
+    assert_eq!(lines, ["#(1 4 9)", "#(11 22)", "#()"]);
+
+    let src = "(vector-for-each (lambda (x y) (display x) (display y)) #(a b) #(1 2 3))";
+    assert_eq!(output("vector_for_each", src), "a1b2");
+}
+
+#[test]
+fn bytevectors() {
+    let lines = values("bytevectors", &[
+        "#u8(0 1 255)",
+        "#u8()",
+        "(bytevector 1 2)",
+        "(make-bytevector 3 9)",
+        "(make-bytevector 2)",
+        "(bytevector-length #u8(1 2 3))",
+        "(bytevector-u8-ref #u8(5 6) 1)",
+        "(let ((b (bytevector-copy #u8(1 2 3)))) (bytevector-u8-set! b 1 200) b)",
+        "(bytevector? #u8(1))",
+        "(bytevector? #(1))",
+        "(vector? #u8(1))",
+        "(equal? (bytevector 1 2) #u8(1 2))",
+        "(equal? #u8(1 2) #u8(1 3))",
+        "'#(1 #u8(2))",
+    ]);
+    assert_eq!(lines, ["#u8(0 1 255)", "#u8()", "#u8(1 2)", "#u8(9 9 9)", "#u8(0 0)", "3", "6", "#u8(1 200 3)", "#t", "#f",
+                       "#f", "#t", "#f", "#(1 #u8(2))"]);
+}
+
+// the vectors made while mapping survive the collections
+#[test]
+fn long_vectors() {
+    let src = "(define (loop i v) (if (= i 0) v (loop (- i 1) (vector-map (lambda (x) (+ x 1)) v))))
+               (write (vector-ref (loop 50000 (make-vector 10 0)) 9))
+               (write (vector-length (make-vector 300000 1)))";
+    assert_eq!(output("long_vectors", src), "50000300000");
+}
+
+#[test]
+fn errors() {
+    assert_eq!(error("vector_ref_error", "(vector-ref #(1 2) 2)"), "error: vector-ref: index out of range 2\n");
+    assert_eq!(error("vector_type_error", "(vector-length '(1))"), "error: vector-length: wrong type argument (1)\n");
+    assert_eq!(error("make_vector_error", "(make-vector 1 2 3)"),
+               "error: make-vector: wrong number of arguments, expected 2, given 3\n");
+    assert_eq!(error("byte_error", "(bytevector 1 256)"), "error: bytevector: value out of range 256\n");
+    assert_eq!(error("u8_set_error", "(bytevector-u8-set! (make-bytevector 1) 0 256)"),
+               "error: bytevector-u8-set!: value out of range 256\n");
+    assert_eq!(error("make_vector_length_error", "(make-vector 1152921504606846975 0)"),
+               "error: make-vector: index out of range 1152921504606846975\n");
+    assert_eq!(error("make_bytevector_length_error", "(make-bytevector 1152921504606846975)"),
+               "error: make-bytevector: index out of range 1152921504606846975\n");
+    assert_eq!(error("make_vector_negative_error", "(make-vector -1)"), "error: make-vector: index out of range -1\n");
+    assert_eq!(error("u8_ref_error", "(bytevector-u8-ref #u8(1) 1)"), "error: bytevector-u8-ref: index out of range 1\n");
+}